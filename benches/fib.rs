@@ -0,0 +1,53 @@
+//! Benchmark recursive fibonacci with and without inlined arithmetic
+//!
+//! Run with `cargo bench --bench fib`. Both builds use the same C optimization level,
+//! so the difference is only in the generated C.
+
+use std::{
+    env,
+    process::{self, Command},
+    time::{Duration, Instant},
+};
+
+use kin::compile::{BuildArgs, OptLevel};
+use kin::Compiler;
+
+const SOURCE: &str = "fib n = n < 2 and n or fib (n - 1) + fib (n - 2)\nprintln (fib 30)\n";
+const RUNS: u32 = 5;
+
+fn bench(name: &str, debug: bool) -> Duration {
+    let dir = env::temp_dir().join(format!("kin-bench-{}-{}", process::id(), name));
+    let build_args = BuildArgs {
+        debug,
+        opt_level: Some(OptLevel::O2),
+        ..Default::default()
+    };
+    let compiler = Compiler::new()
+        .build_args(build_args)
+        .out_dir(&dir)
+        .output(dir.join(name));
+    let items = compiler.check(SOURCE).unwrap();
+    compiler.write_c(items).unwrap();
+    let executable = compiler.compile_c().unwrap();
+    let mut best = Duration::MAX;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        let output = Command::new(&executable).output().unwrap();
+        best = best.min(start.elapsed());
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "832040");
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+    best
+}
+
+fn main() {
+    let call_bin_op = bench("call_bin_op", true);
+    let inlined = bench("inlined", false);
+    println!("fib 30, best of {} runs", RUNS);
+    println!("  kin_call_bin_op: {:?}", call_bin_op);
+    println!("  inlined:         {:?}", inlined);
+    println!(
+        "  speedup:         {:.2}x",
+        call_bin_op.as_secs_f64() / inlined.as_secs_f64()
+    );
+}
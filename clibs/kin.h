@@ -1,7 +1,10 @@
 #ifndef KIN_VALUE_H
 #define KIN_VALUE_H
 
+// Everything here is static so that C sources linked with `--c-source` can include it too
+
 #include <math.h>
+#include <setjmp.h>
 #include <stdio.h>
 #include <stdbool.h>
 #include <string.h>
@@ -16,7 +19,7 @@ static size_t kin_call_stack_capacity = 0;
 #define min(a, b) a < b ? a : b
 #endif
 
-void kin_push_call_stack(char* call_string) {
+static void kin_push_call_stack(char* call_string) {
     size_t new_len = kin_call_stack_len + 1;
     if (new_len >= kin_call_stack_capacity) {
         kin_call_stack_capacity = kin_call_stack_capacity == 0 ? 1 : kin_call_stack_capacity * 2;
@@ -26,11 +29,56 @@ void kin_push_call_stack(char* call_string) {
     kin_call_stack_len = new_len;
 }
 
-void kin_pop_call_stack() {
+static void kin_pop_call_stack() {
     kin_call_stack_len -= 1;
 }
 
-void kin_panic_impl(char* message) {
+// Test harness state
+static bool kin_testing = false;
+static bool kin_test_failed = false;
+static jmp_buf kin_test_jmp;
+static char* kin_test_name = NULL;
+static size_t kin_test_stack_len = 0;
+static size_t kin_tests_passed = 0;
+static size_t kin_tests_failed = 0;
+
+static void kin_test_start(char* name) {
+    kin_testing = true;
+    kin_test_failed = false;
+    kin_test_name = name;
+    kin_test_stack_len = kin_call_stack_len;
+}
+
+static void kin_test_pass() {
+    printf("test %s ... ok\n", kin_test_name);
+    kin_tests_passed++;
+    kin_testing = false;
+}
+
+static void kin_test_fail_start() {
+    if (kin_test_failed) return;
+    printf("test %s ... FAILED\n", kin_test_name);
+    kin_test_failed = true;
+    kin_tests_failed++;
+}
+
+static int kin_test_report() {
+    printf("\ntest result: %s. %zu passed; %zu failed\n",
+        kin_tests_failed ? "FAILED" : "ok", kin_tests_passed, kin_tests_failed);
+    return kin_tests_failed ? EXIT_FAILURE : EXIT_SUCCESS;
+}
+
+static void kin_panic_impl(char* message) {
+    // Recover from the panic if a test is running
+    if (kin_testing) {
+        kin_test_fail_start();
+        if (*message) printf("    %s\n", message);
+        if (kin_call_stack_len > kin_test_stack_len)
+            printf("    failed at %s\n", kin_call_stack[kin_call_stack_len - 1]);
+        kin_testing = false;
+        kin_call_stack_len = kin_test_stack_len;
+        longjmp(kin_test_jmp, 1);
+    }
     printf("%s\n", message);
     for (int i = kin_call_stack_len - 1; i >= 0; i--)
         printf("at %s\n", kin_call_stack[i]);
@@ -74,9 +122,9 @@ static char* kin_type_names[] = {
 typedef struct KinValue KinValue;
 
 // The function pointer type for regular Kin functions
-typedef KinValue(*KinFn)(uint8_t, KinValue* args);
+typedef KinValue(*KinFn)(uint32_t, KinValue* args);
 // The function pointer type for Kin closures
-typedef KinValue(*KinClosureFn)(uint8_t, KinValue* args, KinValue* captures);
+typedef KinValue(*KinClosureFn)(uint32_t, KinValue* args, KinValue* captures);
 
 // A Kin closure
 typedef struct KinFunction {
@@ -120,41 +168,49 @@ static KinValue KIN_TRUE = new_bool(true);
 // The false Kin value
 static KinValue KIN_FALSE = new_bool(false);
 
-KinValue kin_head(KinValue val) {
+static KinValue kin_head(KinValue val) {
     val.mom = NULL;
     val.dad = NULL;
     return val;
 }
 
-KinValue kin_mom(uint8_t count, KinValue* args) {
+static KinValue kin_mom(uint32_t count, KinValue* args) {
     KinValue val = count >= 1 ? args[0] : KIN_NIL;
     return val.mom ? *val.mom : KIN_NIL;
 }
 
-KinValue kin_dad(uint8_t count, KinValue* args) {
+static KinValue kin_dad(uint32_t count, KinValue* args) {
     KinValue val = count >= 1 ? args[0] : KIN_NIL;
     return val.dad ? *val.dad : KIN_NIL;
 }
 
-void kin_binary_type_panic(char* message, KinType a, KinType b) {
+static void kin_binary_type_panic(char* message, KinType a, KinType b) {
     char str[256];
     sprintf(str, message, kin_type_names[a], kin_type_names[b]);
     kin_panic_impl(str);
 }
 
-void kin_unary_type_panic(char* message, KinType ty) {
+static void kin_unary_type_panic(char* message, KinType ty) {
     char str[256];
     sprintf(str, message, kin_type_names[ty]);
     kin_panic_impl(str);
 }
 
+// Panic unless a value has one of the types in a mask of type tags
+static void kin_check_type(KinValue val, uint8_t types, char* expected, char* name) {
+    if (types & (1 << val.type)) return;
+    char str[256];
+    snprintf(str, sizeof str, "Expected %s to be %s, but it is %s", name, expected, kin_type_names[val.type]);
+    kin_panic_impl(str);
+}
+
 // Create a new Kin error from a value
-KinValue kin_error(uint8_t count, KinValue* inner) {
+static KinValue kin_error(uint32_t count, KinValue* inner) {
     return new_val(Error, inner);
 }
 
 // Call a Kin function or closure value
-KinValue kin_call(KinValue val, int count, KinValue* args, char* call_site) {
+static KinValue kin_call(KinValue val, uint32_t count, KinValue* args, char* call_site) {
     kin_push_call_stack(call_site);
     KinValue res;
     switch (val.type) {
@@ -172,7 +228,7 @@ KinValue kin_call(KinValue val, int count, KinValue* args, char* call_site) {
     }
 }
 
-KinValue kin_print(uint8_t count, KinValue* args) {
+static KinValue kin_print(uint32_t count, KinValue* args) {
     KinValue val = count >= 1 ? args[0] : KIN_NIL;
     switch (val.type) {
     case Nil:
@@ -210,27 +266,31 @@ KinValue kin_print(uint8_t count, KinValue* args) {
     return val;
 }
 
-KinValue kin_println(uint8_t count, KinValue* args) {
+static KinValue kin_println(uint32_t count, KinValue* args) {
     KinValue res = kin_print(count, args);
     printf("\n");
     return res;
 }
 
-KinValue kin_panic(uint8_t count, KinValue* args) {
-    printf("\nKin panicked:\n");
+static KinValue kin_panic(uint32_t count, KinValue* args) {
+    if (kin_testing) {
+        kin_test_fail_start();
+        printf("    ");
+    }
+    else printf("\nKin panicked:\n");
     kin_println(count, args);
     kin_panic_impl("");
     return KIN_NIL;
 }
 
-KinValue kin_call_bin_op(KinValue f(KinValue, KinValue), KinValue a, KinValue b, char* call_site) {
+static KinValue kin_call_bin_op(KinValue f(KinValue, KinValue), KinValue a, KinValue b, char* call_site) {
     kin_push_call_stack(call_site);
     KinValue res = f(a, b);
     kin_pop_call_stack();
     return res;
 }
 
-KinValue kin_add(KinValue a, KinValue b) {
+static KinValue kin_add(KinValue a, KinValue b) {
     switch (a.type) {
     case Int:
         switch (b.type) {
@@ -254,7 +314,7 @@ KinValue kin_add(KinValue a, KinValue b) {
     return KIN_NIL;
 }
 
-KinValue kin_sub(KinValue a, KinValue b) {
+static KinValue kin_sub(KinValue a, KinValue b) {
     switch (a.type) {
     case Int:
         switch (b.type) {
@@ -278,7 +338,7 @@ KinValue kin_sub(KinValue a, KinValue b) {
     return KIN_NIL;
 }
 
-KinValue kin_mul(KinValue a, KinValue b) {
+static KinValue kin_mul(KinValue a, KinValue b) {
     switch (a.type) {
     case Int:
         switch (b.type) {
@@ -302,7 +362,7 @@ KinValue kin_mul(KinValue a, KinValue b) {
     return KIN_NIL;
 }
 
-KinValue kin_div(KinValue a, KinValue b) {
+static KinValue kin_div(KinValue a, KinValue b) {
     switch (a.type) {
     case Int:
         switch (b.type) {
@@ -326,7 +386,7 @@ KinValue kin_div(KinValue a, KinValue b) {
     return KIN_NIL;
 }
 
-KinValue kin_rem(KinValue a, KinValue b) {
+static KinValue kin_rem(KinValue a, KinValue b) {
     switch (a.type) {
     case Int:
         switch (b.type) {
@@ -350,13 +410,13 @@ KinValue kin_rem(KinValue a, KinValue b) {
     return KIN_NIL;
 }
 
-#define bin_fn(f) KinValue f## _fn(uint8_t count, KinValue* args) {  \
+#define bin_fn(f) static KinValue f## _fn(uint32_t count, KinValue* args) {  \
     KinValue left = count >= 1 ? args[0] : KIN_NIL; \
     KinValue right = count >= 2 ? args[1] : KIN_NIL; \
     return f(left, right); \
 }
 
-bool kin_eq_impl(KinValue a, KinValue b) {
+static bool kin_eq_impl(KinValue a, KinValue b) {
     switch (a.type) {
     case Nil: return b.type == Nil;
     case Bool: return b.type == Bool && a.data.Bool == b.data.Bool;
@@ -390,7 +450,7 @@ bool kin_eq_impl(KinValue a, KinValue b) {
     }
 }
 
-bool kin_lt_impl(KinValue a, KinValue b) {
+static bool kin_lt_impl(KinValue a, KinValue b) {
     switch (a.type) {
     case Bool: if (b.type == Bool) return a.data.Bool < b.data.Bool; break;
     case Int:
@@ -424,7 +484,7 @@ bool kin_lt_impl(KinValue a, KinValue b) {
     return false;
 }
 
-bool kin_gt_impl(KinValue a, KinValue b) {
+static bool kin_gt_impl(KinValue a, KinValue b) {
     switch (a.type) {
     case Bool: if (b.type == Bool) return a.data.Bool > b.data.Bool; break;
     case Int:
@@ -458,27 +518,27 @@ bool kin_gt_impl(KinValue a, KinValue b) {
     return false;
 }
 
-KinValue kin_eq(KinValue a, KinValue b) {
+static KinValue kin_eq(KinValue a, KinValue b) {
     return new_bool(kin_eq_impl(a, b));
 }
 
-KinValue kin_neq(KinValue a, KinValue b) {
+static KinValue kin_neq(KinValue a, KinValue b) {
     return new_bool(!kin_eq_impl(a, b));
 }
 
-KinValue kin_lt(KinValue a, KinValue b) {
+static KinValue kin_lt(KinValue a, KinValue b) {
     return new_bool(kin_lt_impl(a, b));
 }
 
-KinValue kin_le(KinValue a, KinValue b) {
+static KinValue kin_le(KinValue a, KinValue b) {
     return new_bool(kin_lt_impl(a, b) || kin_eq_impl(a, b));
 }
 
-KinValue kin_gt(KinValue a, KinValue b) {
+static KinValue kin_gt(KinValue a, KinValue b) {
     return new_bool(kin_gt_impl(a, b));
 }
 
-KinValue kin_ge(KinValue a, KinValue b) {
+static KinValue kin_ge(KinValue a, KinValue b) {
     return new_bool(kin_gt_impl(a, b) || kin_eq_impl(a, b));
 }
 
@@ -494,7 +554,7 @@ bin_fn(kin_le);
 bin_fn(kin_gt);
 bin_fn(kin_ge);
 
-KinValue kin_neg(KinValue val) {
+static KinValue kin_neg(KinValue val) {
     switch (val.type) {
     case Int: return new_int(-val.data.Int);
     case Real: return new_real(-val.data.Real);
@@ -504,17 +564,17 @@ KinValue kin_neg(KinValue val) {
     }
 }
 
-KinValue kin_not(uint8_t count, KinValue* args) {
+static KinValue kin_not(uint32_t count, KinValue* args) {
     KinValue val = count >= 1 ? args[0] : KIN_NIL;
     if (val.type == Bool) return new_bool(!val.data.Bool);
     else return new_bool(val.type == Nil);
 }
 
-bool kin_is_true(KinValue val) {
+static bool kin_is_true(KinValue val) {
     return (val.type == Bool) * val.data.Bool + (val.type != Bool) * (val.type != Nil && val.type != Error);
 }
 
-KinValue kin_assert(uint8_t count, KinValue* args) {
+static KinValue kin_assert(uint32_t count, KinValue* args) {
     KinValue val = count >= 1 ? args[0] : KIN_NIL;
     if (!kin_is_true(val)) {
         if (count >= 2) kin_panic(count - 1, args + 1);
@@ -523,4 +583,55 @@ KinValue kin_assert(uint8_t count, KinValue* args) {
     return val;
 }
 
+// Embedding
+//
+// Constructors and accessors for C programs that call functions exported by `kin build --lib`
+
+static KinValue kin_nil() {
+    return KIN_NIL;
+}
+
+static KinValue kin_bool(bool b) {
+    return new_bool(b);
+}
+
+static KinValue kin_int(long i) {
+    return new_int(i);
+}
+
+static KinValue kin_real(double r) {
+    return new_real(r);
+}
+
+// The string is not copied, so it must outlive the value
+static KinValue kin_string(char* s) {
+    return new_string(s, strlen(s));
+}
+
+static KinType kin_type(KinValue val) {
+    return val.type;
+}
+
+static bool kin_as_bool(KinValue val) {
+    return kin_is_true(val);
+}
+
+static long kin_as_int(KinValue val) {
+    return val.type == Real ? (long)val.data.Real : val.type == Int ? val.data.Int : 0;
+}
+
+static double kin_as_real(KinValue val) {
+    return val.type == Int ? (double)val.data.Int : val.type == Real ? val.data.Real : 0;
+}
+
+// Get a string's bytes, which are not null-terminated, and its length
+static char* kin_as_string(KinValue val, size_t* len) {
+    if (val.type != String) {
+        *len = 0;
+        return NULL;
+    }
+    *len = val.data.String.len;
+    return val.data.String.s;
+}
+
 #endif
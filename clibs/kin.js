@@ -0,0 +1,303 @@
+// The Kin runtime for the JavaScript backend
+//
+// This is pasted at the top of the generated script. It follows kin.h: values are
+// objects with a type, data, and mom and dad links. Ints are BigInts wrapped to 64 bits,
+// so they behave like C's. Functions and closures are JavaScript functions that take
+// each argument separately. Missing arguments are nil.
+"use strict";
+
+// Kin types
+const NIL = 0;
+const BOOL = 1;
+const INT = 2;
+const REAL = 3;
+const STRING = 4;
+const FUNCTION = 5;
+const CLOSURE = 6;
+const ERROR = 7;
+
+const kin_type_names = ["nil", "bool", "int", "real", "string", "function", "function", "error"];
+
+// Output
+
+// A page can define `kinOutput(text)` before loading the script to capture what it prints.
+// Otherwise Node writes to stdout and browsers log each line to the console.
+let kin_line = "";
+function kin_write(text) {
+    if (typeof globalThis.kinOutput === "function") {
+        globalThis.kinOutput(text);
+    } else if (typeof process !== "undefined" && process.stdout) {
+        process.stdout.write(text);
+    } else {
+        const lines = (kin_line + text).split("\n");
+        kin_line = lines.pop();
+        for (const line of lines) console.log(line);
+    }
+}
+
+// Panics
+
+class KinPanic extends Error {}
+
+function kin_panic_impl(message) {
+    kin_write(message + "\n");
+    throw new KinPanic(message);
+}
+
+function kin_binary_type_panic(message, a, b) {
+    kin_panic_impl(`${message} ${kin_type_names[a.type]} and ${kin_type_names[b.type]}`);
+}
+
+// Panic unless a value has one of the types in a mask of type tags
+function kin_check_type(val, types, expected, name) {
+    if (types & (1 << val.type)) return;
+    kin_panic_impl(`Expected ${name} to be ${expected}, but it is ${kin_type_names[val.type]}`);
+}
+
+// Run a program's main function
+function kin_run(main) {
+    try {
+        main();
+    } catch (e) {
+        if (!(e instanceof KinPanic)) throw e;
+        if (typeof process !== "undefined") process.exitCode = 1;
+    }
+}
+
+// Values
+
+function new_val(type, data) {
+    return { type, data, mom: null, dad: null };
+}
+
+const KIN_NIL = new_val(NIL, null);
+const KIN_TRUE = new_val(BOOL, true);
+const KIN_FALSE = new_val(BOOL, false);
+
+function kin_new_bool(b) {
+    return b ? KIN_TRUE : KIN_FALSE;
+}
+
+function kin_new_int(i) {
+    return new_val(INT, BigInt.asIntN(64, i));
+}
+
+function kin_new_real(r) {
+    return new_val(REAL, r);
+}
+
+function kin_new_string(s) {
+    return new_val(STRING, s);
+}
+
+function kin_new_function(f) {
+    return new_val(FUNCTION, f);
+}
+
+function kin_new_closure(f) {
+    return new_val(CLOSURE, f);
+}
+
+function kin_new_tree(left, middle, right) {
+    kin_panic_impl("Trees are not supported by the JavaScript backend");
+}
+
+function kin_with_mom(val, mom) {
+    return { ...val, mom };
+}
+
+function kin_with_dad(dad, val) {
+    return { ...val, dad };
+}
+
+function kin_head(val) {
+    return { ...val, mom: null, dad: null };
+}
+
+function kin_is_true(val) {
+    return val.type === BOOL ? val.data : val.type !== NIL && val.type !== ERROR;
+}
+
+// `and` and `or` only evaluate their right side if they need to
+
+function kin_and(left, right) {
+    return kin_is_true(left) ? right() : left;
+}
+
+function kin_or(left, right) {
+    return kin_is_true(left) ? left : right();
+}
+
+// Call a Kin function or closure value
+function kin_call(val, ...args) {
+    if (val.type === FUNCTION || val.type === CLOSURE) return val.data(...args);
+    kin_panic_impl(`Attempted to call ${kin_type_names[val.type]} value`);
+}
+
+// Operators
+
+function kin_is_number(val) {
+    return val.type === INT || val.type === REAL;
+}
+
+// Apply an operator to two ints, or two numbers as reals, or panic
+function kin_arith(a, b, int, real, message) {
+    if (a.type === INT && b.type === INT) return kin_new_int(int(a.data, b.data));
+    if (kin_is_number(a) && kin_is_number(b)) return kin_new_real(real(Number(a.data), Number(b.data)));
+    kin_binary_type_panic(message, a, b);
+}
+
+function kin_add(a, b) {
+    return kin_arith(a, b, (x, y) => x + y, (x, y) => x + y, "Attempted to add incompatible types");
+}
+
+function kin_sub(a, b) {
+    return kin_arith(a, b, (x, y) => x - y, (x, y) => x - y, "Attempted to subtract incompatible types");
+}
+
+function kin_mul(a, b) {
+    return kin_arith(a, b, (x, y) => x * y, (x, y) => x * y, "Attempted to multiply incompatible types");
+}
+
+function kin_div(a, b) {
+    return kin_arith(a, b, (x, y) => x / y, (x, y) => x / y, "Attempted to divide incompatible types");
+}
+
+function kin_rem(a, b) {
+    return kin_arith(a, b, (x, y) => x % y, (x, y) => x % y, "Attempted to divide incompatible types");
+}
+
+function kin_neg(val) {
+    switch (val.type) {
+        case INT: return kin_new_int(-val.data);
+        case REAL: return kin_new_real(-val.data);
+    }
+    kin_panic_impl(`Attempted to negate ${kin_type_names[val.type]}`);
+}
+
+function kin_eq_impl(a, b) {
+    if (kin_is_number(a) && kin_is_number(b)) {
+        return a.type === b.type ? a.data === b.data : Number(a.data) === Number(b.data);
+    }
+    if (a.type !== b.type) return false;
+    switch (a.type) {
+        case NIL: return true;
+        case ERROR: return kin_eq_impl(a.data, b.data);
+        default: return a.data === b.data;
+    }
+}
+
+// Order two values, returning -1, 0, or 1, or panic if they cannot be compared
+//
+// Unordered reals return NaN, so that every comparison with them is false like in C.
+function kin_compare(a, b) {
+    if (kin_is_number(a) && kin_is_number(b)) {
+        const [x, y] = a.type === b.type ? [a.data, b.data] : [Number(a.data), Number(b.data)];
+        return x < y ? -1 : x > y ? 1 : x === y ? 0 : NaN;
+    }
+    if (a.type === b.type) {
+        switch (a.type) {
+            case BOOL:
+            case STRING:
+                return a.data < b.data ? -1 : a.data > b.data ? 1 : 0;
+            case ERROR:
+                return kin_compare(a.data, b.data);
+        }
+    }
+    kin_binary_type_panic("Attempted to compare incompatible types", a, b);
+}
+
+function kin_eq(a, b) {
+    return kin_new_bool(kin_eq_impl(a, b));
+}
+
+function kin_neq(a, b) {
+    return kin_new_bool(!kin_eq_impl(a, b));
+}
+
+function kin_lt(a, b) {
+    return kin_new_bool(kin_compare(a, b) < 0);
+}
+
+function kin_le(a, b) {
+    return kin_new_bool(kin_compare(a, b) <= 0);
+}
+
+function kin_gt(a, b) {
+    return kin_new_bool(kin_compare(a, b) > 0);
+}
+
+function kin_ge(a, b) {
+    return kin_new_bool(kin_compare(a, b) >= 0);
+}
+
+// Format a real the way the C runtime does
+function kin_format_real(r) {
+    if (Number.isNaN(r)) return "nan";
+    if (!Number.isFinite(r)) return r < 0 ? "-inf" : "inf";
+    const s = r.toFixed(6);
+    let i = s.length - 1;
+    while (s[i] === "0" || s[i] === ".") i--;
+    return s.slice(0, i + 1);
+}
+
+function kin_format(val) {
+    switch (val.type) {
+        case NIL: return "nil";
+        case BOOL: return val.data ? "true" : "false";
+        case INT: return val.data.toString();
+        case REAL: return kin_format_real(val.data);
+        case STRING: return val.data;
+        case FUNCTION:
+        case CLOSURE: return "function";
+        case ERROR: return "Error: " + kin_format(val.data);
+    }
+}
+
+// Builtin functions
+
+const kin_mom = kin_new_function((val = KIN_NIL) => val.mom || KIN_NIL);
+
+const kin_dad = kin_new_function((val = KIN_NIL) => val.dad || KIN_NIL);
+
+const kin_print = kin_new_function((val = KIN_NIL) => {
+    kin_write(kin_format(val));
+    return val;
+});
+
+const kin_println = kin_new_function((val = KIN_NIL) => {
+    kin_write(kin_format(val) + "\n");
+    return val;
+});
+
+const kin_error = kin_new_function((val = KIN_NIL) => new_val(ERROR, val));
+
+const kin_panic = kin_new_function((val = KIN_NIL) => {
+    kin_write("\nKin panicked:\n");
+    kin_println.data(val);
+    kin_panic_impl("");
+});
+
+const kin_not = kin_new_function((val = KIN_NIL) =>
+    kin_new_bool(val.type === BOOL ? !val.data : val.type === NIL));
+
+const kin_assert = kin_new_function((val = KIN_NIL, ...message) => {
+    if (!kin_is_true(val)) kin_panic.data(...(message.length ? message : [val]));
+    return val;
+});
+
+function kin_bin_fn(f) {
+    return kin_new_function((a = KIN_NIL, b = KIN_NIL) => f(a, b));
+}
+
+const kin_add_fn = kin_bin_fn(kin_add);
+const kin_sub_fn = kin_bin_fn(kin_sub);
+const kin_mul_fn = kin_bin_fn(kin_mul);
+const kin_div_fn = kin_bin_fn(kin_div);
+const kin_rem_fn = kin_bin_fn(kin_rem);
+const kin_eq_fn = kin_bin_fn(kin_eq);
+const kin_ne_fn = kin_bin_fn(kin_neq);
+const kin_lt_fn = kin_bin_fn(kin_lt);
+const kin_le_fn = kin_bin_fn(kin_le);
+const kin_gt_fn = kin_bin_fn(kin_gt);
+const kin_ge_fn = kin_bin_fn(kin_ge);
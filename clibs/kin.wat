@@ -0,0 +1,585 @@
+  ;; The Kin runtime for the WebAssembly backend
+  ;;
+  ;; This is pasted into the generated module, which adds the heap pointer, the function
+  ;; table, the program's data, and the program's functions.
+  ;;
+  ;; Values are pointers to 24-byte cells in linear memory:
+  ;;    0: the type, numbered like `KinType` in kin.h
+  ;;    4: the mom cell, or 0
+  ;;    8: an int or bool as an i64, a real as an f64, a string's bytes,
+  ;;       a function's table index, or the inner cell of an error
+  ;;   12: a string's length, or a closure's captures
+  ;;   16: the dad cell, or 0
+  ;; Cells are never changed once they are built, so they are shared freely.
+  ;; Nothing is freed.
+  ;;
+  ;; Every function, builtin or not, takes a pointer to its argument cells, the argument
+  ;; count, and a pointer to its captures, and returns a cell.
+
+  (import "kin" "write" (func $kin_write (param i32 i32)))
+  (import "kin" "write_real" (func $kin_write_real (param f64)))
+
+  (type $kin_fn (func (param i32 i32 i32) (result i32)))
+
+  (memory (export "memory") 1)
+
+  ;; Cells for nil, true, and false
+  (global $kin_nil i32 (i32.const 8))
+  (global $kin_true i32 (i32.const 32))
+  (global $kin_false i32 (i32.const 56))
+  (data (i32.const 32) "\01\00\00\00\00\00\00\00\01")
+  (data (i32.const 56) "\01")
+
+  ;; 96 to 128 is scratch space for printing ints
+
+  ;; Strings, 64 bytes apart
+  (data (i32.const 128) "nil")
+  (data (i32.const 192) "true")
+  (data (i32.const 256) "false")
+  (data (i32.const 320) "function")
+  (data (i32.const 384) "Error: ")
+  (data (i32.const 448) "\n")
+  (data (i32.const 512) "int")
+  (data (i32.const 576) "real")
+  (data (i32.const 640) "string")
+  (data (i32.const 704) "error")
+  (data (i32.const 768) "bool")
+  (data (i32.const 832) " and ")
+  (data (i32.const 896) "Attempted to add incompatible types ")
+  (data (i32.const 960) "Attempted to subtract incompatible types ")
+  (data (i32.const 1024) "Attempted to multiply incompatible types ")
+  (data (i32.const 1088) "Attempted to divide incompatible types ")
+  (data (i32.const 1152) "Attempted to compare incompatible types ")
+  (data (i32.const 1216) "Attempted to negate ")
+  (data (i32.const 1280) "Attempted to call ")
+  (data (i32.const 1344) " value")
+  (data (i32.const 1408) "\nKin panicked:\n")
+  (data (i32.const 1472) "Expected ")
+  (data (i32.const 1536) " to be ")
+  (data (i32.const 1600) ", but it is ")
+  (data (i32.const 1664) "Trees are not supported by the WebAssembly backend")
+
+  ;; Memory
+
+  (func $kin_alloc (param $size i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (global.get $kin_heap))
+    (global.set $kin_heap
+      (i32.and (i32.add (i32.add (local.get $ptr) (local.get $size)) (i32.const 7)) (i32.const -8)))
+    (block $done
+      (loop $grow
+        (br_if $done
+          (i32.le_u (global.get $kin_heap) (i32.mul (memory.size) (i32.const 65536))))
+        (if (i32.eq (memory.grow (i32.const 1)) (i32.const -1))
+          (then (unreachable)))
+        (br $grow)))
+    (local.get $ptr))
+
+  (func $kin_cell (param $type i32) (result i32)
+    (local $cell i32)
+    (local.set $cell (call $kin_alloc (i32.const 24)))
+    (i32.store (local.get $cell) (local.get $type))
+    (local.get $cell))
+
+  (func $kin_copy (param $val i32) (result i32)
+    (local $cell i32)
+    (local.set $cell (call $kin_alloc (i32.const 24)))
+    (i64.store (local.get $cell) (i64.load (local.get $val)))
+    (i64.store offset=8 (local.get $cell) (i64.load offset=8 (local.get $val)))
+    (i64.store offset=16 (local.get $cell) (i64.load offset=16 (local.get $val)))
+    (local.get $cell))
+
+  ;; Constructors
+
+  (func $kin_new_bool (param $b i32) (result i32)
+    (select (global.get $kin_true) (global.get $kin_false) (local.get $b)))
+
+  (func $kin_new_int (param $i i64) (result i32)
+    (local $cell i32)
+    (local.set $cell (call $kin_cell (i32.const 2)))
+    (i64.store offset=8 (local.get $cell) (local.get $i))
+    (local.get $cell))
+
+  (func $kin_new_real (param $r f64) (result i32)
+    (local $cell i32)
+    (local.set $cell (call $kin_cell (i32.const 3)))
+    (f64.store offset=8 (local.get $cell) (local.get $r))
+    (local.get $cell))
+
+  (func $kin_new_string (param $ptr i32) (param $len i32) (result i32)
+    (local $cell i32)
+    (local.set $cell (call $kin_cell (i32.const 4)))
+    (i32.store offset=8 (local.get $cell) (local.get $ptr))
+    (i32.store offset=12 (local.get $cell) (local.get $len))
+    (local.get $cell))
+
+  (func $kin_new_function (param $index i32) (result i32)
+    (local $cell i32)
+    (local.set $cell (call $kin_cell (i32.const 5)))
+    (i32.store offset=8 (local.get $cell) (local.get $index))
+    (local.get $cell))
+
+  (func $kin_new_closure (param $index i32) (param $captures i32) (result i32)
+    (local $cell i32)
+    (local.set $cell (call $kin_cell (i32.const 6)))
+    (i32.store offset=8 (local.get $cell) (local.get $index))
+    (i32.store offset=12 (local.get $cell) (local.get $captures))
+    (local.get $cell))
+
+  (func $kin_with_mom (param $head i32) (param $mom i32) (result i32)
+    (local $cell i32)
+    (local.set $cell (call $kin_copy (local.get $head)))
+    (i32.store offset=4 (local.get $cell) (local.get $mom))
+    (local.get $cell))
+
+  (func $kin_with_dad (param $dad i32) (param $head i32) (result i32)
+    (local $cell i32)
+    (local.set $cell (call $kin_copy (local.get $head)))
+    (i32.store offset=16 (local.get $cell) (local.get $dad))
+    (local.get $cell))
+
+  (func $kin_head (param $val i32) (result i32)
+    (local $cell i32)
+    (local.set $cell (call $kin_copy (local.get $val)))
+    (i32.store offset=4 (local.get $cell) (i32.const 0))
+    (i32.store offset=16 (local.get $cell) (i32.const 0))
+    (local.get $cell))
+
+  (func $kin_tree (param $left i32) (param $middle i32) (param $right i32) (result i32)
+    (call $kin_write (i32.const 1664) (i32.const 50))
+    (call $kin_write (i32.const 448) (i32.const 1))
+    (unreachable))
+
+  ;; Get an argument, or nil if it was not passed
+  (func $kin_arg (param $args i32) (param $count i32) (param $i i32) (result i32)
+    (if (result i32) (i32.lt_u (local.get $i) (local.get $count))
+      (then (i32.load (i32.add (local.get $args) (i32.shl (local.get $i) (i32.const 2)))))
+      (else (global.get $kin_nil))))
+
+  ;; Output
+
+  (func $kin_write_type (param $type i32)
+    (if (i32.eqz (local.get $type))
+      (then (return (call $kin_write (i32.const 128) (i32.const 3)))))
+    (if (i32.eq (local.get $type) (i32.const 1))
+      (then (return (call $kin_write (i32.const 768) (i32.const 4)))))
+    (if (i32.eq (local.get $type) (i32.const 2))
+      (then (return (call $kin_write (i32.const 512) (i32.const 3)))))
+    (if (i32.eq (local.get $type) (i32.const 3))
+      (then (return (call $kin_write (i32.const 576) (i32.const 4)))))
+    (if (i32.eq (local.get $type) (i32.const 4))
+      (then (return (call $kin_write (i32.const 640) (i32.const 6)))))
+    (if (i32.eq (local.get $type) (i32.const 7))
+      (then (return (call $kin_write (i32.const 704) (i32.const 5)))))
+    (call $kin_write (i32.const 320) (i32.const 8)))
+
+  (func $kin_write_int (param $i i64)
+    (local $pos i32)
+    (local $digit i64)
+    (local $negative i32)
+    (local.set $negative (i64.lt_s (local.get $i) (i64.const 0)))
+    (local.set $pos (i32.const 128))
+    ;; Digits of negative ints are negated one at a time, so the minimum int works
+    (loop $digits
+      (local.set $pos (i32.sub (local.get $pos) (i32.const 1)))
+      (local.set $digit (i64.rem_s (local.get $i) (i64.const 10)))
+      (if (i64.lt_s (local.get $digit) (i64.const 0))
+        (then (local.set $digit (i64.sub (i64.const 0) (local.get $digit)))))
+      (i32.store8 (local.get $pos) (i32.add (i32.const 48) (i32.wrap_i64 (local.get $digit))))
+      (local.set $i (i64.div_s (local.get $i) (i64.const 10)))
+      (br_if $digits (i64.ne (local.get $i) (i64.const 0))))
+    (if (local.get $negative)
+      (then
+        (local.set $pos (i32.sub (local.get $pos) (i32.const 1)))
+        (i32.store8 (local.get $pos) (i32.const 45))))
+    (call $kin_write (local.get $pos) (i32.sub (i32.const 128) (local.get $pos))))
+
+  (func $kin_write_value (param $val i32)
+    (local $type i32)
+    (local.set $type (i32.load (local.get $val)))
+    (if (i32.eqz (local.get $type))
+      (then (return (call $kin_write (i32.const 128) (i32.const 3)))))
+    (if (i32.eq (local.get $type) (i32.const 1))
+      (then
+        (if (i64.ne (i64.load offset=8 (local.get $val)) (i64.const 0))
+          (then (call $kin_write (i32.const 192) (i32.const 4)))
+          (else (call $kin_write (i32.const 256) (i32.const 5))))
+        (return)))
+    (if (i32.eq (local.get $type) (i32.const 2))
+      (then (return (call $kin_write_int (i64.load offset=8 (local.get $val))))))
+    (if (i32.eq (local.get $type) (i32.const 3))
+      (then (return (call $kin_write_real (f64.load offset=8 (local.get $val))))))
+    (if (i32.eq (local.get $type) (i32.const 4))
+      (then
+        (return (call $kin_write
+          (i32.load offset=8 (local.get $val))
+          (i32.load offset=12 (local.get $val))))))
+    (if (i32.eq (local.get $type) (i32.const 7))
+      (then
+        (call $kin_write (i32.const 384) (i32.const 7))
+        (return (call $kin_write_value (i32.load offset=8 (local.get $val))))))
+    (call $kin_write (i32.const 320) (i32.const 8)))
+
+  ;; Panics
+
+  (func $kin_type_panic (param $message i32) (param $len i32) (param $a i32) (param $b i32)
+    (call $kin_write (local.get $message) (local.get $len))
+    (call $kin_write_type (local.get $a))
+    (call $kin_write (i32.const 832) (i32.const 5))
+    (call $kin_write_type (local.get $b))
+    (call $kin_write (i32.const 448) (i32.const 1))
+    (unreachable))
+
+  (func $kin_check_type
+    (param $val i32) (param $types i32)
+    (param $expected i32) (param $expected_len i32)
+    (param $name i32) (param $name_len i32)
+    (local $type i32)
+    (local.set $type (i32.load (local.get $val)))
+    (if (i32.and (local.get $types) (i32.shl (i32.const 1) (local.get $type)))
+      (then (return)))
+    (call $kin_write (i32.const 1472) (i32.const 9))
+    (call $kin_write (local.get $name) (local.get $name_len))
+    (call $kin_write (i32.const 1536) (i32.const 7))
+    (call $kin_write (local.get $expected) (local.get $expected_len))
+    (call $kin_write (i32.const 1600) (i32.const 12))
+    (call $kin_write_type (local.get $type))
+    (call $kin_write (i32.const 448) (i32.const 1))
+    (unreachable))
+
+  ;; Operators
+
+  (func $kin_is_true (param $val i32) (result i32)
+    (local $type i32)
+    (local.set $type (i32.load (local.get $val)))
+    (if (result i32) (i32.eq (local.get $type) (i32.const 1))
+      (then (i64.ne (i64.load offset=8 (local.get $val)) (i64.const 0)))
+      (else
+        (i32.and
+          (i32.ne (local.get $type) (i32.const 0))
+          (i32.ne (local.get $type) (i32.const 7))))))
+
+  (func $kin_is_number (param $type i32) (result i32)
+    (i32.or (i32.eq (local.get $type) (i32.const 2)) (i32.eq (local.get $type) (i32.const 3))))
+
+  (func $kin_to_real (param $val i32) (result f64)
+    (if (result f64) (i32.eq (i32.load (local.get $val)) (i32.const 2))
+      (then (f64.convert_i64_s (i64.load offset=8 (local.get $val))))
+      (else (f64.load offset=8 (local.get $val)))))
+
+  ;; Apply an arithmetic operator: 0 add, 1 subtract, 2 multiply, 3 divide, 4 remainder
+  (func $kin_arith (param $op i32) (param $a i32) (param $b i32) (result i32)
+    (local $ta i32)
+    (local $tb i32)
+    (local $x i64)
+    (local $y i64)
+    (local $r f64)
+    (local $s f64)
+    (local.set $ta (i32.load (local.get $a)))
+    (local.set $tb (i32.load (local.get $b)))
+    (if (i32.and (i32.eq (local.get $ta) (i32.const 2)) (i32.eq (local.get $tb) (i32.const 2)))
+      (then
+        (local.set $x (i64.load offset=8 (local.get $a)))
+        (local.set $y (i64.load offset=8 (local.get $b)))
+        (if (i32.eqz (local.get $op))
+          (then (return (call $kin_new_int (i64.add (local.get $x) (local.get $y))))))
+        (if (i32.eq (local.get $op) (i32.const 1))
+          (then (return (call $kin_new_int (i64.sub (local.get $x) (local.get $y))))))
+        (if (i32.eq (local.get $op) (i32.const 2))
+          (then (return (call $kin_new_int (i64.mul (local.get $x) (local.get $y))))))
+        (if (i32.eq (local.get $op) (i32.const 3))
+          (then (return (call $kin_new_int (i64.div_s (local.get $x) (local.get $y))))))
+        (return (call $kin_new_int (i64.rem_s (local.get $x) (local.get $y))))))
+    (if (i32.and (call $kin_is_number (local.get $ta)) (call $kin_is_number (local.get $tb)))
+      (then
+        (local.set $r (call $kin_to_real (local.get $a)))
+        (local.set $s (call $kin_to_real (local.get $b)))
+        (if (i32.eqz (local.get $op))
+          (then (return (call $kin_new_real (f64.add (local.get $r) (local.get $s))))))
+        (if (i32.eq (local.get $op) (i32.const 1))
+          (then (return (call $kin_new_real (f64.sub (local.get $r) (local.get $s))))))
+        (if (i32.eq (local.get $op) (i32.const 2))
+          (then (return (call $kin_new_real (f64.mul (local.get $r) (local.get $s))))))
+        (if (i32.eq (local.get $op) (i32.const 3))
+          (then (return (call $kin_new_real (f64.div (local.get $r) (local.get $s))))))
+        (return (call $kin_new_real
+          (f64.sub
+            (local.get $r)
+            (f64.mul (local.get $s) (f64.trunc (f64.div (local.get $r) (local.get $s)))))))))
+    (if (i32.eqz (local.get $op))
+      (then (call $kin_type_panic (i32.const 896) (i32.const 36) (local.get $ta) (local.get $tb))))
+    (if (i32.eq (local.get $op) (i32.const 1))
+      (then (call $kin_type_panic (i32.const 960) (i32.const 41) (local.get $ta) (local.get $tb))))
+    (if (i32.eq (local.get $op) (i32.const 2))
+      (then (call $kin_type_panic (i32.const 1024) (i32.const 41) (local.get $ta) (local.get $tb))))
+    (call $kin_type_panic (i32.const 1088) (i32.const 39) (local.get $ta) (local.get $tb))
+    (unreachable))
+
+  (func $kin_add (param $a i32) (param $b i32) (result i32)
+    (call $kin_arith (i32.const 0) (local.get $a) (local.get $b)))
+  (func $kin_sub (param $a i32) (param $b i32) (result i32)
+    (call $kin_arith (i32.const 1) (local.get $a) (local.get $b)))
+  (func $kin_mul (param $a i32) (param $b i32) (result i32)
+    (call $kin_arith (i32.const 2) (local.get $a) (local.get $b)))
+  (func $kin_div (param $a i32) (param $b i32) (result i32)
+    (call $kin_arith (i32.const 3) (local.get $a) (local.get $b)))
+  (func $kin_rem (param $a i32) (param $b i32) (result i32)
+    (call $kin_arith (i32.const 4) (local.get $a) (local.get $b)))
+
+  (func $kin_neg (param $val i32) (result i32)
+    (local $type i32)
+    (local.set $type (i32.load (local.get $val)))
+    (if (i32.eq (local.get $type) (i32.const 2))
+      (then
+        (return (call $kin_new_int (i64.sub (i64.const 0) (i64.load offset=8 (local.get $val)))))))
+    (if (i32.eq (local.get $type) (i32.const 3))
+      (then (return (call $kin_new_real (f64.neg (f64.load offset=8 (local.get $val)))))))
+    (call $kin_write (i32.const 1216) (i32.const 20))
+    (call $kin_write_type (local.get $type))
+    (call $kin_write (i32.const 448) (i32.const 1))
+    (unreachable))
+
+  ;; Compare bytes the way C compares unsigned chars, returning -1, 0, or 1
+  (func $kin_compare_bytes (param $a i32) (param $a_len i32) (param $b i32) (param $b_len i32) (result i32)
+    (local $i i32)
+    (local $len i32)
+    (local $x i32)
+    (local $y i32)
+    (local.set $len
+      (select (local.get $a_len) (local.get $b_len) (i32.lt_u (local.get $a_len) (local.get $b_len))))
+    (block $done
+      (loop $bytes
+        (br_if $done (i32.ge_u (local.get $i) (local.get $len)))
+        (local.set $x (i32.load8_u (i32.add (local.get $a) (local.get $i))))
+        (local.set $y (i32.load8_u (i32.add (local.get $b) (local.get $i))))
+        (if (i32.lt_u (local.get $x) (local.get $y)) (then (return (i32.const -1))))
+        (if (i32.gt_u (local.get $x) (local.get $y)) (then (return (i32.const 1))))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $bytes)))
+    (i32.sub
+      (i32.gt_u (local.get $a_len) (local.get $b_len))
+      (i32.lt_u (local.get $a_len) (local.get $b_len))))
+
+  (func $kin_eq_impl (param $a i32) (param $b i32) (result i32)
+    (local $ta i32)
+    (local $tb i32)
+    (local.set $ta (i32.load (local.get $a)))
+    (local.set $tb (i32.load (local.get $b)))
+    (if (i32.and (call $kin_is_number (local.get $ta)) (call $kin_is_number (local.get $tb)))
+      (then
+        (if (i32.and (i32.eq (local.get $ta) (i32.const 2)) (i32.eq (local.get $tb) (i32.const 2)))
+          (then
+            (return (i64.eq (i64.load offset=8 (local.get $a)) (i64.load offset=8 (local.get $b))))))
+        (return (f64.eq (call $kin_to_real (local.get $a)) (call $kin_to_real (local.get $b))))))
+    (if (i32.ne (local.get $ta) (local.get $tb))
+      (then (return (i32.const 0))))
+    (if (i32.eqz (local.get $ta))
+      (then (return (i32.const 1))))
+    (if (i32.eq (local.get $ta) (i32.const 1))
+      (then
+        (return (i64.eq (i64.load offset=8 (local.get $a)) (i64.load offset=8 (local.get $b))))))
+    (if (i32.eq (local.get $ta) (i32.const 4))
+      (then
+        (return (i32.eqz (call $kin_compare_bytes
+          (i32.load offset=8 (local.get $a)) (i32.load offset=12 (local.get $a))
+          (i32.load offset=8 (local.get $b)) (i32.load offset=12 (local.get $b)))))))
+    (if (i32.eq (local.get $ta) (i32.const 7))
+      (then
+        (return (call $kin_eq_impl
+          (i32.load offset=8 (local.get $a))
+          (i32.load offset=8 (local.get $b))))))
+    ;; Functions and closures are equal if they run the same code
+    (i32.eq (i32.load offset=8 (local.get $a)) (i32.load offset=8 (local.get $b))))
+
+  ;; Order two values, returning -1, 0, or 1, or panic if they cannot be compared
+  ;; Unordered reals return 2, which every comparison treats as false like in C
+  (func $kin_compare (param $a i32) (param $b i32) (result i32)
+    (local $ta i32)
+    (local $tb i32)
+    (local $x i64)
+    (local $y i64)
+    (local $r f64)
+    (local $s f64)
+    (local.set $ta (i32.load (local.get $a)))
+    (local.set $tb (i32.load (local.get $b)))
+    (if (i32.and (call $kin_is_number (local.get $ta)) (call $kin_is_number (local.get $tb)))
+      (then
+        (if (i32.and (i32.eq (local.get $ta) (i32.const 2)) (i32.eq (local.get $tb) (i32.const 2)))
+          (then
+            (local.set $x (i64.load offset=8 (local.get $a)))
+            (local.set $y (i64.load offset=8 (local.get $b)))
+            (return (i32.sub
+              (i64.gt_s (local.get $x) (local.get $y))
+              (i64.lt_s (local.get $x) (local.get $y))))))
+        (local.set $r (call $kin_to_real (local.get $a)))
+        (local.set $s (call $kin_to_real (local.get $b)))
+        (if (i32.or (f64.ne (local.get $r) (local.get $r)) (f64.ne (local.get $s) (local.get $s)))
+          (then (return (i32.const 2))))
+        (return (i32.sub
+          (f64.gt (local.get $r) (local.get $s))
+          (f64.lt (local.get $r) (local.get $s))))))
+    (if (i32.ne (local.get $ta) (local.get $tb))
+      (then (call $kin_type_panic (i32.const 1152) (i32.const 40) (local.get $ta) (local.get $tb))))
+    (if (i32.eq (local.get $ta) (i32.const 1))
+      (then
+        (local.set $x (i64.load offset=8 (local.get $a)))
+        (local.set $y (i64.load offset=8 (local.get $b)))
+        (return (i32.sub
+          (i64.gt_u (local.get $x) (local.get $y))
+          (i64.lt_u (local.get $x) (local.get $y))))))
+    (if (i32.eq (local.get $ta) (i32.const 4))
+      (then
+        (return (call $kin_compare_bytes
+          (i32.load offset=8 (local.get $a)) (i32.load offset=12 (local.get $a))
+          (i32.load offset=8 (local.get $b)) (i32.load offset=12 (local.get $b))))))
+    (if (i32.or (i32.eq (local.get $ta) (i32.const 5)) (i32.eq (local.get $ta) (i32.const 6)))
+      (then
+        (return (i32.sub
+          (i32.gt_u (i32.load offset=8 (local.get $a)) (i32.load offset=8 (local.get $b)))
+          (i32.lt_u (i32.load offset=8 (local.get $a)) (i32.load offset=8 (local.get $b)))))))
+    (if (i32.eq (local.get $ta) (i32.const 7))
+      (then
+        (return (call $kin_compare
+          (i32.load offset=8 (local.get $a))
+          (i32.load offset=8 (local.get $b))))))
+    (call $kin_type_panic (i32.const 1152) (i32.const 40) (local.get $ta) (local.get $tb))
+    (unreachable))
+
+  (func $kin_eq (param $a i32) (param $b i32) (result i32)
+    (call $kin_new_bool (call $kin_eq_impl (local.get $a) (local.get $b))))
+  (func $kin_neq (param $a i32) (param $b i32) (result i32)
+    (call $kin_new_bool (i32.eqz (call $kin_eq_impl (local.get $a) (local.get $b)))))
+  (func $kin_lt (param $a i32) (param $b i32) (result i32)
+    (call $kin_new_bool (i32.lt_s (call $kin_compare (local.get $a) (local.get $b)) (i32.const 0))))
+  (func $kin_le (param $a i32) (param $b i32) (result i32)
+    (call $kin_new_bool (i32.le_s (call $kin_compare (local.get $a) (local.get $b)) (i32.const 0))))
+  (func $kin_gt (param $a i32) (param $b i32) (result i32)
+    (call $kin_new_bool (i32.eq (call $kin_compare (local.get $a) (local.get $b)) (i32.const 1))))
+  (func $kin_ge (param $a i32) (param $b i32) (result i32)
+    (call $kin_new_bool (i32.lt_u (call $kin_compare (local.get $a) (local.get $b)) (i32.const 2))))
+
+  ;; Call a function or closure value
+  (func $kin_call (param $f i32) (param $args i32) (param $count i32) (result i32)
+    (local $type i32)
+    (local.set $type (i32.load (local.get $f)))
+    (if (i32.eq (local.get $type) (i32.const 5))
+      (then
+        (return (call_indirect (type $kin_fn)
+          (local.get $args) (local.get $count) (i32.const 0)
+          (i32.load offset=8 (local.get $f))))))
+    (if (i32.eq (local.get $type) (i32.const 6))
+      (then
+        (return (call_indirect (type $kin_fn)
+          (local.get $args) (local.get $count) (i32.load offset=12 (local.get $f))
+          (i32.load offset=8 (local.get $f))))))
+    (call $kin_write (i32.const 1280) (i32.const 18))
+    (call $kin_write_type (local.get $type))
+    (call $kin_write (i32.const 1344) (i32.const 6))
+    (call $kin_write (i32.const 448) (i32.const 1))
+    (unreachable))
+
+  ;; Builtin functions, in the order of `BUILTIN_FUNCTIONS`
+
+  (func $kin_builtin_mom (type $kin_fn) (param $args i32) (param $count i32) (param $captures i32) (result i32)
+    (local $mom i32)
+    (local.set $mom
+      (i32.load offset=4 (call $kin_arg (local.get $args) (local.get $count) (i32.const 0))))
+    (select (local.get $mom) (global.get $kin_nil) (local.get $mom)))
+
+  (func $kin_builtin_dad (type $kin_fn) (param $args i32) (param $count i32) (param $captures i32) (result i32)
+    (local $dad i32)
+    (local.set $dad
+      (i32.load offset=16 (call $kin_arg (local.get $args) (local.get $count) (i32.const 0))))
+    (select (local.get $dad) (global.get $kin_nil) (local.get $dad)))
+
+  (func $kin_builtin_print (type $kin_fn) (param $args i32) (param $count i32) (param $captures i32) (result i32)
+    (local $val i32)
+    (local.set $val (call $kin_arg (local.get $args) (local.get $count) (i32.const 0)))
+    (call $kin_write_value (local.get $val))
+    (local.get $val))
+
+  (func $kin_builtin_println (type $kin_fn) (param $args i32) (param $count i32) (param $captures i32) (result i32)
+    (local $val i32)
+    (local.set $val (call $kin_builtin_print (local.get $args) (local.get $count) (i32.const 0)))
+    (call $kin_write (i32.const 448) (i32.const 1))
+    (local.get $val))
+
+  (func $kin_builtin_error (type $kin_fn) (param $args i32) (param $count i32) (param $captures i32) (result i32)
+    (local $cell i32)
+    (local.set $cell (call $kin_cell (i32.const 7)))
+    (i32.store offset=8
+      (local.get $cell)
+      (call $kin_arg (local.get $args) (local.get $count) (i32.const 0)))
+    (local.get $cell))
+
+  (func $kin_builtin_panic (type $kin_fn) (param $args i32) (param $count i32) (param $captures i32) (result i32)
+    (call $kin_write (i32.const 1408) (i32.const 15))
+    (drop (call $kin_builtin_println (local.get $args) (local.get $count) (i32.const 0)))
+    (unreachable))
+
+  (func $kin_builtin_not (type $kin_fn) (param $args i32) (param $count i32) (param $captures i32) (result i32)
+    (local $val i32)
+    (local.set $val (call $kin_arg (local.get $args) (local.get $count) (i32.const 0)))
+    (if (result i32) (i32.eq (i32.load (local.get $val)) (i32.const 1))
+      (then (call $kin_new_bool (i64.eqz (i64.load offset=8 (local.get $val)))))
+      (else (call $kin_new_bool (i32.eqz (i32.load (local.get $val)))))))
+
+  (func $kin_builtin_assert (type $kin_fn) (param $args i32) (param $count i32) (param $captures i32) (result i32)
+    (local $val i32)
+    (local.set $val (call $kin_arg (local.get $args) (local.get $count) (i32.const 0)))
+    (if (i32.eqz (call $kin_is_true (local.get $val)))
+      (then
+        (if (i32.ge_u (local.get $count) (i32.const 2))
+          (then
+            (drop (call $kin_builtin_panic
+              (i32.add (local.get $args) (i32.const 4))
+              (i32.sub (local.get $count) (i32.const 1))
+              (i32.const 0))))
+          (else
+            (drop (call $kin_builtin_panic (local.get $args) (local.get $count) (i32.const 0)))))))
+    (local.get $val))
+
+  (func $kin_builtin_add (type $kin_fn) (param $args i32) (param $count i32) (param $captures i32) (result i32)
+    (call $kin_add
+      (call $kin_arg (local.get $args) (local.get $count) (i32.const 0))
+      (call $kin_arg (local.get $args) (local.get $count) (i32.const 1))))
+  (func $kin_builtin_sub (type $kin_fn) (param $args i32) (param $count i32) (param $captures i32) (result i32)
+    (call $kin_sub
+      (call $kin_arg (local.get $args) (local.get $count) (i32.const 0))
+      (call $kin_arg (local.get $args) (local.get $count) (i32.const 1))))
+  (func $kin_builtin_mul (type $kin_fn) (param $args i32) (param $count i32) (param $captures i32) (result i32)
+    (call $kin_mul
+      (call $kin_arg (local.get $args) (local.get $count) (i32.const 0))
+      (call $kin_arg (local.get $args) (local.get $count) (i32.const 1))))
+  (func $kin_builtin_div (type $kin_fn) (param $args i32) (param $count i32) (param $captures i32) (result i32)
+    (call $kin_div
+      (call $kin_arg (local.get $args) (local.get $count) (i32.const 0))
+      (call $kin_arg (local.get $args) (local.get $count) (i32.const 1))))
+  (func $kin_builtin_rem (type $kin_fn) (param $args i32) (param $count i32) (param $captures i32) (result i32)
+    (call $kin_rem
+      (call $kin_arg (local.get $args) (local.get $count) (i32.const 0))
+      (call $kin_arg (local.get $args) (local.get $count) (i32.const 1))))
+  (func $kin_builtin_eq (type $kin_fn) (param $args i32) (param $count i32) (param $captures i32) (result i32)
+    (call $kin_eq
+      (call $kin_arg (local.get $args) (local.get $count) (i32.const 0))
+      (call $kin_arg (local.get $args) (local.get $count) (i32.const 1))))
+  (func $kin_builtin_ne (type $kin_fn) (param $args i32) (param $count i32) (param $captures i32) (result i32)
+    (call $kin_neq
+      (call $kin_arg (local.get $args) (local.get $count) (i32.const 0))
+      (call $kin_arg (local.get $args) (local.get $count) (i32.const 1))))
+  (func $kin_builtin_lt (type $kin_fn) (param $args i32) (param $count i32) (param $captures i32) (result i32)
+    (call $kin_lt
+      (call $kin_arg (local.get $args) (local.get $count) (i32.const 0))
+      (call $kin_arg (local.get $args) (local.get $count) (i32.const 1))))
+  (func $kin_builtin_le (type $kin_fn) (param $args i32) (param $count i32) (param $captures i32) (result i32)
+    (call $kin_le
+      (call $kin_arg (local.get $args) (local.get $count) (i32.const 0))
+      (call $kin_arg (local.get $args) (local.get $count) (i32.const 1))))
+  (func $kin_builtin_gt (type $kin_fn) (param $args i32) (param $count i32) (param $captures i32) (result i32)
+    (call $kin_gt
+      (call $kin_arg (local.get $args) (local.get $count) (i32.const 0))
+      (call $kin_arg (local.get $args) (local.get $count) (i32.const 1))))
+  (func $kin_builtin_ge (type $kin_fn) (param $args i32) (param $count i32) (param $captures i32) (result i32)
+    (call $kin_ge
+      (call $kin_arg (local.get $args) (local.get $count) (i32.const 0))
+      (call $kin_arg (local.get $args) (local.get $count) (i32.const 1))))
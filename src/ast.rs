@@ -1,20 +1,154 @@
 #![allow(clippy::upper_case_acronyms)]
 
+use std::{fmt, ops::BitOr};
+
 use pest::Span;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Lifetime {
-    pub depth: u8,
-    pub refs: u8,
+    pub depth: u32,
+    pub refs: u32,
 }
 
 impl Lifetime {
     pub const STATIC: Self = Lifetime::new(0, 0);
-    pub const fn new(depth: u8, refs: u8) -> Self {
+    pub const fn new(depth: u32, refs: u32) -> Self {
         Lifetime { depth, refs }
     }
 }
 
+/// A set of the types a value may have at runtime
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Type(u8);
+
+impl Type {
+    /// No type, for values that are never produced
+    pub const NEVER: Self = Type(0);
+    pub const NIL: Self = Type(1);
+    pub const BOOL: Self = Type(1 << 1);
+    pub const INT: Self = Type(1 << 2);
+    pub const REAL: Self = Type(1 << 3);
+    pub const STRING: Self = Type(1 << 4);
+    pub const FUNCTION: Self = Type(1 << 5);
+    pub const CLOSURE: Self = Type(1 << 6);
+    pub const ERROR: Self = Type(1 << 7);
+    pub const NUMBER: Self = Type(Self::INT.0 | Self::REAL.0);
+    pub const CALLABLE: Self = Type(Self::FUNCTION.0 | Self::CLOSURE.0);
+    pub const ANY: Self = Type(u8::MAX);
+    /// Get the type an annotation names
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "nil" => Type::NIL,
+            "bool" => Type::BOOL,
+            "int" => Type::INT,
+            "real" => Type::REAL,
+            "string" => Type::STRING,
+            "function" => Type::CALLABLE,
+            "error" => Type::ERROR,
+            _ => return None,
+        })
+    }
+    /// The set as a mask of runtime type tags
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+    pub fn is_never(self) -> bool {
+        self == Type::NEVER
+    }
+    /// Whether this set has every type in `other`
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+    /// The types in both sets
+    pub fn intersect(self, other: Self) -> Self {
+        Type(self.0 & other.0)
+    }
+}
+
+impl BitOr for Type {
+    type Output = Self;
+    fn bitor(self, other: Self) -> Self {
+        Type(self.0 | other.0)
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if *self == Type::ANY {
+            return write!(f, "any type");
+        }
+        // Functions and closures look the same to users
+        let names: Vec<&str> = [
+            (Type::NIL, "nil"),
+            (Type::BOOL, "bool"),
+            (Type::INT, "int"),
+            (Type::REAL, "real"),
+            (Type::STRING, "string"),
+            (Type::CALLABLE, "function"),
+            (Type::ERROR, "error"),
+        ]
+        .iter()
+        .filter(|(ty, _)| !self.intersect(*ty).is_never())
+        .map(|(_, name)| *name)
+        .collect();
+        match names.len() {
+            0 => write!(f, "nothing"),
+            1 => write!(f, "{}", names[0]),
+            n => write!(f, "{} or {}", names[..n - 1].join(", "), names[n - 1]),
+        }
+    }
+}
+
+/// A step from a value to a value that it references
+#[derive(Debug, Clone)]
+pub enum RefStep<'a> {
+    /// The value of a def
+    Def(Ident<'a>),
+    /// The tail of a list
+    Tail,
+    /// The dad of a value
+    Dad,
+    /// A child of a tree
+    Child,
+    /// A value captured by a closure
+    Capture,
+    /// The return value of a closure
+    Return,
+}
+
+/// A value that other values reference
+#[derive(Debug, Clone)]
+pub enum RefOrigin<'a> {
+    /// A def or param, identified where it is bound
+    Binding(Ident<'a>),
+    /// A value created by an expression
+    Value(Span<'a>),
+}
+
+/// Why a value's lifetime references the scope it does
+///
+/// This is recorded alongside lifetimes so that lifetime errors can explain themselves.
+#[derive(Debug, Clone)]
+pub struct Provenance<'a> {
+    /// The steps from the value to its origin, outermost first
+    pub steps: Vec<RefStep<'a>>,
+    pub origin: RefOrigin<'a>,
+}
+
+impl<'a> Provenance<'a> {
+    pub fn new(origin: RefOrigin<'a>) -> Self {
+        Provenance {
+            steps: Vec::new(),
+            origin,
+        }
+    }
+    /// Prepend a step to the chain
+    pub fn step(mut self, step: RefStep<'a>) -> Self {
+        self.steps.insert(0, step);
+        self
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Ident<'a> {
     pub name: &'a str,
@@ -38,25 +172,33 @@ impl<'a> Eq for Ident<'a> {}
 pub enum Item<'a> {
     Node(Node<'a>),
     Def(Def<'a>),
+    Extern(Extern<'a>),
 }
 
 impl<'a> Item<'a> {
     pub fn is_const(&self) -> bool {
         match self {
             Item::Node(node) => node.kind.is_const(),
-            Item::Def(_) => true,
+            Item::Def(_) | Item::Extern(_) => true,
         }
     }
     pub fn lifetime(&self) -> Lifetime {
         match self {
             Item::Node(node) => node.lifetime,
-            Item::Def(_) => Lifetime::STATIC,
+            Item::Def(_) | Item::Extern(_) => Lifetime::STATIC,
+        }
+    }
+    pub fn provenance(&self) -> Option<Provenance<'a>> {
+        match self {
+            Item::Node(node) => node.provenance.clone(),
+            Item::Def(_) | Item::Extern(_) => None,
         }
     }
     pub fn span(&self) -> &Span<'a> {
         match self {
             Item::Node(node) => node.kind.span(),
             Item::Def(def) => &def.ident.span,
+            Item::Extern(ext) => &ext.ident.span,
         }
     }
 }
@@ -66,6 +208,15 @@ pub type Items<'a> = Vec<Item<'a>>;
 #[derive(Debug, Clone)]
 pub struct Param<'a> {
     pub ident: Ident<'a>,
+    /// The annotated type
+    pub ty: Option<Type>,
+}
+
+impl<'a> Param<'a> {
+    /// Describe the param in type errors, given a description of its function
+    pub fn describe(&self, function: &str) -> String {
+        format!("param `{}` of {}", self.ident.name, function)
+    }
 }
 
 pub type Params<'a> = Vec<Param<'a>>;
@@ -73,6 +224,8 @@ pub type Params<'a> = Vec<Param<'a>>;
 #[derive(Debug, Clone)]
 pub struct Def<'a> {
     pub ident: Ident<'a>,
+    /// The annotated type of the value, or of the return value of a function
+    pub ty: Option<Type>,
     pub params: Params<'a>,
     pub items: Items<'a>,
 }
@@ -81,6 +234,51 @@ impl<'a> Def<'a> {
     pub fn is_function(&self) -> bool {
         !self.params.is_empty()
     }
+    pub fn is_test(&self) -> bool {
+        self.ident.name.starts_with("test_")
+    }
+}
+
+/// A C function bound with `extern name arity`
+///
+/// The function has the same name in C and the `KinFn` signature of the builtins.
+#[derive(Debug, Clone)]
+pub struct Extern<'a> {
+    pub ident: Ident<'a>,
+    /// How many args the function is always passed. Missing args are nil.
+    pub arity: u32,
+}
+
+impl<'a> Extern<'a> {
+    /// A def for backends that cannot call C, which panics when it is called
+    pub fn stub(&self, backend: &str) -> Def<'a> {
+        let message = format!(
+            "`{}` is an extern C function, which the {} backend cannot call",
+            self.ident.name, backend
+        );
+        let span = self.ident.span.clone();
+        let term = |term| NodeKind::Term(term, span.clone()).life(0, 0);
+        let call = NodeKind::Call(CallExpr {
+            caller: term(Term::Ident(Ident {
+                name: "panic",
+                span: span.clone(),
+            }))
+            .into(),
+            args: vec![term(Term::String(message))],
+            span: span.clone(),
+        });
+        let closure = Closure {
+            span: span.clone(),
+            params: Vec::new(),
+            body: vec![Item::Node(call.life(0, 0))],
+        };
+        Def {
+            ident: self.ident.clone(),
+            ty: None,
+            params: Vec::new(),
+            items: vec![Item::Node(term(Term::Closure(closure.into())))],
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -92,10 +290,12 @@ pub enum NodeKind<'a> {
 }
 
 impl<'a> NodeKind<'a> {
-    pub fn life(self, depth: u8, refs: u8) -> Node<'a> {
+    pub fn life(self, depth: u32, refs: u32) -> Node<'a> {
         Node {
             kind: self,
             lifetime: Lifetime::new(depth, refs),
+            provenance: None,
+            ty: Type::ANY,
         }
     }
     pub fn span(&self) -> &Span<'a> {
@@ -118,6 +318,21 @@ impl<'a> NodeKind<'a> {
 pub struct Node<'a> {
     pub kind: NodeKind<'a>,
     pub lifetime: Lifetime,
+    /// Why `lifetime.refs` is what it is
+    pub provenance: Option<Provenance<'a>>,
+    /// The types the node may evaluate to, filled in by inference
+    pub ty: Type,
+}
+
+impl<'a> Node<'a> {
+    pub fn traced(mut self, provenance: Option<Provenance<'a>>) -> Self {
+        self.provenance = provenance;
+        self
+    }
+    pub fn typed(mut self, ty: Type) -> Self {
+        self.ty = ty;
+        self
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -0,0 +1,740 @@
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    io::{self, Read, Write},
+};
+
+use crate::{
+    ast::*,
+    transpile::{TranspileOptions, BUILTIN_FUNCTIONS},
+};
+
+/// What `.kinc` files start with
+const MAGIC: &[u8; 4] = b"KINC";
+/// The version of the `.kinc` format, bumped whenever it changes
+const VERSION: u8 = 1;
+
+/// A compiled program
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub constants: Vec<Constant>,
+    pub functions: Vec<Function>,
+    /// The function the program starts in
+    pub main: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    Int(i64),
+    Real(f64),
+    String(String),
+}
+
+/// A compiled function or closure
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub params: u32,
+    /// How many local slots the function needs, including its params
+    pub locals: u32,
+    /// Where the values the function captures come from when a closure over it is made
+    pub captures: Vec<CaptureFrom>,
+    pub code: Vec<Op>,
+}
+
+/// Where a captured value comes from in the frame that makes a closure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureFrom {
+    Local(u32),
+    Capture(u32),
+    /// The function running in the frame
+    Current,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// Push a constant
+    Const(u32),
+    Nil,
+    True,
+    False,
+    /// Push a builtin function by its index in `BUILTIN_FUNCTIONS`
+    Builtin(u32),
+    /// Push a local of the current frame
+    Local(u32),
+    /// Pop a value into a local
+    SetLocal(u32),
+    /// Push a value captured by the current closure
+    Capture(u32),
+    /// Push the function running in the current frame
+    Current,
+    /// Push a closure over a function
+    Closure(u32),
+    /// Pop a callee and this many arguments above it, and push what the callee returns
+    Call(u32),
+    Pop,
+    /// Jump to an op if the top value is falsy, leaving it. Otherwise pop it.
+    JumpIfFalsy(u32),
+    /// Jump to an op if the top value is truthy, leaving it. Otherwise pop it.
+    JumpIfTruthy(u32),
+    /// Pop two operands and push the result of an operator. Never `and` or `or`.
+    Binary(BinOp),
+    Neg,
+    Head,
+    /// Pop three values and make a tree
+    Tree,
+    /// Panic unless the top value has one of the types in a mask. The constants are
+    /// the name of the expected types and a description of the value.
+    CheckType(u8, u32, u32),
+    Return,
+}
+
+/// Compile checked items to bytecode
+pub fn compile(items: Items, options: TranspileOptions) -> Program {
+    let mut compiler = BytecodeCompiler {
+        constants: Vec::new(),
+        strings: HashMap::new(),
+        functions: Vec::new(),
+        frames: Vec::new(),
+        type_checks: !options.no_type_checks,
+    };
+    let main = compiler.function("main", None, Vec::new(), None, items, "");
+    Program {
+        constants: compiler.constants,
+        functions: compiler.functions,
+        main,
+    }
+}
+
+/// How a name is reached from a frame
+#[derive(Debug, Clone, Copy)]
+enum Access {
+    Local(u32),
+    Capture(u32),
+    Current,
+}
+
+struct Frame<'a> {
+    locals: u32,
+    scopes: Vec<HashMap<&'a str, Access>>,
+    captures: Vec<CaptureFrom>,
+    code: Vec<Op>,
+}
+
+struct BytecodeCompiler<'a> {
+    constants: Vec<Constant>,
+    /// The constant indices of strings, so each is stored once
+    strings: HashMap<String, u32>,
+    /// Finished functions. Functions are reserved before they are finished.
+    functions: Vec<Function>,
+    frames: Vec<Frame<'a>>,
+    type_checks: bool,
+}
+
+/// Convert a count or index to the `u32` the bytecode stores it as
+///
+/// Parsing rejects sources long enough for a count to overflow.
+fn to_u32(i: usize) -> u32 {
+    u32::try_from(i).expect("bytecode counts and indices fit in a u32")
+}
+
+impl<'a> BytecodeCompiler<'a> {
+    fn frame(&mut self) -> &mut Frame<'a> {
+        self.frames.last_mut().unwrap()
+    }
+    fn emit(&mut self, op: Op) -> usize {
+        let code = &mut self.frame().code;
+        code.push(op);
+        code.len() - 1
+    }
+    fn constant(&mut self, constant: Constant) -> u32 {
+        if let Constant::String(s) = &constant {
+            if let Some(&i) = self.strings.get(s) {
+                return i;
+            }
+            self.strings.insert(s.clone(), to_u32(self.constants.len()));
+        }
+        self.constants.push(constant);
+        to_u32(self.constants.len()) - 1
+    }
+    /// Bind a name to a new local, returning its slot
+    fn bind(&mut self, name: &'a str) -> u32 {
+        let frame = self.frame();
+        let slot = frame.locals;
+        frame.locals += 1;
+        frame
+            .scopes
+            .last_mut()
+            .unwrap()
+            .insert(name, Access::Local(slot));
+        slot
+    }
+    /// Find a name from a frame, capturing it from enclosing frames if needed
+    fn resolve(&mut self, frame: usize, name: &'a str) -> Option<Access> {
+        let found = self.frames[frame]
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied());
+        if found.is_some() || frame == 0 {
+            return found;
+        }
+        let from = match self.resolve(frame - 1, name)? {
+            Access::Local(slot) => CaptureFrom::Local(slot),
+            Access::Capture(i) => CaptureFrom::Capture(i),
+            Access::Current => CaptureFrom::Current,
+        };
+        let frame = &mut self.frames[frame];
+        let access = Access::Capture(to_u32(frame.captures.len()));
+        frame.captures.push(from);
+        frame.scopes[0].insert(name, access);
+        Some(access)
+    }
+    fn check_type(&mut self, ty: Type, name: &str) {
+        if self.type_checks {
+            let expected = self.constant(Constant::String(ty.to_string()));
+            let name = self.constant(Constant::String(name.into()));
+            self.emit(Op::CheckType(ty.bits(), expected, name));
+        }
+    }
+    /// Compile a function, returning its index
+    fn function(
+        &mut self,
+        name: &str,
+        this: Option<&'a str>,
+        params: Params<'a>,
+        ret: Option<Type>,
+        items: Items<'a>,
+        description: &str,
+    ) -> u32 {
+        let index = to_u32(self.functions.len());
+        self.functions.push(Function {
+            name: name.into(),
+            params: to_u32(params.len()),
+            locals: 0,
+            captures: Vec::new(),
+            code: Vec::new(),
+        });
+        let mut scope = HashMap::new();
+        if let Some(this) = this {
+            scope.insert(this, Access::Current);
+        }
+        self.frames.push(Frame {
+            locals: 0,
+            scopes: vec![scope],
+            captures: Vec::new(),
+            code: Vec::new(),
+        });
+        // Arguments arrive in the first slots
+        for param in &params {
+            let slot = self.bind(param.ident.name);
+            if let Some(ty) = param.ty.filter(|_| self.type_checks) {
+                self.emit(Op::Local(slot));
+                self.check_type(ty, &param.describe(description));
+                self.emit(Op::Pop);
+            }
+        }
+        self.items(items);
+        if let Some(ty) = ret {
+            self.check_type(ty, &format!("the return value of {}", description));
+        }
+        self.emit(Op::Return);
+        let frame = self.frames.pop().unwrap();
+        let function = &mut self.functions[index as usize];
+        function.locals = frame.locals;
+        function.captures = frame.captures;
+        function.code = frame.code;
+        index
+    }
+    /// Compile items, leaving the value of the last one on the stack
+    fn items(&mut self, items: Items<'a>) {
+        let mut has_value = false;
+        for item in items {
+            if has_value {
+                self.emit(Op::Pop);
+            }
+            has_value = match item {
+                Item::Def(def) => {
+                    self.def(def);
+                    false
+                }
+                Item::Extern(ext) => {
+                    self.def(ext.stub("VM"));
+                    false
+                }
+                Item::Node(node) => {
+                    self.node(node);
+                    true
+                }
+            };
+        }
+        if !has_value {
+            self.emit(Op::Nil);
+        }
+    }
+    fn def(&mut self, def: Def<'a>) {
+        let name = def.ident.name;
+        if def.is_function() {
+            let description = format!("`{}`", name);
+            let index = self.function(
+                name,
+                Some(name),
+                def.params,
+                def.ty,
+                def.items,
+                &description,
+            );
+            self.emit(Op::Closure(index));
+        } else {
+            // Defs in the body are local to it
+            self.frame().scopes.push(HashMap::new());
+            self.items(def.items);
+            self.frame().scopes.pop();
+            if let Some(ty) = def.ty {
+                self.check_type(ty, &format!("`{}`", name));
+            }
+        }
+        let slot = self.bind(name);
+        self.emit(Op::SetLocal(slot));
+    }
+    fn node(&mut self, node: Node<'a>) {
+        match node.kind {
+            NodeKind::Term(term, _) => self.term(term),
+            NodeKind::BinExpr(expr) => {
+                self.node(*expr.left);
+                let jump = match expr.op {
+                    BinOp::And => Some(self.emit(Op::JumpIfFalsy(0))),
+                    BinOp::Or => Some(self.emit(Op::JumpIfTruthy(0))),
+                    _ => None,
+                };
+                self.node(*expr.right);
+                match jump {
+                    // Point the jump past the right side
+                    Some(jump) => {
+                        let target = to_u32(self.frame().code.len());
+                        let code = &mut self.frame().code;
+                        code[jump] = match code[jump] {
+                            Op::JumpIfFalsy(_) => Op::JumpIfFalsy(target),
+                            _ => Op::JumpIfTruthy(target),
+                        };
+                    }
+                    None => {
+                        self.emit(Op::Binary(expr.op));
+                    }
+                }
+            }
+            NodeKind::UnExpr(expr) => {
+                self.node(*expr.inner);
+                self.emit(match expr.op {
+                    UnOp::Neg => Op::Neg,
+                    UnOp::Head => Op::Head,
+                });
+            }
+            NodeKind::Call(expr) => {
+                let count = to_u32(expr.args.len());
+                self.node(*expr.caller);
+                for arg in expr.args {
+                    self.node(arg);
+                }
+                self.emit(Op::Call(count));
+            }
+        }
+    }
+    fn term(&mut self, term: Term<'a>) {
+        match term {
+            Term::Int(i) => {
+                let i = self.constant(Constant::Int(i));
+                self.emit(Op::Const(i));
+            }
+            Term::Real(r) => {
+                let i = self.constant(Constant::Real(r));
+                self.emit(Op::Const(i));
+            }
+            Term::String(s) => {
+                let i = self.constant(Constant::String(s));
+                self.emit(Op::Const(i));
+            }
+            Term::Ident(ident) => {
+                let frame = self.frames.len() - 1;
+                let op = match self.resolve(frame, ident.name) {
+                    Some(Access::Local(slot)) => Op::Local(slot),
+                    Some(Access::Capture(i)) => Op::Capture(i),
+                    Some(Access::Current) => Op::Current,
+                    None => match ident.name {
+                        "_" | "nil" => Op::Nil,
+                        "true" => Op::True,
+                        "false" => Op::False,
+                        name => Op::Builtin(
+                            BUILTIN_FUNCTIONS
+                                .iter()
+                                .position(|(n, _)| *n == name)
+                                .expect("unknown names are rejected when parsing")
+                                as u32,
+                        ),
+                    },
+                };
+                self.emit(op);
+            }
+            Term::Expr(items) => {
+                self.frame().scopes.push(HashMap::new());
+                self.items(items);
+                self.frame().scopes.pop();
+            }
+            Term::Tree(nodes) => {
+                let [left, middle, right] = *nodes;
+                self.node(left);
+                self.node(middle);
+                self.node(right);
+                self.emit(Op::Tree);
+            }
+            Term::Closure(closure) => {
+                let index = self.function(
+                    "closure",
+                    None,
+                    closure.params,
+                    None,
+                    closure.body,
+                    "a closure",
+                );
+                self.emit(Op::Closure(index));
+            }
+        }
+    }
+}
+
+const BIN_OPS: &[BinOp] = &[
+    BinOp::Or,
+    BinOp::And,
+    BinOp::Equals,
+    BinOp::NotEquals,
+    BinOp::Less,
+    BinOp::LessOrEqual,
+    BinOp::Greater,
+    BinOp::GreaterOrEqual,
+    BinOp::Add,
+    BinOp::Sub,
+    BinOp::Mul,
+    BinOp::Div,
+    BinOp::Rem,
+    BinOp::Mom,
+    BinOp::Dad,
+];
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+impl Program {
+    /// Write the program in the `.kinc` format
+    pub fn write_to(&self, mut w: impl Write) -> io::Result<()> {
+        let w = &mut w;
+        w.write_all(MAGIC)?;
+        w.write_all(&[VERSION])?;
+        write_u32(w, self.constants.len() as u32)?;
+        for constant in &self.constants {
+            match constant {
+                Constant::Int(i) => {
+                    w.write_all(&[0])?;
+                    w.write_all(&i.to_le_bytes())?;
+                }
+                Constant::Real(r) => {
+                    w.write_all(&[1])?;
+                    w.write_all(&r.to_le_bytes())?;
+                }
+                Constant::String(s) => {
+                    w.write_all(&[2])?;
+                    write_str(w, s)?;
+                }
+            }
+        }
+        write_u32(w, self.functions.len() as u32)?;
+        for function in &self.functions {
+            write_str(w, &function.name)?;
+            write_u32(w, function.params)?;
+            write_u32(w, function.locals)?;
+            write_u32(w, function.captures.len() as u32)?;
+            for capture in &function.captures {
+                let (tag, i) = match *capture {
+                    CaptureFrom::Local(i) => (0, i),
+                    CaptureFrom::Capture(i) => (1, i),
+                    CaptureFrom::Current => (2, 0),
+                };
+                w.write_all(&[tag])?;
+                write_u32(w, i)?;
+            }
+            write_u32(w, function.code.len() as u32)?;
+            for op in &function.code {
+                write_op(w, *op)?;
+            }
+        }
+        write_u32(w, self.main)
+    }
+    /// Read a program in the `.kinc` format
+    pub fn read_from(mut r: impl Read) -> io::Result<Program> {
+        let r = &mut r;
+        let mut header = [0; 5];
+        r.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(invalid("not a .kinc file"));
+        }
+        if header[4] != VERSION {
+            return Err(invalid(
+                "the .kinc file was made by a different version of Kin",
+            ));
+        }
+        let constants = (0..read_u32(r)?)
+            .map(|_| {
+                Ok(match read_u8(r)? {
+                    0 => Constant::Int(i64::from_le_bytes(read_array(r)?)),
+                    1 => Constant::Real(f64::from_le_bytes(read_array(r)?)),
+                    2 => Constant::String(read_str(r)?),
+                    _ => return Err(invalid("unknown constant")),
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        let functions = (0..read_u32(r)?)
+            .map(|_| {
+                let name = read_str(r)?;
+                let params = read_u32(r)?;
+                let locals = read_u32(r)?;
+                let captures = (0..read_u32(r)?)
+                    .map(|_| {
+                        let tag = read_u8(r)?;
+                        let i = read_u32(r)?;
+                        Ok(match tag {
+                            0 => CaptureFrom::Local(i),
+                            1 => CaptureFrom::Capture(i),
+                            2 => CaptureFrom::Current,
+                            _ => return Err(invalid("unknown capture")),
+                        })
+                    })
+                    .collect::<io::Result<_>>()?;
+                let code = (0..read_u32(r)?)
+                    .map(|_| read_op(r))
+                    .collect::<io::Result<_>>()?;
+                Ok(Function {
+                    name,
+                    params,
+                    locals,
+                    captures,
+                    code,
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        let main = read_u32(r)?;
+        let program = Program {
+            constants,
+            functions,
+            main,
+        };
+        program.validate()?;
+        Ok(program)
+    }
+    /// Check that a program read from a file cannot make the VM misbehave
+    ///
+    /// Every index must be in bounds, including those of the values a closure captures
+    /// from the frame that makes it. Each function needs at most one local per `SetLocal`
+    /// besides its params, and must never pop more values than it has pushed.
+    fn validate(&self) -> io::Result<()> {
+        let functions = self.functions.len() as u32;
+        let constants = self.constants.len() as u32;
+        match self.functions.get(self.main as usize) {
+            None => return Err(invalid("missing main function")),
+            Some(main) if !main.captures.is_empty() => {
+                return Err(invalid("main function has captures"))
+            }
+            Some(_) => {}
+        }
+        for function in &self.functions {
+            let set_locals = function
+                .code
+                .iter()
+                .filter(|op| matches!(op, Op::SetLocal(_)))
+                .count();
+            if function.params > function.locals
+                || (function.locals - function.params) as usize > set_locals
+            {
+                return Err(invalid("wrong number of locals"));
+            }
+            let code = function.code.len() as u32;
+            let captures_in_bounds = |i: u32| {
+                self.functions[i as usize]
+                    .captures
+                    .iter()
+                    .all(|from| match *from {
+                        CaptureFrom::Local(slot) => slot < function.locals,
+                        CaptureFrom::Capture(i) => (i as usize) < function.captures.len(),
+                        CaptureFrom::Current => true,
+                    })
+            };
+            let in_bounds = function.code.iter().all(|op| match *op {
+                Op::Const(i) => i < constants,
+                Op::Builtin(i) => (i as usize) < BUILTIN_FUNCTIONS.len(),
+                Op::Local(i) | Op::SetLocal(i) => i < function.locals,
+                Op::Capture(i) => (i as usize) < function.captures.len(),
+                Op::Closure(i) => i < functions && captures_in_bounds(i),
+                Op::JumpIfFalsy(i) | Op::JumpIfTruthy(i) => i < code,
+                Op::CheckType(_, expected, name) => expected < constants && name < constants,
+                _ => true,
+            });
+            if !in_bounds || function.code.last() != Some(&Op::Return) {
+                return Err(invalid("malformed function"));
+            }
+            if !balanced(&function.code) {
+                return Err(invalid("unbalanced stack"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether code never pops more values than it has pushed
+///
+/// Each op must be reached with the same number of values on the stack from every op
+/// that leads to it. Jump targets must already be in bounds.
+fn balanced(code: &[Op]) -> bool {
+    let mut depths: Vec<Option<u64>> = vec![None; code.len()];
+    let mut pending = vec![(0, 0)];
+    while let Some((ip, depth)) = pending.pop() {
+        match depths[ip] {
+            Some(reached) if reached == depth => continue,
+            Some(_) => return false,
+            None => depths[ip] = Some(depth),
+        }
+        // How many values the op needs, and how many it leaves in their place
+        let (needs, leaves) = match code[ip] {
+            Op::Const(_)
+            | Op::Nil
+            | Op::True
+            | Op::False
+            | Op::Builtin(_)
+            | Op::Local(_)
+            | Op::Capture(_)
+            | Op::Current
+            | Op::Closure(_) => (0, 1),
+            Op::SetLocal(_) | Op::Pop => (1, 0),
+            Op::Call(count) => (count as u64 + 1, 1),
+            Op::JumpIfFalsy(target) | Op::JumpIfTruthy(target) => {
+                if depth < 1 {
+                    return false;
+                }
+                pending.push((target as usize, depth));
+                (1, 0)
+            }
+            Op::Binary(_) => (2, 1),
+            Op::Neg | Op::Head | Op::CheckType(..) => (1, 1),
+            Op::Tree => (3, 1),
+            Op::Return => {
+                if depth < 1 {
+                    return false;
+                }
+                continue;
+            }
+        };
+        if depth < needs {
+            return false;
+        }
+        // Only the last op can end a function, and it returns
+        pending.push((ip + 1, depth - needs + leaves));
+    }
+    true
+}
+
+fn write_u32(w: &mut impl Write, i: u32) -> io::Result<()> {
+    w.write_all(&i.to_le_bytes())
+}
+
+fn write_str(w: &mut impl Write, s: &str) -> io::Result<()> {
+    write_u32(w, s.len() as u32)?;
+    w.write_all(s.as_bytes())
+}
+
+fn write_op(w: &mut impl Write, op: Op) -> io::Result<()> {
+    let (code, operand) = match op {
+        Op::Const(i) => (0, Some(i)),
+        Op::Nil => (1, None),
+        Op::True => (2, None),
+        Op::False => (3, None),
+        Op::Builtin(i) => (4, Some(i)),
+        Op::Local(i) => (5, Some(i)),
+        Op::SetLocal(i) => (6, Some(i)),
+        Op::Capture(i) => (7, Some(i)),
+        Op::Current => (8, None),
+        Op::Closure(i) => (9, Some(i)),
+        Op::Call(i) => (10, Some(i)),
+        Op::Pop => (11, None),
+        Op::JumpIfFalsy(i) => (12, Some(i)),
+        Op::JumpIfTruthy(i) => (13, Some(i)),
+        Op::Binary(op) => {
+            let i = BIN_OPS.iter().position(|&o| o == op).unwrap();
+            (14, Some(i as u32))
+        }
+        Op::Neg => (15, None),
+        Op::Head => (16, None),
+        Op::Tree => (17, None),
+        Op::CheckType(types, expected, name) => {
+            w.write_all(&[18, types])?;
+            write_u32(w, expected)?;
+            return write_u32(w, name);
+        }
+        Op::Return => (19, None),
+    };
+    w.write_all(&[code])?;
+    match operand {
+        Some(i) => write_u32(w, i),
+        None => Ok(()),
+    }
+}
+
+fn read_array<const N: usize>(r: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    r.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_u8(r: &mut impl Read) -> io::Result<u8> {
+    Ok(read_array::<1>(r)?[0])
+}
+
+fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+    Ok(u32::from_le_bytes(read_array(r)?))
+}
+
+fn read_str(r: &mut impl Read) -> io::Result<String> {
+    let len = read_u32(r)? as usize;
+    let mut bytes = Vec::new();
+    r.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    String::from_utf8(bytes).map_err(|_| invalid("string is not UTF-8"))
+}
+
+fn read_op(r: &mut impl Read) -> io::Result<Op> {
+    Ok(match read_u8(r)? {
+        0 => Op::Const(read_u32(r)?),
+        1 => Op::Nil,
+        2 => Op::True,
+        3 => Op::False,
+        4 => Op::Builtin(read_u32(r)?),
+        5 => Op::Local(read_u32(r)?),
+        6 => Op::SetLocal(read_u32(r)?),
+        7 => Op::Capture(read_u32(r)?),
+        8 => Op::Current,
+        9 => Op::Closure(read_u32(r)?),
+        10 => Op::Call(read_u32(r)?),
+        11 => Op::Pop,
+        12 => Op::JumpIfFalsy(read_u32(r)?),
+        13 => Op::JumpIfTruthy(read_u32(r)?),
+        14 => match BIN_OPS.get(read_u32(r)? as usize) {
+            Some(BinOp::And) | Some(BinOp::Or) | None => return Err(invalid("unknown operator")),
+            Some(&op) => Op::Binary(op),
+        },
+        15 => Op::Neg,
+        16 => Op::Head,
+        17 => Op::Tree,
+        18 => Op::CheckType(read_u8(r)?, read_u32(r)?, read_u32(r)?),
+        19 => Op::Return,
+        _ => return Err(invalid("unknown op")),
+    })
+}
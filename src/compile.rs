@@ -0,0 +1,862 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    fmt, fs,
+    hash::{Hash, Hasher},
+    io,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    str::FromStr,
+};
+
+use clap::Clap;
+use itertools::Itertools;
+use serde::{de, Deserialize, Deserializer};
+
+use crate::{
+    ast::Items,
+    bytecode::{self, Program},
+    infer::infer,
+    js::transpile_js,
+    optimize::optimize,
+    parse::{parse, TranspileError},
+    transpile::{transpile_with, Runtime, Transpilation, TranspileOptions, RUNTIME_HEADER},
+    wat::transpile_wat,
+};
+
+#[derive(Debug)]
+pub enum CompileError<'a> {
+    Check(Vec<TranspileError<'a>>),
+    Io(io::Error),
+    NoCCompiler,
+    SanitizersUnsupported(CCompiler),
+    CCompiler(String),
+    /// Top-level functions of a library that capture values
+    Unexportable(Vec<&'a str>),
+    /// A library name that would give invalid or clashing export names
+    InvalidLibraryName(String),
+}
+
+impl<'a> fmt::Display for CompileError<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompileError::Check(errors) => {
+                for (i, error) in errors.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}", error)?;
+                }
+                if let Some(error) = errors.first() {
+                    write!(
+                        f,
+                        "\nFor more information about an error, try `kin explain {}`",
+                        error.code()
+                    )?;
+                }
+                Ok(())
+            }
+            CompileError::Io(e) => write!(f, "{}", e),
+            CompileError::NoCCompiler => write!(f, "No compatible C compiler detected."),
+            CompileError::SanitizersUnsupported(comp) => {
+                write!(f, "{} does not support sanitizers", comp.name())
+            }
+            CompileError::CCompiler(stderr) => write!(f, "C compilation failed\n{}", stderr),
+            CompileError::Unexportable(names) => write!(
+                f,
+                "A library can only export functions that use no top-level values, but these do: {}",
+                names.iter().map(|name| format!("`{}`", name)).join(", ")
+            ),
+            CompileError::InvalidLibraryName(name) => write!(
+                f,
+                "A library cannot be named `{}`. Exports are prefixed with the library name, \
+                so it must not be empty, start with a digit, or start with {} and an underscore",
+                name,
+                RESERVED_LIBRARY_PREFIXES.join(", ")
+            ),
+        }
+    }
+}
+
+impl<'a> std::error::Error for CompileError<'a> {}
+
+impl<'a> From<io::Error> for CompileError<'a> {
+    fn from(e: io::Error) -> Self {
+        CompileError::Io(e)
+    }
+}
+
+/// Prefixes of names in the runtime and the generated C, which exports must not share
+const RESERVED_LIBRARY_PREFIXES: &[&str] = &["kin", "KIN", "k", "new", "bin"];
+
+/// A builder for compiling Kin source to C and to executables
+#[derive(Debug, Clone)]
+pub struct Compiler {
+    build_args: BuildArgs,
+    runtime: Runtime,
+    out_dir: PathBuf,
+    output: PathBuf,
+    tests: bool,
+    inherit_output: bool,
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Compiler::new()
+    }
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        Compiler {
+            build_args: BuildArgs::default(),
+            runtime: Runtime::Bundled,
+            out_dir: "build".into(),
+            output: "main".into(),
+            tests: false,
+            inherit_output: false,
+        }
+    }
+    /// Set the args used when compiling C
+    pub fn build_args(self, build_args: BuildArgs) -> Self {
+        Compiler { build_args, ..self }
+    }
+    /// Set where the runtime header comes from
+    pub fn runtime(self, runtime: Runtime) -> Self {
+        Compiler { runtime, ..self }
+    }
+    /// Set the directory that generated C is written to
+    pub fn out_dir(self, out_dir: impl Into<PathBuf>) -> Self {
+        Compiler {
+            out_dir: out_dir.into(),
+            ..self
+        }
+    }
+    /// Set the path of the executable, without an extension
+    pub fn output(self, output: impl Into<PathBuf>) -> Self {
+        Compiler {
+            output: output.into(),
+            ..self
+        }
+    }
+    /// Transpile a test harness instead of a regular program
+    pub fn tests(self, tests: bool) -> Self {
+        Compiler { tests, ..self }
+    }
+    /// Let the C compiler write to this process's stdout and stderr
+    /// instead of capturing its errors
+    pub fn inherit_output(self, inherit_output: bool) -> Self {
+        Compiler {
+            inherit_output,
+            ..self
+        }
+    }
+    /// The path of the generated C file
+    pub fn c_path(&self) -> PathBuf {
+        self.out_dir.join("main.c")
+    }
+    /// The name of a library, which prefixes its exported symbols
+    pub fn library_name(&self) -> String {
+        let name = self
+            .output
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        name.chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect()
+    }
+    /// The path of a library's header
+    pub fn header_path(&self) -> PathBuf {
+        self.out_dir.join(format!("{}.h", self.library_name()))
+    }
+    /// The path of the generated WebAssembly text
+    pub fn wat_path(&self) -> PathBuf {
+        self.output_with(".wat")
+    }
+    /// The path of the generated JavaScript
+    pub fn js_path(&self) -> PathBuf {
+        self.output_with(".js")
+    }
+    /// The path of the compiled bytecode
+    pub fn kinc_path(&self) -> PathBuf {
+        self.output_with(".kinc")
+    }
+    fn output_with(&self, ext: &str) -> PathBuf {
+        let mut path = self.output.clone().into_os_string();
+        path.push(ext);
+        path.into()
+    }
+    /// The path of the executable, or assembly or a library object if that was requested
+    pub fn executable_path(&self) -> PathBuf {
+        let mut path = self.output.clone().into_os_string();
+        path.push(if self.build_args.assembly {
+            ".asm"
+        } else if self.is_library() {
+            ".o"
+        } else {
+            EXE_EXT
+        });
+        path.into()
+    }
+    /// Whether the output is a library rather than an executable
+    ///
+    /// Tests are always built as an executable.
+    fn is_library(&self) -> bool {
+        self.build_args.lib && !self.tests
+    }
+    /// Parse and type check Kin source
+    pub fn check<'a>(&self, source: &'a str) -> Result<Items<'a>, CompileError<'a>> {
+        let mut items = parse(source).map_err(CompileError::Check)?;
+        let errors = infer(
+            &mut items,
+            !self.build_args.no_type_checks,
+            self.is_library(),
+        );
+        if errors.is_empty() {
+            Ok(items)
+        } else {
+            Err(CompileError::Check(errors))
+        }
+    }
+    /// Optimize and transpile checked items
+    pub fn transpile<'a>(&self, items: Items<'a>) -> Transpilation<'a> {
+        let options = TranspileOptions {
+            test_harness: self.tests,
+            inline_ops: self.build_args.build_profile() == BuildProfile::Release,
+            no_type_checks: self.build_args.no_type_checks,
+            library: Some(self.library_name()).filter(|_| self.is_library()),
+        };
+        transpile_with(optimize(items), options)
+    }
+    /// Optimize and transpile checked items to WebAssembly text
+    pub fn transpile_wat(&self, items: Items) -> String {
+        let options = TranspileOptions {
+            test_harness: false,
+            inline_ops: true,
+            no_type_checks: self.build_args.no_type_checks,
+            library: None,
+        };
+        transpile_wat(optimize(items), options)
+    }
+    /// Optimize and transpile checked items to JavaScript
+    pub fn transpile_js(&self, items: Items) -> String {
+        let options = TranspileOptions {
+            test_harness: false,
+            inline_ops: true,
+            no_type_checks: self.build_args.no_type_checks,
+            library: None,
+        };
+        transpile_js(optimize(items), options)
+    }
+    /// Optimize and compile checked items to bytecode for the VM
+    pub fn bytecode(&self, items: Items) -> Program {
+        let options = TranspileOptions {
+            test_harness: false,
+            inline_ops: true,
+            no_type_checks: self.build_args.no_type_checks,
+            library: None,
+        };
+        bytecode::compile(optimize(items), options)
+    }
+    /// Transpile checked items and write the WebAssembly text next to the output
+    pub fn write_wat<'a>(&self, items: Items<'a>) -> Result<PathBuf, CompileError<'a>> {
+        let path = self.wat_path();
+        write_creating_dir(&path, self.transpile_wat(items))?;
+        Ok(path)
+    }
+    /// Transpile checked items and write the JavaScript next to the output
+    pub fn write_js<'a>(&self, items: Items<'a>) -> Result<PathBuf, CompileError<'a>> {
+        let path = self.js_path();
+        write_creating_dir(&path, self.transpile_js(items))?;
+        Ok(path)
+    }
+    /// Compile checked items and write the bytecode next to the output
+    pub fn write_kinc<'a>(&self, items: Items<'a>) -> Result<PathBuf, CompileError<'a>> {
+        let path = self.kinc_path();
+        let mut bytes = Vec::new();
+        self.bytecode(items).write_to(&mut bytes)?;
+        write_creating_dir(&path, bytes)?;
+        Ok(path)
+    }
+    /// Transpile checked items and write the C to the output directory
+    ///
+    /// A library also gets a header that declares its exports.
+    pub fn write_c<'a>(&self, items: Items<'a>) -> Result<PathBuf, CompileError<'a>> {
+        if self.is_library() {
+            let name = self.library_name();
+            let reserved = RESERVED_LIBRARY_PREFIXES
+                .iter()
+                .any(|prefix| name == *prefix || name.starts_with(&format!("{}_", prefix)));
+            if reserved || name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+                return Err(CompileError::InvalidLibraryName(name));
+            }
+        }
+        let transpilation = self.transpile(items);
+        if self.is_library() {
+            if !transpilation.unexported().is_empty() {
+                return Err(CompileError::Unexportable(
+                    transpilation.unexported().to_vec(),
+                ));
+            }
+            fs::create_dir_all(&self.out_dir)?;
+            let header = io::BufWriter::new(fs::File::create(self.header_path())?);
+            transpilation.write_header(header, &self.runtime)?;
+        }
+        transpilation.write(&self.out_dir, &self.runtime)?;
+        Ok(self.c_path())
+    }
+    /// Check and transpile Kin source, returning the generated C
+    pub fn c_source<'a>(&self, source: &'a str) -> Result<String, CompileError<'a>> {
+        let items = self.check(source)?;
+        Ok(self.transpile(items).into_c_source(&self.runtime))
+    }
+    /// Compile the C in the output directory, returning the path of the executable
+    pub fn compile_c<'a>(&self) -> Result<PathBuf, CompileError<'a>> {
+        let ccomp = self
+            .build_args
+            .c_compiler()
+            .ok_or(CompileError::NoCCompiler)?;
+        let args = self.c_compiler_args(&ccomp)?;
+        let mut command = ccomp.command();
+        command.args(args);
+        if self.inherit_output {
+            if !command.status()?.success() {
+                return Err(CompileError::CCompiler(String::new()));
+            }
+        } else {
+            let output = command.stdin(Stdio::null()).output()?;
+            if !output.status.success() {
+                return Err(CompileError::CCompiler(
+                    String::from_utf8_lossy(&output.stderr).into_owned(),
+                ));
+            }
+        }
+        // A missing hash only means the next build cannot be skipped
+        if let Ok(hash) = self.build_hash() {
+            let _ = fs::write(self.hash_path(), hash);
+        }
+        Ok(self.executable_path())
+    }
+    /// Whether the executable was compiled from the current C with the current flags
+    ///
+    /// The generated C stands in for the Kin source, so a new version of Kin also
+    /// counts as a change.
+    pub fn is_compiled(&self) -> bool {
+        self.executable_path().is_file()
+            && matches!(
+                (self.build_hash(), fs::read_to_string(self.hash_path())),
+                (Ok(hash), Ok(stored)) if hash == stored.trim()
+            )
+    }
+    /// Compile the C in the output directory unless nothing has changed since the last
+    /// build, returning the path of the executable and whether it was compiled
+    pub fn compile_c_if_changed<'a>(&self) -> Result<(PathBuf, bool), CompileError<'a>> {
+        if self.is_compiled() {
+            Ok((self.executable_path(), false))
+        } else {
+            Ok((self.compile_c()?, true))
+        }
+    }
+    /// The path of the hash of the last build's inputs
+    fn hash_path(&self) -> PathBuf {
+        let executable = self.executable_path();
+        let name = executable.file_name().unwrap_or_default().to_string_lossy();
+        self.out_dir.join(format!("{}.hash", name))
+    }
+    /// Hash the generated C, the other C sources, the C compiler and its flags, and the
+    /// runtime header
+    fn build_hash(&self) -> io::Result<String> {
+        let mut hasher = DefaultHasher::new();
+        fs::read(self.c_path())?.hash(&mut hasher);
+        let ccomp = self.build_args.c_compiler();
+        ccomp.as_ref().map(CCompiler::name).hash(&mut hasher);
+        if let Some(ccomp) = &ccomp {
+            let args = self.c_compiler_args(ccomp).unwrap_or_default();
+            args.hash(&mut hasher);
+        }
+        for path in &self.build_args.c_sources {
+            fs::read(path)?.hash(&mut hasher);
+        }
+        match &self.runtime {
+            Runtime::Bundled => RUNTIME_HEADER.hash(&mut hasher),
+            // The inline header is part of the C
+            Runtime::Inline => {}
+            Runtime::External(path) => fs::read(path)?.hash(&mut hasher),
+        }
+        Ok(format!("{:016x}", hasher.finish()))
+    }
+    /// Check, transpile, and compile Kin source, returning the path of the executable
+    pub fn executable<'a>(&self, source: &'a str) -> Result<PathBuf, CompileError<'a>> {
+        let items = self.check(source)?;
+        self.write_c(items)?;
+        self.compile_c()
+    }
+    fn c_compiler_args<'a>(&self, ccomp: &CCompiler) -> Result<Vec<String>, CompileError<'a>> {
+        let build_args = &self.build_args;
+        // A library is compiled on its own and the host links the rest
+        let lib = self.is_library();
+        let mut args: Vec<String> = vec![self.c_path().to_string_lossy().into_owned()];
+        if lib {
+            args.push("-c".into());
+        } else {
+            args.extend(
+                build_args
+                    .c_sources
+                    .iter()
+                    .map(|path| path.to_string_lossy().into_owned()),
+            );
+        }
+        args.push("-o".into());
+
+        // Push target arg
+        args.push(self.executable_path().to_string_lossy().into_owned());
+        if build_args.assembly {
+            args.push("-S".into());
+        }
+
+        // Push opt arg
+        let build_profile = build_args.build_profile();
+        args.push(build_args.opt_level(build_profile).arg().into());
+        if build_profile == BuildProfile::Debug {
+            args.push("-g".into());
+        }
+
+        // Push C standard arg
+        args.push("-std=c99".into());
+
+        // Push sanitizer args
+        if !build_args.sanitizers.is_empty() {
+            if !ccomp.supports_sanitizers() {
+                return Err(CompileError::SanitizersUnsupported(ccomp.clone()));
+            }
+            let sanitizers: String = build_args
+                .sanitizers
+                .iter()
+                .map(Sanitizer::name)
+                .intersperse(",")
+                .collect();
+            args.push(format!("-fsanitize={}", sanitizers));
+            args.push("-fno-omit-frame-pointer".into());
+            if build_profile == BuildProfile::Release {
+                args.push("-g".into());
+            }
+        }
+
+        // Push stack size arg
+        if let Some(size) = build_args.stack_size {
+            let size = size * 1024 * 1024;
+            args.extend(ccomp.stack_size_arg(size));
+        }
+
+        // Push profile arg
+        if build_args.profile {
+            args.push("-pg".into());
+        }
+
+        // Push link args
+        if !lib {
+            args.extend(build_args.link.iter().map(|lib| format!("-l{}", lib)));
+            args.extend(ccomp.link_args().iter().map(|&arg| arg.into()));
+        }
+
+        // Push extra C flags
+        if let Some(cflags) = &build_args.cflags {
+            args.extend(cflags.split_whitespace().map(Into::into));
+        }
+        Ok(args)
+    }
+}
+
+#[derive(Debug, Clone, Default, Clap, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BuildArgs {
+    #[clap(long = "stack", about = "The executable stack size in MB")]
+    #[serde(rename = "stack")]
+    pub stack_size: Option<usize>,
+    #[clap(about = "The C compiler to use (gcc, clang, tcc, or custom to use $CC)")]
+    #[serde(deserialize_with = "from_str_opt")]
+    pub compiler: Option<CCompiler>,
+    #[clap(long = "asm")]
+    #[serde(rename = "asm")]
+    pub assembly: bool,
+    #[clap(long = "profile")]
+    pub profile: bool,
+    #[clap(
+        long = "opt",
+        about = "The optimization level (0, 1, 2, 3, or s). Overrides the build profile"
+    )]
+    #[serde(rename = "opt", deserialize_with = "from_str_opt")]
+    pub opt_level: Option<OptLevel>,
+    #[clap(
+        long = "release",
+        about = "Build with optimizations (the default)",
+        conflicts_with = "debug"
+    )]
+    pub release: bool,
+    #[clap(
+        long = "debug",
+        about = "Build without optimizations and with debug info"
+    )]
+    pub debug: bool,
+    #[clap(
+        long = "sanitize",
+        about = "Comma-separated sanitizers to build with (address, undefined)",
+        use_delimiter = true
+    )]
+    #[serde(rename = "sanitize", deserialize_with = "from_str_vec")]
+    pub sanitizers: Vec<Sanitizer>,
+    #[clap(
+        long = "cflags",
+        about = "Extra flags to pass to the C compiler",
+        allow_hyphen_values = true
+    )]
+    pub cflags: Option<String>,
+    #[clap(
+        long = "no-type-checks",
+        about = "Leave out the runtime checks of type annotations"
+    )]
+    #[serde(rename = "no-type-checks")]
+    pub no_type_checks: bool,
+    #[clap(
+        long = "target",
+        about = "What to compile to (c, wat for WebAssembly text, js for JavaScript, or kinc for VM bytecode)"
+    )]
+    #[serde(deserialize_with = "from_str_opt")]
+    pub target: Option<Target>,
+    #[clap(
+        long = "link",
+        about = "Comma-separated libraries to link, such as m for the C math library",
+        use_delimiter = true
+    )]
+    pub link: Vec<String>,
+    #[clap(
+        long = "c-source",
+        about = "Comma-separated C files to compile with the program, such as ones that define externs",
+        use_delimiter = true
+    )]
+    #[serde(rename = "c-source")]
+    pub c_sources: Vec<PathBuf>,
+    #[clap(
+        long = "lib",
+        about = "Build an object file and a header that exports the top-level functions, instead of an executable"
+    )]
+    pub lib: bool,
+}
+
+impl BuildArgs {
+    /// Fill in any unset args from the defaults
+    pub fn or(self, defaults: &BuildArgs) -> BuildArgs {
+        let profile_set = self.release || self.debug;
+        BuildArgs {
+            stack_size: self.stack_size.or(defaults.stack_size),
+            compiler: self.compiler.or_else(|| defaults.compiler.clone()),
+            assembly: self.assembly || defaults.assembly,
+            profile: self.profile || defaults.profile,
+            opt_level: self.opt_level.or(defaults.opt_level),
+            release: if profile_set {
+                self.release
+            } else {
+                defaults.release
+            },
+            debug: if profile_set {
+                self.debug
+            } else {
+                defaults.debug
+            },
+            sanitizers: if self.sanitizers.is_empty() {
+                defaults.sanitizers.clone()
+            } else {
+                self.sanitizers
+            },
+            cflags: match (&defaults.cflags, self.cflags) {
+                (Some(default), Some(cflags)) => Some(format!("{} {}", default, cflags)),
+                (default, cflags) => cflags.or_else(|| default.clone()),
+            },
+            no_type_checks: self.no_type_checks || defaults.no_type_checks,
+            target: self.target.or(defaults.target),
+            link: defaults.link.iter().cloned().chain(self.link).collect(),
+            c_sources: defaults
+                .c_sources
+                .iter()
+                .cloned()
+                .chain(self.c_sources)
+                .collect(),
+            lib: self.lib || defaults.lib,
+        }
+    }
+    pub fn target(&self) -> Target {
+        self.target.unwrap_or(Target::C)
+    }
+    /// Get the C compiler to use
+    pub fn c_compiler(&self) -> Option<CCompiler> {
+        self.compiler.clone().or_else(CCompiler::find)
+    }
+    pub fn build_profile(&self) -> BuildProfile {
+        if self.debug && !self.release {
+            BuildProfile::Debug
+        } else {
+            BuildProfile::Release
+        }
+    }
+    pub fn opt_level(&self, profile: BuildProfile) -> OptLevel {
+        self.opt_level.unwrap_or(match profile {
+            BuildProfile::Debug => OptLevel::O0,
+            BuildProfile::Release => OptLevel::O3,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildProfile {
+    Debug,
+    Release,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptLevel {
+    O0,
+    O1,
+    O2,
+    O3,
+    Size,
+}
+
+impl OptLevel {
+    const ALL: &'static [Self] = &[
+        OptLevel::O0,
+        OptLevel::O1,
+        OptLevel::O2,
+        OptLevel::O3,
+        OptLevel::Size,
+    ];
+    pub fn name(&self) -> &'static str {
+        ["0", "1", "2", "3", "s"][*self as usize]
+    }
+    pub fn arg(&self) -> &'static str {
+        ["-O0", "-O1", "-O2", "-O3", "-Os"][*self as usize]
+    }
+}
+
+impl FromStr for OptLevel {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use anyhow::anyhow;
+        Self::ALL
+            .iter()
+            .find(|level| level.name() == s)
+            .copied()
+            .ok_or_else(|| anyhow!("Unknown optimization level {:?}", s))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    C,
+    Wat,
+    Js,
+    Kinc,
+}
+
+impl Target {
+    const ALL: &'static [Self] = &[Target::C, Target::Wat, Target::Js, Target::Kinc];
+    pub fn name(&self) -> &'static str {
+        ["c", "wat", "js", "kinc"][*self as usize]
+    }
+}
+
+impl FromStr for Target {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use anyhow::anyhow;
+        Self::ALL
+            .iter()
+            .find(|target| target.name() == s)
+            .copied()
+            .ok_or_else(|| anyhow!("Unknown target {:?}", s))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sanitizer {
+    Address,
+    Undefined,
+}
+
+impl Sanitizer {
+    const ALL: &'static [Self] = &[Sanitizer::Address, Sanitizer::Undefined];
+    pub fn name(&self) -> &'static str {
+        ["address", "undefined"][*self as usize]
+    }
+}
+
+impl FromStr for Sanitizer {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use anyhow::anyhow;
+        Self::ALL
+            .iter()
+            .find(|san| san.name() == s)
+            .copied()
+            .ok_or_else(|| anyhow!("Unsupported or unknown sanitizer {:?}", s))
+    }
+}
+
+fn write_creating_dir(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, contents)
+}
+
+fn from_str_opt<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr<Err = anyhow::Error>,
+{
+    let s = String::deserialize(deserializer)?;
+    s.parse().map(Some).map_err(de::Error::custom)
+}
+
+fn from_str_vec<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr<Err = anyhow::Error>,
+{
+    let strings = Vec::<String>::deserialize(deserializer)?;
+    strings
+        .iter()
+        .map(|s| s.parse().map_err(de::Error::custom))
+        .collect()
+}
+
+pub const EXE_EXT: &str = if cfg!(windows) { ".exe" } else { "" };
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetOs {
+    Windows,
+    MacOs,
+    Linux,
+}
+
+impl TargetOs {
+    pub fn current() -> Self {
+        if cfg!(windows) {
+            TargetOs::Windows
+        } else if cfg!(target_os = "macos") {
+            TargetOs::MacOs
+        } else {
+            TargetOs::Linux
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CCompiler {
+    Gcc,
+    Clang,
+    Tcc,
+    /// A gcc-compatible compiler read from the `CC` environment variable, which may be
+    /// a command with leading args, such as `ccache gcc` or `zig cc`
+    Custom(String),
+}
+
+impl CCompiler {
+    const KNOWN: &'static [Self] = &[CCompiler::Gcc, CCompiler::Clang, CCompiler::Tcc];
+    /// Find the C compiler to use, either from `$CC` or the first known compiler installed
+    pub fn find() -> Option<Self> {
+        if let Some(comp) = Self::from_env() {
+            return Some(comp);
+        }
+        Self::KNOWN
+            .iter()
+            .find(|comp| {
+                comp.command()
+                    .arg("-v")
+                    .output()
+                    .is_ok_and(|output| output.status.success())
+            })
+            .cloned()
+    }
+    pub fn from_env() -> Option<Self> {
+        let cc = std::env::var("CC").ok()?;
+        let cc = cc.trim();
+        if cc.is_empty() {
+            return None;
+        }
+        Some(
+            Self::KNOWN
+                .iter()
+                .find(|comp| comp.name() == cc)
+                .cloned()
+                .unwrap_or_else(|| CCompiler::Custom(cc.into())),
+        )
+    }
+    pub fn name(&self) -> &str {
+        match self {
+            CCompiler::Gcc => "gcc",
+            CCompiler::Clang => "clang",
+            CCompiler::Tcc => "tcc",
+            CCompiler::Custom(name) => name,
+        }
+    }
+    /// A command that runs the compiler, with a custom compiler's leading args
+    pub fn command(&self) -> Command {
+        let mut words = self.name().split_whitespace();
+        let mut command = Command::new(words.next().unwrap_or_default());
+        command.args(words);
+        command
+    }
+    pub fn supports_sanitizers(&self) -> bool {
+        !matches!(self, CCompiler::Tcc)
+    }
+    pub fn stack_size_arg(&self, size: usize) -> Option<String> {
+        Some(match (TargetOs::current(), self) {
+            (TargetOs::Windows, CCompiler::Clang) => format!("-Wl,-stack:{}", size),
+            (TargetOs::Windows, CCompiler::Tcc) => format!("-Wl,-stack={}", size),
+            (TargetOs::Windows, _) => format!("-Wl,--stack,{}", size),
+            (TargetOs::MacOs, CCompiler::Tcc) | (TargetOs::Linux, CCompiler::Tcc) => return None,
+            (TargetOs::MacOs, _) => format!("-Wl,-stack_size,{:#x}", size),
+            (TargetOs::Linux, _) => format!("-Wl,-z,stacksize={}", size),
+        })
+    }
+    /// Get a note about setting the stack size that should be shown to the user
+    pub fn stack_size_note(&self, size: usize) -> Option<String> {
+        match TargetOs::current() {
+            TargetOs::Linux => Some(format!(
+                "Note: the main thread's stack size on Linux is limited by the shell. \
+                Run `ulimit -s {}` before running the executable.",
+                size / 1024
+            )),
+            TargetOs::MacOs if *self == CCompiler::Tcc => Some(format!(
+                "Note: tcc cannot set the stack size on macOS. \
+                Run `ulimit -s {}` before running the executable.",
+                size / 1024
+            )),
+            _ => None,
+        }
+    }
+    /// Libraries the runtime needs, such as the math library for `fmod`
+    pub fn link_args(&self) -> &'static [&'static str] {
+        match TargetOs::current() {
+            TargetOs::Windows => &[],
+            TargetOs::MacOs | TargetOs::Linux => &["-lm"],
+        }
+    }
+}
+
+impl FromStr for CCompiler {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use anyhow::anyhow;
+        if s == "custom" {
+            return Self::from_env().ok_or_else(|| {
+                anyhow!("The CC environment variable must be set to use a custom compiler")
+            });
+        }
+        Self::KNOWN
+            .iter()
+            .find(|comp| comp.name() == s)
+            .cloned()
+            .ok_or_else(|| anyhow!("Unsupported or unknown compiler {:?}", s))
+    }
+}
@@ -0,0 +1,318 @@
+use std::fmt;
+
+use pest::Span;
+
+/// A labeled span in a diagnostic
+#[derive(Debug, Clone)]
+pub struct Label<'a> {
+    pub span: Span<'a>,
+    pub message: String,
+    pub primary: bool,
+}
+
+/// A rendered error with a code, labeled spans, and notes
+#[derive(Debug, Clone)]
+pub struct Diagnostic<'a> {
+    pub code: &'static str,
+    pub message: String,
+    pub labels: Vec<Label<'a>>,
+    pub notes: Vec<String>,
+}
+
+impl<'a> Diagnostic<'a> {
+    pub fn new(code: &'static str, message: impl Into<String>) -> Self {
+        Diagnostic {
+            code,
+            message: message.into(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+    /// Add the label that points at the cause of the error
+    pub fn primary(mut self, span: Span<'a>, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+            primary: true,
+        });
+        self
+    }
+    /// Add a label that points at related code
+    pub fn secondary(mut self, span: Span<'a>, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+            primary: false,
+        });
+        self
+    }
+    pub fn note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+}
+
+impl<'a> fmt::Display for Diagnostic<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "error[{}]: {}", self.code, self.message)?;
+        let mut labels: Vec<&Label> = self.labels.iter().collect();
+        labels.sort_by_key(|label| label.span.start());
+        let gutter = labels
+            .iter()
+            .map(|label| label.span.start_pos().line_col().0.to_string().len())
+            .max()
+            .unwrap_or(1);
+        if let Some(primary) = labels.iter().find(|label| label.primary) {
+            let (line, col) = primary.span.start_pos().line_col();
+            writeln!(f, "{:gutter$}--> {}:{}", "", line, col, gutter = gutter)?;
+        }
+        writeln!(f, "{:gutter$} |", "", gutter = gutter)?;
+        let mut prev_line = None;
+        for label in labels {
+            let start = label.span.start_pos();
+            let (line, col) = start.line_col();
+            let text = start.line_of().trim_end_matches(&['\r', '\n'][..]);
+            if prev_line.is_some_and(|prev| line > prev + 1) {
+                writeln!(f, "{:gutter$}...", "", gutter = gutter)?;
+            }
+            if prev_line != Some(line) {
+                writeln!(f, "{:>gutter$} | {}", line, text, gutter = gutter)?;
+            }
+            prev_line = Some(line);
+            // Only the first line of a multi-line span is underlined
+            let prefix: String = text.chars().take(col - 1).collect();
+            let width = label
+                .span
+                .as_str()
+                .lines()
+                .next()
+                .map_or(0, |s| s.chars().count())
+                .max(1);
+            let underline = if label.primary { "^" } else { "-" };
+            let indent: String = prefix
+                .chars()
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            write!(
+                f,
+                "{:gutter$} | {}{}",
+                "",
+                indent,
+                underline.repeat(width),
+                gutter = gutter
+            )?;
+            if label.message.is_empty() {
+                writeln!(f)?;
+            } else {
+                writeln!(f, " {}", label.message)?;
+            }
+        }
+        if !self.notes.is_empty() {
+            writeln!(f, "{:gutter$} |", "", gutter = gutter)?;
+        }
+        for note in &self.notes {
+            writeln!(f, "{:gutter$} = note: {}", "", note, gutter = gutter)?;
+        }
+        Ok(())
+    }
+}
+
+/// The long description of every error code, for `kin explain`
+pub static EXPLANATIONS: &[(&str, &str)] = &[
+    (
+        "K0001",
+        "\
+The source could not be parsed.
+
+Every syntax error is reported, not just the first. After an error, parsing
+resumes at the next item that starts a line, and at the items inside a block
+that ends with `end`.
+
+    x = (1 + 2    -- the parenthesis is never closed
+    println x
+",
+    ),
+    (
+        "K0002",
+        "\
+A name was used that is not bound in any enclosing scope or as a builtin.
+
+Defs are only visible after they are defined, and defs made inside a function
+or parentheses are not visible outside of them.
+
+    x = 5
+    println y    -- y is never defined
+
+If there is a bound name that is close to the unknown one, it is suggested.
+",
+    ),
+    (
+        "K0003",
+        "\
+A number literal could not be represented.
+
+Ints must fit in 64 bits.
+
+    x = 99999999999999999999
+",
+    ),
+    (
+        "K0004",
+        "\
+Def names may not start or end with `_`.
+
+Names of that form are reserved for the generated C. The name `_` on its own
+is allowed.
+
+    _x = 5
+",
+    ),
+    (
+        "K0005",
+        "\
+A function was named `_`.
+
+A def named `_` evaluates its body in place, so it may not take parameters.
+
+    _ x = x + 1
+",
+    ),
+    (
+        "K0006",
+        "\
+A function or block returns a value that references a value local to it.
+
+Local values are freed when the function that defines them returns, so a
+return value may not hold a reference to one. For example, the list below
+has the local `xs` as its tail:
+
+    f x =
+        xs = [1 2]
+        x:xs
+    end
+
+The error traces the chain of references from the returned value to the local
+value that it depends on. Return the value itself or build the returned value
+from the parameters.
+",
+    ),
+    (
+        "K0007",
+        "\
+A builtin value was redefined.
+
+`nil`, `true`, and `false` are provided by the runtime and may not be
+redefined, either as defs or as parameters.
+
+    true = 5
+",
+    ),
+    (
+        "K0008",
+        "\
+The last item in a block is not an expression.
+
+The value of a block is the value of its last item, so it may not be a def.
+
+    f x =
+        y = x + 1
+    end
+",
+    ),
+    (
+        "K0009",
+        "\
+A source file is too long to compile.
+
+The compiler counts params, captures, and bytecode with 32 bits, so it only
+accepts sources of up to 268435455 bytes.
+",
+    ),
+    (
+        "K0010",
+        "\
+An operator is applied to values whose types it can never accept.
+
+Arithmetic works on ints and reals, and ordering compares two bools, two
+numbers, two strings, two functions, or two errors. When the types of both
+operands are known and no combination of them is valid, the operation would
+always panic at runtime.
+
+    1 < \"hi\"    -- cannot compare int and string
+",
+    ),
+    (
+        "K0011",
+        "\
+A value that can never be a number is negated.
+
+Only ints and reals can be negated.
+
+    -\"hi\"    -- cannot negate string
+",
+    ),
+    (
+        "K0012",
+        "\
+A value that can never be a function is called.
+
+    x = 5
+    x 1    -- cannot call int
+",
+    ),
+    (
+        "K0013",
+        "\
+A type annotation names a type that does not exist.
+
+The types are `nil`, `bool`, `int`, `real`, `string`, `function`, and `error`.
+
+    half x: number = x / 2    -- `number` is not a type
+",
+    ),
+    (
+        "K0014",
+        "\
+A value can never have the type its annotation requires.
+
+Annotations are checked at runtime, so this would always panic.
+
+    double x: int = x * 2
+    double \"twice\"    -- expected param `x` of `double` to be int
+",
+    ),
+    (
+        "K0015",
+        "\
+A top-level def named `test_*` takes params.
+
+`kin test` runs each test by evaluating its def, so there are no arguments to
+pass. Write the test as a def with no params, using a block for several items.
+
+    test_add =
+        assert (1 + 1 == 2)
+    end
+",
+    ),
+    (
+        "K0016",
+        "\
+An extern has a name that the generated C cannot declare.
+
+Externs call C functions by their own name, so the name cannot be a C keyword,
+`main`, a name the generated C uses for its locals such as `count` or `args`,
+or a runtime name such as those starting with `kin_`, `new_`, `KIN_`, or `k_`.
+Give the C function another name.
+
+    extern count 1    -- `count` is an argument of every generated function
+",
+    ),
+];
+
+/// Get the long description of an error code
+pub fn explain(code: &str) -> Option<&'static str> {
+    EXPLANATIONS
+        .iter()
+        .find(|(c, _)| c.eq_ignore_ascii_case(code))
+        .map(|(_, explanation)| *explanation)
+}
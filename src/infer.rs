@@ -0,0 +1,391 @@
+use std::collections::HashMap;
+
+use pest::Span;
+
+use crate::{ast::*, parse::TranspileError, transpile::BUILTIN_FUNCTIONS};
+
+/// Infer the types of nodes and report operations that can never succeed
+///
+/// Inference is flow-insensitive: each binding has a single type, the union of every
+/// value it may be bound to. The params of a function that is only ever called by name
+/// take the types of its arguments. All other params may have any type.
+///
+/// Annotations that can never be satisfied are errors. If `checked` is set, annotations
+/// are checked at runtime, so they also narrow the types of what they annotate.
+///
+/// If `library` is set, top-level functions are exported, so they may be called with anything.
+pub fn infer<'a>(items: &mut Items<'a>, checked: bool, library: bool) -> Vec<TranspileError<'a>> {
+    let mut inference = Inference {
+        checked,
+        library,
+        ..Inference::default()
+    };
+    // Types only ever widen, so this reaches a fixed point
+    loop {
+        inference.pass(items);
+        if !inference.changed {
+            break;
+        }
+    }
+    inference.report = true;
+    inference.pass(items);
+    inference.errors
+}
+
+/// What is known about a binding
+#[derive(Debug, Clone, Default)]
+struct BindingInfo {
+    ty: Type,
+    /// The bindings of a function's params
+    params: Vec<usize>,
+    /// The type a function returns
+    ret: Type,
+    /// Whether a function is used as a value, so it may be called with anything
+    escapes: bool,
+    /// The annotated type of the value, or of the return value of a function
+    annotation: Option<Type>,
+    /// How errors refer to an annotated binding
+    description: String,
+}
+
+#[derive(Default)]
+struct Inference<'a> {
+    scopes: Vec<HashMap<&'a str, usize>>,
+    /// Bindings in the order they are bound, which is the same in every pass
+    bindings: Vec<BindingInfo>,
+    next_binding: usize,
+    changed: bool,
+    report: bool,
+    checked: bool,
+    library: bool,
+    errors: Vec<TranspileError<'a>>,
+}
+
+impl<'a> Inference<'a> {
+    fn pass(&mut self, items: &mut Items<'a>) {
+        self.scopes = vec![HashMap::new()];
+        self.next_binding = 0;
+        self.changed = false;
+        self.items(items);
+    }
+    fn error(&mut self, error: TranspileError<'a>) {
+        if self.report {
+            self.errors.push(error);
+        }
+    }
+    fn bind(&mut self, name: &'a str) -> usize {
+        let id = self.next_binding;
+        self.next_binding += 1;
+        if id == self.bindings.len() {
+            self.bindings.push(BindingInfo::default());
+        }
+        self.scopes.last_mut().unwrap().insert(name, id);
+        id
+    }
+    fn annotate(&mut self, id: usize, annotation: Option<Type>, description: String) {
+        let binding = &mut self.bindings[id];
+        binding.annotation = annotation;
+        binding.description = description;
+    }
+    /// Narrow a type to what an annotation allows, if annotations are checked
+    fn narrow(&self, ty: Type, annotation: Option<Type>) -> Type {
+        match annotation {
+            Some(annotation) if self.checked => ty.intersect(annotation),
+            _ => ty,
+        }
+    }
+    /// Report a value that can never satisfy the annotation of a binding
+    fn check_annotation(&mut self, id: usize, ty: Type, span: &Span<'a>) {
+        let binding = &self.bindings[id];
+        if let Some(annotation) = binding.annotation {
+            if !ty.is_never() && ty.intersect(annotation).is_never() {
+                let description = binding.description.clone();
+                self.error(TranspileError::AnnotationMismatch(
+                    span.clone(),
+                    description,
+                    annotation,
+                    ty,
+                ));
+            }
+        }
+    }
+    fn lookup(&self, name: &str) -> Option<usize> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied())
+    }
+    fn widen(&mut self, id: usize, ty: Type) {
+        let binding = &mut self.bindings[id];
+        if !binding.ty.contains(ty) {
+            binding.ty = binding.ty | ty;
+            self.changed = true;
+        }
+    }
+    fn widen_return(&mut self, id: usize, ty: Type) {
+        let binding = &mut self.bindings[id];
+        if !binding.ret.contains(ty) {
+            binding.ret = binding.ret | ty;
+            self.changed = true;
+        }
+    }
+    fn escape(&mut self, id: usize) {
+        let binding = &mut self.bindings[id];
+        if !binding.escapes {
+            binding.escapes = true;
+            self.changed = true;
+        }
+    }
+    /// Get the function bound to a name, if there is one
+    fn function(&self, node: &Node<'a>) -> Option<usize> {
+        match &node.kind {
+            NodeKind::Term(Term::Ident(ident), _) => self
+                .lookup(ident.name)
+                .filter(|&id| !self.bindings[id].params.is_empty()),
+            _ => None,
+        }
+    }
+    /// Infer the types in a list of items, returning the type of the last one
+    fn items(&mut self, items: &mut Items<'a>) -> Type {
+        let mut ty = Type::NIL;
+        for item in items {
+            ty = match item {
+                Item::Def(def) => {
+                    self.def(def);
+                    Type::ANY
+                }
+                Item::Extern(ext) => {
+                    let id = self.bind(ext.ident.name);
+                    self.widen(id, Type::FUNCTION);
+                    Type::ANY
+                }
+                Item::Node(node) => self.node(node),
+            };
+        }
+        ty
+    }
+    fn def(&mut self, def: &mut Def<'a>) {
+        if def.is_function() {
+            let id = self.bind(def.ident.name);
+            self.widen(id, Type::CALLABLE);
+            let top_level = self.scopes.len() == 1;
+            // The test harness runs tests without arguments
+            if top_level && def.is_test() {
+                self.error(TranspileError::TestTakesParams(def.ident.clone()));
+            }
+            // Hosts call a library's exports
+            if self.library && top_level {
+                self.escape(id);
+            }
+            let function = format!("`{}`", def.ident.name);
+            self.annotate(id, def.ty, format!("the return value of {}", function));
+            self.scopes.push(HashMap::new());
+            let params: Vec<usize> = def
+                .params
+                .iter()
+                .map(|param| {
+                    let param_id = self.bind(param.ident.name);
+                    self.annotate(param_id, param.ty, param.describe(&function));
+                    param_id
+                })
+                .collect();
+            if self.bindings[id].escapes {
+                for &param in &params {
+                    self.widen(param, Type::ANY);
+                }
+            }
+            self.bindings[id].params = params;
+            let ret = self.items(&mut def.items);
+            self.scopes.pop();
+            self.check_annotation(id, ret, last_span(&def.items));
+            self.widen_return(id, ret);
+        } else {
+            self.scopes.push(HashMap::new());
+            let ty = self.items(&mut def.items);
+            self.scopes.pop();
+            let id = self.bind(def.ident.name);
+            self.annotate(id, def.ty, format!("`{}`", def.ident.name));
+            self.check_annotation(id, ty, last_span(&def.items));
+            self.widen(id, ty);
+        }
+    }
+    fn node(&mut self, node: &mut Node<'a>) -> Type {
+        let ty = match &mut node.kind {
+            NodeKind::Term(term, _) => self.term(term),
+            NodeKind::BinExpr(expr) => self.bin_expr(expr),
+            NodeKind::UnExpr(expr) => {
+                let inner = self.node(&mut expr.inner);
+                match expr.op {
+                    UnOp::Neg => {
+                        let number = inner.intersect(Type::NUMBER);
+                        if number.is_never() && !inner.is_never() {
+                            let span = expr.inner.kind.span().clone();
+                            self.error(TranspileError::InvalidNegation(span, inner));
+                        }
+                        number
+                    }
+                    UnOp::Head => inner,
+                }
+            }
+            NodeKind::Call(expr) => self.call_expr(expr),
+        };
+        node.ty = ty;
+        ty
+    }
+    fn term(&mut self, term: &mut Term<'a>) -> Type {
+        match term {
+            Term::Int(_) => Type::INT,
+            Term::Real(_) => Type::REAL,
+            Term::String(_) => Type::STRING,
+            Term::Ident(ident) => {
+                if let Some(id) = self.lookup(ident.name) {
+                    let BindingInfo {
+                        ty,
+                        ref params,
+                        annotation,
+                        ..
+                    } = self.bindings[id];
+                    if !params.is_empty() {
+                        self.escape(id);
+                        return ty;
+                    }
+                    return self.narrow(ty, annotation);
+                }
+                match ident.name {
+                    "_" | "nil" => Type::NIL,
+                    "true" | "false" => Type::BOOL,
+                    name if BUILTIN_FUNCTIONS.iter().any(|(n, _)| *n == name) => Type::FUNCTION,
+                    _ => Type::ANY,
+                }
+            }
+            Term::Expr(items) => {
+                self.scopes.push(HashMap::new());
+                let ty = self.items(items);
+                self.scopes.pop();
+                ty
+            }
+            Term::Tree(nodes) => {
+                for node in nodes.iter_mut() {
+                    self.node(node);
+                }
+                Type::ANY
+            }
+            Term::Closure(closure) => {
+                self.scopes.push(HashMap::new());
+                for param in &closure.params {
+                    let id = self.bind(param.ident.name);
+                    self.annotate(id, param.ty, param.describe("a closure"));
+                    self.widen(id, Type::ANY);
+                }
+                self.items(&mut closure.body);
+                self.scopes.pop();
+                Type::CALLABLE
+            }
+        }
+    }
+    fn bin_expr(&mut self, expr: &mut BinExpr<'a>) -> Type {
+        let left = self.node(&mut expr.left);
+        let right = self.node(&mut expr.right);
+        let valid = match expr.op {
+            BinOp::Or | BinOp::And => return left | right,
+            BinOp::Mom => return left,
+            BinOp::Dad => return right,
+            BinOp::Equals | BinOp::NotEquals => return Type::BOOL,
+            BinOp::Less | BinOp::LessOrEqual | BinOp::Greater | BinOp::GreaterOrEqual => {
+                let same = Type::BOOL | Type::STRING | Type::CALLABLE | Type::ERROR;
+                !left.intersect(right).intersect(same).is_never()
+                    || !(left.intersect(Type::NUMBER).is_never()
+                        || right.intersect(Type::NUMBER).is_never())
+            }
+            _ => {
+                !(left.intersect(Type::NUMBER).is_never()
+                    || right.intersect(Type::NUMBER).is_never())
+            }
+        };
+        if left.is_never() || right.is_never() {
+            return Type::NEVER;
+        }
+        if !valid {
+            let operands = [
+                (expr.left.kind.span().clone(), left),
+                (expr.right.kind.span().clone(), right),
+            ];
+            self.error(TranspileError::IncompatibleOperands(
+                expr.op,
+                expr.op_span.clone(),
+                operands,
+            ));
+            return Type::NEVER;
+        }
+        match expr.op {
+            BinOp::Less | BinOp::LessOrEqual | BinOp::Greater | BinOp::GreaterOrEqual => Type::BOOL,
+            _ => {
+                let (left, right) = (left.intersect(Type::NUMBER), right.intersect(Type::NUMBER));
+                let int = if left.contains(Type::INT) && right.contains(Type::INT) {
+                    Type::INT
+                } else {
+                    Type::NEVER
+                };
+                let real = if left.contains(Type::REAL) || right.contains(Type::REAL) {
+                    Type::REAL
+                } else {
+                    Type::NEVER
+                };
+                int | real
+            }
+        }
+    }
+    fn call_expr(&mut self, expr: &mut CallExpr<'a>) -> Type {
+        let function = self.function(&expr.caller);
+        let caller = match function {
+            // Calling a function by name does not let it escape
+            Some(id) => {
+                expr.caller.ty = self.bindings[id].ty;
+                expr.caller.ty
+            }
+            None => self.node(&mut expr.caller),
+        };
+        let args: Vec<Type> = expr.args.iter_mut().map(|arg| self.node(arg)).collect();
+        if let Some(id) = function {
+            // Missing arguments are nil
+            let params = self.bindings[id].params.clone();
+            for (i, param) in params.into_iter().enumerate() {
+                let arg = args.get(i).copied().unwrap_or(Type::NIL);
+                let span = expr.args.get(i).map_or(&expr.span, |arg| arg.kind.span());
+                self.check_annotation(param, arg, &span.clone());
+                self.widen(param, arg);
+            }
+            let binding = &self.bindings[id];
+            return self.narrow(binding.ret, binding.annotation);
+        }
+        if caller.is_never() {
+            return Type::NEVER;
+        }
+        if caller.intersect(Type::CALLABLE).is_never() {
+            let span = expr.caller.kind.span().clone();
+            self.error(TranspileError::NotCallable(span, caller));
+            return Type::NEVER;
+        }
+        match &expr.caller.kind {
+            NodeKind::Term(Term::Ident(ident), _) if self.lookup(ident.name).is_none() => {
+                builtin_return(ident.name)
+            }
+            _ => Type::ANY,
+        }
+    }
+}
+
+/// The span of the value of a list of items
+fn last_span<'b, 'a>(items: &'b Items<'a>) -> &'b Span<'a> {
+    items.last().unwrap().span()
+}
+
+/// The type a builtin function returns
+fn builtin_return(name: &str) -> Type {
+    match name {
+        "not" | "eq" | "ne" | "lt" | "le" | "gt" | "ge" => Type::BOOL,
+        "add" | "sub" | "mul" | "div" | "rem" => Type::NUMBER,
+        "error" => Type::ERROR,
+        _ => Type::ANY,
+    }
+}
@@ -0,0 +1,248 @@
+use std::{collections::HashMap, fmt::Write};
+
+use crate::{
+    ast::*,
+    transpile::{TranspileOptions, BUILTIN_FUNCTIONS, BUILTIN_VALUES},
+};
+
+/// The runtime pasted at the top of every JavaScript program
+pub const JS_RUNTIME: &str = include_str!("../clibs/kin.js");
+
+/// Transpile checked items to a JavaScript script
+///
+/// The script runs the program when it loads. It prints with Node's stdout if it has it,
+/// or to a `kinOutput` function if the page defines one, or else to the console.
+pub fn transpile_js(items: Items, options: TranspileOptions) -> String {
+    let mut js = Js {
+        scopes: vec![HashMap::new()],
+        names: HashMap::new(),
+        indent: 1,
+        type_checks: !options.no_type_checks,
+    };
+    let mut script = String::from(JS_RUNTIME);
+    script.push_str("\nfunction kin_main() {\n");
+    let (body, value) = js.statements(items);
+    script.push_str(&body);
+    js.line(&mut script, format!("{};", value));
+    script.push_str("}\n\nkin_run(kin_main);\n");
+    script
+}
+
+struct Js<'a> {
+    /// The JavaScript names of Kin names
+    scopes: Vec<HashMap<&'a str, String>>,
+    /// How many times each name has been used
+    names: HashMap<String, usize>,
+    indent: usize,
+    type_checks: bool,
+}
+
+impl<'a> Js<'a> {
+    /// Get a unique JavaScript name for a Kin name
+    fn unique(&mut self, name: &str) -> String {
+        let name: String = name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let count = self.names.entry(name.clone()).or_insert(0);
+        *count += 1;
+        format!("k_{}_{}", name, count)
+    }
+    fn bind(&mut self, name: &'a str) -> String {
+        let js_name = self.unique(name);
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert(name, js_name.clone());
+        js_name
+    }
+    fn line(&self, body: &mut String, line: impl AsRef<str>) {
+        writeln!(body, "{}{}", "    ".repeat(self.indent), line.as_ref()).unwrap();
+    }
+    fn check_type(&self, body: &mut String, value: &str, ty: Type, name: &str) {
+        if self.type_checks {
+            let check = format!(
+                "kin_check_type({}, {}, \"{}\", {:?});",
+                value,
+                ty.bits(),
+                ty,
+                name
+            );
+            self.line(body, check);
+        }
+    }
+    /// Transpile items as statements, returning them and the value of the last one
+    fn statements(&mut self, items: Items<'a>) -> (String, String) {
+        let mut body = String::new();
+        let mut value = None;
+        for item in items {
+            if let Some(value) = value.take() {
+                self.line(&mut body, format!("{};", value));
+            }
+            match item {
+                Item::Def(def) => self.def(def, &mut body),
+                Item::Extern(ext) => self.def(ext.stub("JavaScript"), &mut body),
+                Item::Node(node) => value = Some(self.node(node)),
+            }
+        }
+        (body, value.unwrap_or_else(|| "KIN_NIL".into()))
+    }
+    fn def(&mut self, def: Def<'a>, body: &mut String) {
+        if def.is_function() {
+            // Functions can call themselves
+            let name = self.bind(def.ident.name);
+            let description = format!("`{}`", def.ident.name);
+            let function = self.function(def.params, def.ty, def.items, &description);
+            self.line(
+                body,
+                format!("const {} = kin_new_function({});", name, function),
+            );
+        } else {
+            let value = self.block(def.items);
+            let name = self.bind(def.ident.name);
+            self.line(body, format!("const {} = {};", name, value));
+            if let Some(ty) = def.ty {
+                self.check_type(body, &name, ty, &format!("`{}`", def.ident.name));
+            }
+        }
+    }
+    /// Transpile a function expression
+    fn function(
+        &mut self,
+        params: Params<'a>,
+        ret: Option<Type>,
+        items: Items<'a>,
+        description: &str,
+    ) -> String {
+        self.scopes.push(HashMap::new());
+        self.indent += 1;
+        let mut body = String::new();
+        let mut names = Vec::new();
+        for param in &params {
+            let name = self.bind(param.ident.name);
+            if let Some(ty) = param.ty {
+                self.check_type(&mut body, &name, ty, &param.describe(description));
+            }
+            names.push(format!("{} = KIN_NIL", name));
+        }
+        let (statements, mut value) = self.statements(items);
+        body.push_str(&statements);
+        if let Some(ty) = ret {
+            let ret_name = self.unique("ret");
+            self.line(&mut body, format!("const {} = {};", ret_name, value));
+            let name = format!("the return value of {}", description);
+            self.check_type(&mut body, &ret_name, ty, &name);
+            value = ret_name;
+        }
+        self.line(&mut body, format!("return {};", value));
+        self.indent -= 1;
+        self.scopes.pop();
+        format!(
+            "function ({}) {{\n{}{}}}",
+            names.join(", "),
+            body,
+            "    ".repeat(self.indent)
+        )
+    }
+    /// Transpile items as a single expression
+    fn block(&mut self, items: Items<'a>) -> String {
+        if items.iter().all(|item| matches!(item, Item::Node(_))) {
+            let mut values: Vec<String> = items
+                .into_iter()
+                .map(|item| match item {
+                    Item::Node(node) => self.node(node),
+                    Item::Def(_) | Item::Extern(_) => unreachable!(),
+                })
+                .collect();
+            return match values.len() {
+                0 => "KIN_NIL".into(),
+                1 => values.pop().unwrap(),
+                _ => format!("({})", values.join(", ")),
+            };
+        }
+        // Defs need a scope of their own
+        self.scopes.push(HashMap::new());
+        self.indent += 1;
+        let (mut body, value) = self.statements(items);
+        self.line(&mut body, format!("return {};", value));
+        self.indent -= 1;
+        self.scopes.pop();
+        format!("(() => {{\n{}{}}})()", body, "    ".repeat(self.indent))
+    }
+    fn node(&mut self, node: Node<'a>) -> String {
+        match node.kind {
+            NodeKind::Term(term, _) => self.term(term),
+            NodeKind::BinExpr(expr) => {
+                let left = self.node(*expr.left);
+                let right = self.node(*expr.right);
+                let f = match expr.op {
+                    BinOp::Or => return format!("kin_or({}, () => {})", left, right),
+                    BinOp::And => return format!("kin_and({}, () => {})", left, right),
+                    BinOp::Equals => "kin_eq",
+                    BinOp::NotEquals => "kin_neq",
+                    BinOp::Less => "kin_lt",
+                    BinOp::LessOrEqual => "kin_le",
+                    BinOp::Greater => "kin_gt",
+                    BinOp::GreaterOrEqual => "kin_ge",
+                    BinOp::Add => "kin_add",
+                    BinOp::Sub => "kin_sub",
+                    BinOp::Mul => "kin_mul",
+                    BinOp::Div => "kin_div",
+                    BinOp::Rem => "kin_rem",
+                    BinOp::Mom => "kin_with_mom",
+                    BinOp::Dad => "kin_with_dad",
+                };
+                format!("{}({}, {})", f, left, right)
+            }
+            NodeKind::UnExpr(expr) => {
+                let inner = self.node(*expr.inner);
+                match expr.op {
+                    UnOp::Neg => format!("kin_neg({})", inner),
+                    UnOp::Head => format!("kin_head({})", inner),
+                }
+            }
+            NodeKind::Call(expr) => {
+                let mut args = vec![self.node(*expr.caller)];
+                args.extend(expr.args.into_iter().map(|arg| self.node(arg)));
+                format!("kin_call({})", args.join(", "))
+            }
+        }
+    }
+    fn term(&mut self, term: Term<'a>) -> String {
+        match term {
+            Term::Int(i) => format!("kin_new_int({}n)", i),
+            Term::Real(r) => format!("kin_new_real({:?})", r),
+            Term::String(s) => format!("kin_new_string({:?})", s),
+            Term::Ident(ident) => {
+                let bound = self
+                    .scopes
+                    .iter()
+                    .rev()
+                    .find_map(|scope| scope.get(ident.name));
+                if let Some(name) = bound {
+                    return name.clone();
+                }
+                BUILTIN_VALUES
+                    .iter()
+                    .chain(BUILTIN_FUNCTIONS)
+                    .find(|(name, _)| *name == ident.name)
+                    .map(|(_, js_name)| js_name.to_string())
+                    .expect("unknown names are rejected when parsing")
+            }
+            Term::Expr(items) => self.block(items),
+            Term::Tree(nodes) => {
+                let [left, middle, right] = *nodes;
+                format!(
+                    "kin_new_tree({}, {}, {})",
+                    self.node(left),
+                    self.node(middle),
+                    self.node(right)
+                )
+            }
+            Term::Closure(closure) => {
+                let function = self.function(closure.params, None, closure.body, "a closure");
+                format!("kin_new_closure({})", function)
+            }
+        }
+    }
+}
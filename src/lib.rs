@@ -0,0 +1,17 @@
+#![allow(unstable_name_collisions)]
+
+pub mod ast;
+pub mod bytecode;
+pub mod compile;
+pub mod diagnostic;
+pub mod infer;
+pub mod js;
+pub mod optimize;
+pub mod parse;
+pub mod transpile;
+pub mod vm;
+pub mod wat;
+
+pub use compile::{CompileError, Compiler};
+pub use parse::parse;
+pub use transpile::transpile;
@@ -1,90 +1,211 @@
-#![allow(unstable_name_collisions)]
+mod manifest;
+mod watch;
 
-mod ast;
-mod parse;
-mod transpile;
-
-use std::str::FromStr;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::*,
+};
 
 use clap::Clap;
+use kin::{
+    ast, bytecode,
+    compile::*,
+    diagnostic,
+    transpile::{Runtime, Transpilation, RUNTIME_HEADER},
+    vm,
+};
 
 fn main() {
-    use std::process::*;
-
-    use transpile::*;
-
     color_backtrace::install();
 
     let app = App::parse();
 
-    // Parse and check
-    let input = std::fs::read_to_string("test.kin").unwrap();
-    let items = match parse::parse(&input) {
-        Ok(items) => items,
-        Err(errors) => {
-            for error in errors {
-                println!("{}", error)
+    // Explain an error code
+    if let Sub::Explain(args) = &app.sub {
+        match diagnostic::explain(&args.code) {
+            Some(explanation) => print!("{}", explanation),
+            None => {
+                println!("{} is not a Kin error code", args.code);
+                exit(1);
             }
-            exit(1);
         }
-    };
-    println!("Check succeeded");
+        return;
+    }
 
-    // Transpile
-    if !app.sub.transpiles() {
+    // Create a new project
+    if let Sub::New(args) = &app.sub {
+        if let Err(e) = manifest::new_project(&args.name) {
+            println!("{}", e);
+            exit(1);
+        }
+        println!("Created project {}", args.name);
         return;
     }
-    let transpilation = transpile(items);
-    transpilation.write().unwrap();
-    println!("Transpilation succeeded");
 
-    // Compile
-    let build_args = if let Some(args) = app.sub.build_args() {
-        args
-    } else {
+    // Run precompiled bytecode, which needs no project
+    if let Sub::Run(RunArgs {
+        kinc: Some(path), ..
+    }) = &app.sub
+    {
+        let program = fs::File::open(path)
+            .map(io::BufReader::new)
+            .and_then(bytecode::Program::read_from)
+            .unwrap_or_else(|e| {
+                println!("Unable to read {}: {}", path.display(), e);
+                exit(1);
+            });
+        run_vm(&program);
         return;
-    };
+    }
 
-    let ccomp = build_args.compiler.unwrap_or_else(CCompiler::find);
+    // Resolve the runtime before the working directory changes
+    let runtime = match &app.runtime {
+        Some(path) => match path.canonicalize() {
+            Ok(path) => Runtime::External(path),
+            Err(e) => {
+                println!("Unable to find runtime {}: {}", path.display(), e);
+                exit(1);
+            }
+        },
+        None => Runtime::Bundled,
+    };
 
-    let mut args: Vec<String> = vec!["build/main.c".into(), "-o".into()];
+    // Find the project
+    let project = match manifest::Project::find() {
+        Ok(project) => project,
+        Err(e) => {
+            println!("{:#}", e);
+            exit(1);
+        }
+    };
+    std::env::set_current_dir(&project.root).unwrap();
 
-    let name = "test";
+    // Rerun on changes
+    if let Sub::Watch(args) = &app.sub {
+        if args.kinc.is_some() {
+            println!("`kin watch` watches the project, so it cannot run a .kinc file");
+            exit(1);
+        }
+        let mut run_args = Vec::new();
+        if let Runtime::External(path) = &runtime {
+            run_args.push("--runtime".into());
+            run_args.push(path.into());
+        }
+        run_args.push("run".into());
+        run_args.extend(watch::subcommand_args());
+        watch::watch(&project, run_args);
+    }
 
-    // Push target arg
-    if build_args.assembly {
-        args.push(format!("{}.asm", name));
-        args.push("-S".into());
-    } else {
-        args.push(format!("{}{}", name, EXE_EXT));
+    let build_args = app
+        .sub
+        .build_args()
+        .map(|args| args.clone().or(&project.build));
+    let target = build_args.as_ref().map_or(Target::C, BuildArgs::target);
+    if target != Target::C && !matches!(app.sub, Sub::Build(_)) {
+        println!("Only `kin build` supports the {} target", target.name());
+        exit(1);
+    }
+    let lib = build_args.as_ref().is_some_and(|args| args.lib);
+    if lib && (target != Target::C || !matches!(app.sub, Sub::Build(_))) {
+        println!("Only `kin build` with the c target supports --lib");
+        exit(1);
     }
+    let vm = matches!(&app.sub, Sub::Run(args) if args.vm);
+    // Only C needs a C compiler
+    let build_args = build_args.map(|args| match target {
+        Target::C if !vm => resolve_build_args(args),
+        _ => args,
+    });
+    let compiler = Compiler::new()
+        .runtime(runtime.clone())
+        .build_args(build_args.clone().unwrap_or_default())
+        .output(&project.output)
+        .inherit_output(true);
 
-    // Push opt arg
-    args.push("-O3".into());
+    // Run tests
+    if let Sub::Test(_) = &app.sub {
+        if !run_tests(&project, compiler) {
+            exit(1);
+        }
+        return;
+    }
 
-    // Push C standard arg
-    args.push("-std=c99".into());
+    // Parse and check
+    let input = match fs::read_to_string(&project.entry) {
+        Ok(input) => input,
+        Err(e) => {
+            println!("Unable to read {}: {}", project.entry.display(), e);
+            exit(1);
+        }
+    };
+    let items = compiler.check(&input).unwrap_or_else(|e| fail(e));
+    let c_output = app.sub.c_output();
+    let quiet = c_output.is_some_and(|path| path == Path::new("-"));
+    if !quiet {
+        println!("Check succeeded");
+    }
 
-    // Push stack size arg
-    if let Some(size) = build_args.stack_size {
-        args.push(ccomp.stack_size_arg(size * 1024 * 1024));
+    // Run on the VM
+    if vm {
+        println!();
+        run_vm(&compiler.bytecode(items));
+        return;
     }
 
-    // Push profile arg
-    if build_args.profile {
-        args.push("-pg".into());
+    // Transpile
+    if !app.sub.transpiles() {
+        return;
     }
+    if target != Target::C {
+        let written = match target {
+            Target::Wat => compiler.write_wat(items),
+            Target::Kinc => compiler.write_kinc(items),
+            _ => compiler.write_js(items),
+        };
+        written.unwrap_or_else(|e| fail(e));
+        println!("Transpilation succeeded");
+        return;
+    }
+    match c_output {
+        Some(_) if quiet => {
+            // A bundled runtime has nowhere to go, so it is pasted into the C
+            let runtime = match runtime {
+                Runtime::Bundled => Runtime::Inline,
+                runtime => runtime,
+            };
+            let stdout = io::stdout();
+            let result = compiler.transpile(items).write_to(stdout.lock(), &runtime);
+            match result {
+                Err(e) if e.kind() != io::ErrorKind::BrokenPipe => {
+                    eprintln!("Unable to write to stdout: {}", e);
+                    exit(1);
+                }
+                _ => return,
+            }
+        }
+        Some(path) => {
+            let result = write_c_file(compiler.transpile(items), path, &runtime);
+            if let Err(e) = result {
+                println!("Unable to write to {}: {}", path.display(), e);
+                exit(1);
+            }
+        }
+        None => {
+            compiler.write_c(items).unwrap_or_else(|e| fail(e));
+        }
+    }
+    println!("Transpilation succeeded");
 
-    let compile_status = Command::new(ccomp.name())
-        .args(args)
-        .spawn()
-        .unwrap()
-        .wait()
-        .unwrap();
-    if compile_status.success() {
+    // Compile
+    if build_args.is_none() {
+        return;
+    }
+    let (executable, compiled) = compiler.compile_c_if_changed().unwrap_or_else(|e| fail(e));
+    if compiled {
         println!("Compilation succeeded");
     } else {
-        exit(1);
+        println!("Compilation skipped because nothing changed");
     }
 
     // Run
@@ -92,14 +213,133 @@ fn main() {
         return;
     }
     println!();
-    let run_status = Command::new("./test").spawn().unwrap().wait().unwrap();
+    let run_status = Command::new(PathBuf::from(".").join(executable))
+        .spawn()
+        .unwrap()
+        .wait()
+        .unwrap();
     if !run_status.success() {
         exit(1);
     }
 }
 
+/// Write generated C to a file, with the bundled runtime header next to it
+fn write_c_file(transpilation: Transpilation, path: &Path, runtime: &Runtime) -> io::Result<()> {
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    if !dir.as_os_str().is_empty() {
+        fs::create_dir_all(dir)?;
+    }
+    if let Runtime::Bundled = runtime {
+        fs::write(dir.join("kin.h"), RUNTIME_HEADER)?;
+    }
+    transpilation.write_to(io::BufWriter::new(fs::File::create(path)?), runtime)
+}
+
+/// Run bytecode, exiting with an error if it panics
+fn run_vm(program: &bytecode::Program) {
+    let stdout = io::stdout();
+    match vm::run(program, io::BufWriter::new(stdout.lock())) {
+        Ok(()) => {}
+        Err(vm::VmError::Panic) => exit(1),
+        Err(vm::VmError::Io(e)) if e.kind() == io::ErrorKind::BrokenPipe => {}
+        Err(e) => {
+            eprintln!("Unable to write to stdout: {}", e);
+            exit(1);
+        }
+    }
+}
+
+fn fail(error: CompileError) -> ! {
+    println!("{}", error);
+    exit(1)
+}
+
+/// Pick the C compiler once and tell the user about any stack size caveats
+fn resolve_build_args(mut build_args: BuildArgs) -> BuildArgs {
+    let ccomp = build_args
+        .c_compiler()
+        .unwrap_or_else(|| fail(CompileError::NoCCompiler));
+    if let Some(size) = build_args.stack_size {
+        if let Some(note) = ccomp.stack_size_note(size * 1024 * 1024) {
+            println!("{}", note);
+        }
+    }
+    build_args.compiler = Some(ccomp);
+    build_args
+}
+
+/// Transpile, compile, and run a test harness for the entry file and each file in `tests`
+fn run_tests(project: &manifest::Project, compiler: Compiler) -> bool {
+    let mut files = vec![project.entry.clone()];
+    if let Ok(entries) = fs::read_dir("tests") {
+        let mut test_files: Vec<PathBuf> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "kin"))
+            .collect();
+        test_files.sort();
+        files.extend(test_files);
+    }
+
+    let compiler = compiler
+        .tests(true)
+        .output(format!("build/{}_tests", project.output));
+    let mut files_run = 0;
+    let mut files_failed = 0;
+    for path in files.into_iter().filter(|path| path.is_file()) {
+        let input = match fs::read_to_string(&path) {
+            Ok(input) => input,
+            Err(e) => {
+                println!("Unable to read {}: {}", path.display(), e);
+                files_failed += 1;
+                continue;
+            }
+        };
+        let items = match compiler.check(&input) {
+            Ok(items) => items,
+            Err(e) => {
+                println!("{}", e);
+                files_failed += 1;
+                continue;
+            }
+        };
+        if !items
+            .iter()
+            .any(|item| matches!(item, ast::Item::Def(def) if def.is_test()))
+        {
+            continue;
+        }
+        println!("Running tests in {}", path.display());
+        files_run += 1;
+        let executable = match compiler.write_c(items).and_then(|_| compiler.compile_c()) {
+            Ok(executable) => executable,
+            Err(e) => {
+                println!("{}", e);
+                files_failed += 1;
+                continue;
+            }
+        };
+        let status = Command::new(PathBuf::from(".").join(executable))
+            .status()
+            .unwrap();
+        if !status.success() {
+            files_failed += 1;
+        }
+        println!();
+    }
+    if files_run == 0 && files_failed == 0 {
+        println!("No tests found");
+    }
+    files_failed == 0
+}
+
 #[derive(Clap)]
 struct App {
+    #[clap(
+        long = "runtime",
+        about = "Use an external runtime header instead of the bundled one"
+    )]
+    runtime: Option<PathBuf>,
     #[clap(subcommand)]
     sub: Sub,
 }
@@ -109,80 +349,77 @@ enum Sub {
     #[clap(alias = "c")]
     Check,
     #[clap(alias = "t")]
-    Trans,
+    Trans(TransArgs),
     #[clap(alias = "b")]
     Build(BuildArgs),
     #[clap(alias = "r")]
-    Run(BuildArgs),
+    Run(RunArgs),
+    #[clap(
+        alias = "w",
+        about = "Rebuild and rerun whenever a source file changes"
+    )]
+    Watch(RunArgs),
+    #[clap(about = "Run all defs named test_* in the entry file and the tests directory")]
+    Test(BuildArgs),
+    #[clap(about = "Create a new Kin project")]
+    New(NewArgs),
+    #[clap(about = "Explain an error code, such as K0001")]
+    Explain(ExplainArgs),
 }
 
-impl Sub {
-    fn build_args(&self) -> Option<&BuildArgs> {
-        match self {
-            Sub::Build(args) | Sub::Run(args) => Some(args),
-            _ => None,
-        }
-    }
-    fn transpiles(&self) -> bool {
-        !matches!(self, Sub::Check)
-    }
+#[derive(Clap)]
+struct TransArgs {
+    #[clap(
+        short = 'o',
+        long = "output",
+        about = "Where to write the generated C. Use - for stdout"
+    )]
+    output: Option<PathBuf>,
 }
 
 #[derive(Clap)]
-struct BuildArgs {
-    #[clap(long = "stack", about = "The executable stack size in MB")]
-    stack_size: Option<usize>,
-    #[clap(about = "The C compiler to use")]
-    compiler: Option<CCompiler>,
-    #[clap(long = "asm")]
-    assembly: bool,
-    #[clap(long = "profile")]
-    profile: bool,
+struct RunArgs {
+    #[clap(flatten)]
+    build: BuildArgs,
+    #[clap(
+        long = "vm",
+        about = "Run on the bytecode VM instead of compiling with a C compiler"
+    )]
+    vm: bool,
+    #[clap(
+        long = "kinc",
+        about = "Run a compiled .kinc file on the VM instead of the project"
+    )]
+    kinc: Option<PathBuf>,
 }
 
-const EXE_EXT: &str = if cfg!(windows) { ".exe" } else { "" };
+#[derive(Clap)]
+struct NewArgs {
+    #[clap(about = "The name of the project directory")]
+    name: String,
+}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum CCompiler {
-    Gcc,
-    Clang,
+#[derive(Clap)]
+struct ExplainArgs {
+    #[clap(about = "The error code to explain")]
+    code: String,
 }
 
-impl CCompiler {
-    const ALL: &'static [Self] = &[CCompiler::Gcc, CCompiler::Clang];
-    fn find() -> Self {
-        use std::process::*;
-        for &comp in Self::ALL {
-            if Command::new(comp.name())
-                .arg("-v")
-                .output()
-                .map_or(false, |output| output.status.success())
-            {
-                return comp;
-            }
+impl Sub {
+    fn build_args(&self) -> Option<&BuildArgs> {
+        match self {
+            Sub::Build(args) | Sub::Test(args) => Some(args),
+            Sub::Run(args) => Some(&args.build),
+            _ => None,
         }
-        println!("No compatible C compiler detected.");
-        exit(1)
-    }
-    pub fn name(&self) -> &'static str {
-        ["gcc", "clang"][*self as usize]
     }
-    pub fn stack_size_arg(&self, size: usize) -> String {
+    fn c_output(&self) -> Option<&Path> {
         match self {
-            CCompiler::Gcc => format!("-Wl,--stack,{}", size),
-            CCompiler::Clang => format!("-Wl,-stack:{}", size),
+            Sub::Trans(args) => args.output.as_deref(),
+            _ => None,
         }
     }
-}
-
-impl FromStr for CCompiler {
-    type Err = anyhow::Error;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        use anyhow::anyhow;
-        Self::ALL
-            .iter()
-            .find(|comp| comp.name() == s)
-            .copied()
-            .ok_or_else(|| anyhow!("Unsupported or unknown compiler {:?}", s))
+    fn transpiles(&self) -> bool {
+        !matches!(self, Sub::Check | Sub::Explain(_))
     }
 }
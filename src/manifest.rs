@@ -0,0 +1,149 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context};
+use serde::Deserialize;
+
+use kin::compile::BuildArgs;
+
+pub const MANIFEST_NAME: &str = "kin.toml";
+const DEFAULT_ENTRY: &str = "test.kin";
+const DEFAULT_OUTPUT: &str = "test";
+
+/// The contents of a `kin.toml` file
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Manifest {
+    pub package: Package,
+    #[serde(default)]
+    pub build: BuildArgs,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Package {
+    pub name: String,
+    pub entry: Option<PathBuf>,
+    pub output: Option<String>,
+    #[serde(default)]
+    pub module_paths: Vec<PathBuf>,
+}
+
+/// A resolved project, either from a manifest or from the defaults
+pub struct Project {
+    pub root: PathBuf,
+    pub entry: PathBuf,
+    pub output: String,
+    pub build: BuildArgs,
+    /// Directories, relative to the root, that hold the project's modules
+    pub module_paths: Vec<PathBuf>,
+}
+
+impl Project {
+    /// Find the project for the current directory
+    ///
+    /// This walks up from the current directory looking for a `kin.toml`.
+    /// If none is found, the current directory is used with default settings.
+    pub fn find() -> anyhow::Result<Self> {
+        let current_dir = env::current_dir()?;
+        for dir in current_dir.ancestors() {
+            let manifest_path = dir.join(MANIFEST_NAME);
+            if manifest_path.is_file() {
+                return Project::from_manifest(dir, &manifest_path);
+            }
+        }
+        Ok(Project {
+            root: current_dir,
+            entry: DEFAULT_ENTRY.into(),
+            output: DEFAULT_OUTPUT.into(),
+            build: BuildArgs::default(),
+            module_paths: Vec::new(),
+        })
+    }
+    fn from_manifest(root: &Path, manifest_path: &Path) -> anyhow::Result<Self> {
+        let text = fs::read_to_string(manifest_path)?;
+        let manifest: Manifest = toml::from_str(&text)
+            .with_context(|| format!("Invalid manifest {}", manifest_path.display()))?;
+        let Manifest { package, build } = manifest;
+        let entry = package.entry.unwrap_or_else(|| "src/main.kin".into());
+        let entry = Project::resolve_entry(root, &entry, &package.module_paths)
+            .ok_or_else(|| anyhow!("Unable to find entry file {}", entry.display()))?;
+        Ok(Project {
+            root: root.into(),
+            entry,
+            output: package.output.unwrap_or(package.name),
+            build,
+            module_paths: package.module_paths,
+        })
+    }
+    /// The files a change to which can change the build, relative to the root
+    ///
+    /// These are the manifest, the entry file, and the Kin files next to the entry
+    /// and in each module path.
+    pub fn source_files(&self) -> Vec<PathBuf> {
+        let mut files = vec![PathBuf::from(MANIFEST_NAME), self.entry.clone()];
+        let entry_dir = self.entry.parent().unwrap_or_else(|| Path::new(""));
+        for dir in std::iter::once(entry_dir).chain(self.module_paths.iter().map(PathBuf::as_path))
+        {
+            let read_dir = if dir.as_os_str().is_empty() {
+                fs::read_dir(".")
+            } else {
+                fs::read_dir(dir)
+            };
+            if let Ok(entries) = read_dir {
+                files.extend(
+                    entries
+                        .filter_map(Result::ok)
+                        .map(|entry| dir.join(entry.file_name()))
+                        .filter(|path| path.extension().is_some_and(|ext| ext == "kin")),
+                );
+            }
+        }
+        files.sort();
+        files.dedup();
+        files
+    }
+    /// Look for the entry file relative to the root, then in each module path
+    fn resolve_entry(root: &Path, entry: &Path, module_paths: &[PathBuf]) -> Option<PathBuf> {
+        if root.join(entry).is_file() {
+            return Some(entry.into());
+        }
+        module_paths
+            .iter()
+            .map(|path| path.join(entry))
+            .find(|path| root.join(path).is_file())
+    }
+}
+
+/// Create a new project directory with a manifest and a hello world entry file
+pub fn new_project(name: &str) -> anyhow::Result<()> {
+    let root = Path::new(name);
+    if root.exists() {
+        bail!("{} already exists", root.display());
+    }
+    let package_name = root
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| anyhow!("Invalid project name {:?}", name))?;
+    fs::create_dir_all(root.join("src"))?;
+    fs::write(
+        root.join(MANIFEST_NAME),
+        format!(
+            "[package]\n\
+            name = {}\n\
+            entry = \"src/main.kin\"\n\
+            module-paths = [\"src\"]\n\
+            \n\
+            [build]\n",
+            toml::Value::from(package_name)
+        ),
+    )?;
+    fs::write(root.join("src/main.kin"), "println \"Hello World!\"\n")?;
+    fs::write(
+        root.join(".gitignore"),
+        format!("/build\n/{}\n", package_name),
+    )?;
+    Ok(())
+}
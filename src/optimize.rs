@@ -0,0 +1,342 @@
+use std::collections::HashMap;
+
+use pest::Span;
+
+use crate::ast::*;
+
+/// A value known at compile time
+#[derive(Debug, Clone, PartialEq)]
+enum Const {
+    Nil,
+    Bool(bool),
+    Int(i64),
+    Real(f64),
+    String(String),
+}
+
+impl Const {
+    /// Whether the runtime would consider this value true
+    fn is_true(&self) -> bool {
+        !matches!(self, Const::Nil | Const::Bool(false))
+    }
+    fn real(&self) -> Option<f64> {
+        match self {
+            Const::Int(i) => Some(*i as f64),
+            Const::Real(r) => Some(*r),
+            _ => None,
+        }
+    }
+    fn into_node<'a>(self, span: Span<'a>, depth: u32) -> Node<'a> {
+        let builtin = |name| {
+            Term::Ident(Ident {
+                name,
+                span: span.clone(),
+            })
+        };
+        let (term, lifetime, ty) = match self {
+            Const::Nil => (builtin("nil"), Lifetime::STATIC, Type::NIL),
+            Const::Bool(true) => (builtin("true"), Lifetime::STATIC, Type::BOOL),
+            Const::Bool(false) => (builtin("false"), Lifetime::STATIC, Type::BOOL),
+            Const::Int(i) => (Term::Int(i), Lifetime::new(depth, 0), Type::INT),
+            Const::Real(r) => (Term::Real(r), Lifetime::new(depth, 0), Type::REAL),
+            Const::String(s) => (Term::String(s), Lifetime::STATIC, Type::STRING),
+        };
+        NodeKind::Term(term, span)
+            .life(lifetime.depth, lifetime.refs)
+            .typed(ty)
+    }
+    /// Evaluate a binary operation the way the runtime would
+    ///
+    /// Operations that would panic or whose results the runtime leaves undefined are not folded.
+    fn bin_op(&self, op: BinOp, other: &Const) -> Option<Const> {
+        use Const::*;
+        let real = |f: fn(f64, f64) -> f64| {
+            let r = f(self.real()?, other.real()?);
+            // Non-finite reals have no C literal
+            Some(Real(r)).filter(|_| r.is_finite())
+        };
+        Some(match op {
+            BinOp::Add => match (self, other) {
+                (Int(a), Int(b)) => Int(a.checked_add(*b)?),
+                _ => real(|a, b| a + b)?,
+            },
+            BinOp::Sub => match (self, other) {
+                (Int(a), Int(b)) => Int(a.checked_sub(*b)?),
+                _ => real(|a, b| a - b)?,
+            },
+            BinOp::Mul => match (self, other) {
+                (Int(a), Int(b)) => Int(a.checked_mul(*b)?),
+                _ => real(|a, b| a * b)?,
+            },
+            BinOp::Div => match (self, other) {
+                (Int(a), Int(b)) => Int(a.checked_div(*b)?),
+                _ => real(|a, b| a / b)?,
+            },
+            BinOp::Rem => match (self, other) {
+                (Int(a), Int(b)) => Int(a.checked_rem(*b)?),
+                _ => real(|a, b| a % b)?,
+            },
+            BinOp::Equals => Bool(self.equals(other)),
+            BinOp::NotEquals => Bool(!self.equals(other)),
+            BinOp::Less => Bool(self.less(other)?),
+            BinOp::Greater => Bool(other.less(self)?),
+            BinOp::LessOrEqual => Bool(self.less(other)? || self.equals(other)),
+            BinOp::GreaterOrEqual => Bool(other.less(self)? || self.equals(other)),
+            BinOp::Or | BinOp::And | BinOp::Mom | BinOp::Dad => return None,
+        })
+    }
+    fn equals(&self, other: &Const) -> bool {
+        match (self.real(), other.real()) {
+            (Some(a), Some(b)) => match (self, other) {
+                (Const::Int(a), Const::Int(b)) => a == b,
+                _ => a == b,
+            },
+            _ => self == other,
+        }
+    }
+    /// Compare two values, or `None` if the runtime would panic
+    fn less(&self, other: &Const) -> Option<bool> {
+        match (self, other) {
+            (Const::Bool(a), Const::Bool(b)) => Some(a < b),
+            (Const::Int(a), Const::Int(b)) => Some(a < b),
+            (Const::String(a), Const::String(b)) => Some(a.as_bytes() < b.as_bytes()),
+            _ => Some(self.real()? < other.real()?),
+        }
+    }
+}
+
+/// Fold constant expressions, inline defs bound to constants, and remove dead code
+pub fn optimize(items: Items<'_>) -> Items<'_> {
+    let mut folder = Folder {
+        scopes: vec![HashMap::new()],
+        in_parens: false,
+    };
+    folder.items(items, false)
+}
+
+struct Folder<'a> {
+    /// The constant value of each binding, or `None` if it is not constant
+    scopes: Vec<HashMap<&'a str, Option<Const>>>,
+    /// Defs in parentheses may be visible after them, so they are never removed
+    in_parens: bool,
+}
+
+impl<'a> Folder<'a> {
+    fn bind(&mut self, name: &'a str, value: Option<Const>) {
+        self.scopes.last_mut().unwrap().insert(name, value);
+    }
+    fn constant(&self, node: &Node<'a>) -> Option<Const> {
+        match &node.kind {
+            NodeKind::Term(term, _) => match term {
+                Term::Int(i) => Some(Const::Int(*i)),
+                Term::Real(r) => Some(Const::Real(*r)),
+                Term::String(s) => Some(Const::String(s.clone())),
+                Term::Ident(ident) => {
+                    if let Some(value) = self
+                        .scopes
+                        .iter()
+                        .rev()
+                        .find_map(|scope| scope.get(ident.name))
+                    {
+                        return value.clone();
+                    }
+                    match ident.name {
+                        "nil" => Some(Const::Nil),
+                        "true" => Some(Const::Bool(true)),
+                        "false" => Some(Const::Bool(false)),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+    /// Fold a list of items
+    ///
+    /// If `keep_last` is set, the last item is the value of the list and is always kept.
+    /// Other constant expressions have no effect and are removed.
+    fn items(&mut self, items: Items<'a>, keep_last: bool) -> Items<'a> {
+        let item_count = items.len();
+        let mut folded = Vec::with_capacity(item_count);
+        for (i, item) in items.into_iter().enumerate() {
+            let is_value = keep_last && i + 1 == item_count;
+            match item {
+                Item::Def(def) => folded.extend(self.def(def).map(Item::Def)),
+                Item::Extern(ext) => {
+                    self.bind(ext.ident.name, None);
+                    folded.push(Item::Extern(ext));
+                }
+                Item::Node(node) => {
+                    let node = self.node(node);
+                    if is_value || self.constant(&node).is_none() {
+                        folded.push(Item::Node(node));
+                    }
+                }
+            }
+        }
+        folded
+    }
+    /// Fold a def, returning `None` if it was inlined
+    fn def(&mut self, def: Def<'a>) -> Option<Def<'a>> {
+        let Def {
+            ident,
+            ty,
+            params,
+            items,
+        } = def;
+        if params.is_empty() {
+            self.scopes.push(HashMap::new());
+            let items = self.items(items, true);
+            self.scopes.pop();
+            let value = match items.as_slice() {
+                [Item::Node(node)] => self.constant(node),
+                _ => None,
+            };
+            let inlined = value.is_some();
+            self.bind(ident.name, value);
+            // Tests are kept so that they still run
+            let def = Def {
+                ident,
+                ty,
+                params,
+                items,
+            };
+            if inlined && !self.in_parens && !def.is_test() {
+                None
+            } else {
+                Some(def)
+            }
+        } else {
+            self.bind(ident.name, None);
+            self.scopes.push(HashMap::new());
+            for param in &params {
+                self.bind(param.ident.name, None);
+            }
+            let in_parens = std::mem::replace(&mut self.in_parens, false);
+            let items = self.items(items, true);
+            self.in_parens = in_parens;
+            self.scopes.pop();
+            Some(Def {
+                ident,
+                ty,
+                params,
+                items,
+            })
+        }
+    }
+    fn node(&mut self, node: Node<'a>) -> Node<'a> {
+        let Node {
+            kind,
+            lifetime,
+            provenance,
+            ty,
+        } = node;
+        let span = kind.span().clone();
+        let kind = match kind {
+            NodeKind::Term(term, span) => NodeKind::Term(self.term(term), span),
+            NodeKind::BinExpr(expr) => {
+                let BinExpr {
+                    left,
+                    right,
+                    op,
+                    span,
+                    op_span,
+                } = expr;
+                let left = self.node(*left);
+                let left_value = self.constant(&left);
+                // Only the branch that would be evaluated is kept
+                match (op, &left_value) {
+                    (BinOp::Or, Some(value)) if value.is_true() => return left,
+                    (BinOp::And, Some(value)) if !value.is_true() => return left,
+                    (BinOp::Or, Some(_)) | (BinOp::And, Some(_)) => return self.node(*right),
+                    _ => {}
+                }
+                let right = self.node(*right);
+                if let (Some(a), Some(b)) = (left_value, self.constant(&right)) {
+                    if let Some(value) = a.bin_op(op, &b) {
+                        return value.into_node(span, lifetime.depth);
+                    }
+                }
+                NodeKind::BinExpr(BinExpr::new(left, right, op, span, op_span))
+            }
+            NodeKind::UnExpr(expr) => {
+                let UnExpr { inner, op, span } = expr;
+                let inner = self.node(*inner);
+                let value = match (&op, self.constant(&inner)) {
+                    (UnOp::Neg, Some(Const::Int(i))) => i.checked_neg().map(Const::Int),
+                    (UnOp::Neg, Some(Const::Real(r))) => Some(Const::Real(-r)),
+                    _ => None,
+                };
+                if let Some(value) = value {
+                    return value.into_node(span, lifetime.depth);
+                }
+                NodeKind::UnExpr(UnExpr::new(inner, op, span))
+            }
+            NodeKind::Call(expr) => {
+                let CallExpr { caller, args, span } = expr;
+                NodeKind::Call(CallExpr {
+                    caller: self.node(*caller).into(),
+                    args: args.into_iter().map(|arg| self.node(arg)).collect(),
+                    span,
+                })
+            }
+        };
+        let node = Node {
+            kind,
+            lifetime,
+            provenance,
+            ty,
+        };
+        // Inline idents bound to constants and unwrap constant parentheses
+        if let NodeKind::Term(Term::Ident(_), _) | NodeKind::Term(Term::Expr(_), _) = node.kind {
+            if let Some(value) = self.constant(&node).or_else(|| self.paren_value(&node)) {
+                return value.into_node(span, lifetime.depth);
+            }
+        }
+        node
+    }
+    /// Get the value of parentheses that only hold a constant
+    fn paren_value(&self, node: &Node<'a>) -> Option<Const> {
+        match &node.kind {
+            NodeKind::Term(Term::Expr(items), _) => match items.as_slice() {
+                [Item::Node(node)] => self.constant(node),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+    fn term(&mut self, term: Term<'a>) -> Term<'a> {
+        match term {
+            Term::Expr(items) => {
+                self.scopes.push(HashMap::new());
+                let in_parens = std::mem::replace(&mut self.in_parens, true);
+                let items = self.items(items, true);
+                self.in_parens = in_parens;
+                self.scopes.pop();
+                Term::Expr(items)
+            }
+            Term::Tree(nodes) => {
+                let [left, middle, right] = *nodes;
+                Term::Tree(Box::new([
+                    self.node(left),
+                    self.node(middle),
+                    self.node(right),
+                ]))
+            }
+            Term::Closure(closure) => {
+                let Closure { span, params, body } = *closure;
+                self.scopes.push(HashMap::new());
+                for param in &params {
+                    self.bind(param.ident.name, None);
+                }
+                let in_parens = std::mem::replace(&mut self.in_parens, false);
+                let body = self.items(body, true);
+                self.in_parens = in_parens;
+                self.scopes.pop();
+                Term::Closure(Closure { span, params, body }.into())
+            }
+            term => term,
+        }
+    }
+}
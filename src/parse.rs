@@ -1,69 +1,233 @@
 #![allow(clippy::upper_case_acronyms)]
 
-use std::{collections::HashMap, fmt};
+use std::{collections::HashMap, convert::TryFrom, fmt};
 
 use itertools::Itertools;
 use pest::{
-    error::{Error as PestError, ErrorVariant},
+    error::{Error as PestError, ErrorVariant, InputLocation},
     iterators::Pair,
     Parser, RuleType, Span,
 };
 
-use crate::ast::*;
+use crate::{ast::*, diagnostic::Diagnostic};
 
 #[derive(Debug)]
 pub enum TranspileError<'a> {
-    UnknownDef(Ident<'a>),
-    Parse(PestError<Rule>),
+    UnknownDef(Ident<'a>, Option<&'a str>),
+    Parse(Span<'a>, String),
     InvalidLiteral(Span<'a>),
     DefUnderscoreTerminus(Span<'a>),
     FunctionNamedUnderscore(Span<'a>),
-    ReturnReferencesLocal(Span<'a>),
+    ReturnReferencesLocal(Span<'a>, Option<Provenance<'a>>),
     ForbiddenRedefinition(Ident<'a>),
     LastItemNotExpression(Span<'a>),
+    SourceTooLong(usize),
+    IncompatibleOperands(BinOp, Span<'a>, [(Span<'a>, Type); 2]),
+    InvalidNegation(Span<'a>, Type),
+    NotCallable(Span<'a>, Type),
+    UnknownType(Ident<'a>),
+    AnnotationMismatch(Span<'a>, String, Type, Type),
+    TestTakesParams(Ident<'a>),
+    ReservedExternName(Ident<'a>),
 }
 
-impl<'a> fmt::Display for TranspileError<'a> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl<'a> TranspileError<'a> {
+    pub fn code(&self) -> &'static str {
         match self {
-            TranspileError::UnknownDef(ident) => format_span(
-                format!("Unknown def: {:?}", ident.name),
-                ident.span.clone(),
-                f,
-            ),
-            TranspileError::Parse(e) => e.fmt(f),
-            TranspileError::InvalidLiteral(span) => format_span("Invalid literal", span.clone(), f),
+            TranspileError::Parse(..) => "K0001",
+            TranspileError::UnknownDef(..) => "K0002",
+            TranspileError::InvalidLiteral(_) => "K0003",
+            TranspileError::DefUnderscoreTerminus(_) => "K0004",
+            TranspileError::FunctionNamedUnderscore(_) => "K0005",
+            TranspileError::ReturnReferencesLocal(..) => "K0006",
+            TranspileError::ForbiddenRedefinition(_) => "K0007",
+            TranspileError::LastItemNotExpression(_) => "K0008",
+            TranspileError::SourceTooLong(..) => "K0009",
+            TranspileError::IncompatibleOperands(..) => "K0010",
+            TranspileError::InvalidNegation(..) => "K0011",
+            TranspileError::NotCallable(..) => "K0012",
+            TranspileError::UnknownType(_) => "K0013",
+            TranspileError::AnnotationMismatch(..) => "K0014",
+            TranspileError::TestTakesParams(_) => "K0015",
+            TranspileError::ReservedExternName(_) => "K0016",
+        }
+    }
+    pub fn diagnostic(&self) -> Diagnostic<'a> {
+        let code = self.code();
+        match self {
+            TranspileError::UnknownDef(ident, suggestion) => {
+                let diagnostic = Diagnostic::new(code, format!("Unknown def `{}`", ident.name));
+                match suggestion {
+                    Some(suggestion) => diagnostic.primary(
+                        ident.span.clone(),
+                        format!("did you mean `{}`?", suggestion),
+                    ),
+                    None => diagnostic.primary(ident.span.clone(), "not found in this scope"),
+                }
+            }
+            TranspileError::Parse(span, message) => {
+                Diagnostic::new(code, "Syntax error").primary(span.clone(), message)
+            }
+            TranspileError::InvalidLiteral(span) => {
+                Diagnostic::new(code, "Invalid literal").primary(span.clone(), "")
+            }
             TranspileError::DefUnderscoreTerminus(span) => {
-                format_span("Def names may not start or end with '_'", span.clone(), f)
+                Diagnostic::new(code, "Def names may not start or end with '_'")
+                    .primary(span.clone(), "")
             }
             TranspileError::FunctionNamedUnderscore(span) => {
-                format_span("Function cannot be named '_'", span.clone(), f)
+                Diagnostic::new(code, "Function cannot be named '_'").primary(span.clone(), "")
             }
-            TranspileError::ReturnReferencesLocal(span) => {
-                format_span("Return value references local value", span.clone(), f)
+            TranspileError::ReturnReferencesLocal(span, provenance) => {
+                let diagnostic = Diagnostic::new(code, "Return value references local value");
+                match provenance {
+                    Some(provenance) => {
+                        let (origin_span, origin_label) = match &provenance.origin {
+                            RefOrigin::Binding(ident) => {
+                                (ident.span.clone(), format!("`{}` defined here", ident.name))
+                            }
+                            RefOrigin::Value(span) => (span.clone(), "created here".into()),
+                        };
+                        diagnostic
+                            .primary(span.clone(), describe_reference(provenance))
+                            .secondary(origin_span, origin_label)
+                    }
+                    None => diagnostic.primary(span.clone(), "returned here"),
+                }
             }
-            TranspileError::ForbiddenRedefinition(ident) => format_span(
-                format!("{} cannot be redefined", ident.name),
-                ident.span.clone(),
-                f,
-            ),
-            TranspileError::LastItemNotExpression(span) => format_span(
-                "The last item in a block must be an expression",
-                span.clone(),
-                f,
-            ),
+            TranspileError::ForbiddenRedefinition(ident) => {
+                Diagnostic::new(code, format!("`{}` cannot be redefined", ident.name))
+                    .primary(ident.span.clone(), "")
+                    .note(format!("`{}` is a builtin value", ident.name))
+            }
+            TranspileError::LastItemNotExpression(span) => {
+                Diagnostic::new(code, "The last item in a block must be an expression")
+                    .primary(span.clone(), "")
+            }
+            TranspileError::SourceTooLong(len) => {
+                Diagnostic::new(code, format!("Source is {} bytes long", len)).note(format!(
+                    "sources may be at most {} bytes long",
+                    MAX_SOURCE_LEN
+                ))
+            }
+            TranspileError::IncompatibleOperands(op, op_span, [(left, lt), (right, rt)]) => {
+                let verb = match op {
+                    BinOp::Add => "add",
+                    BinOp::Sub => "subtract",
+                    BinOp::Mul => "multiply",
+                    BinOp::Div | BinOp::Rem => "divide",
+                    _ => "compare",
+                };
+                Diagnostic::new(code, format!("Cannot {} {} and {}", verb, lt, rt))
+                    .primary(op_span.clone(), "")
+                    .secondary(left.clone(), format!("this is {}", lt))
+                    .secondary(right.clone(), format!("this is {}", rt))
+            }
+            TranspileError::InvalidNegation(span, ty) => {
+                Diagnostic::new(code, format!("Cannot negate {}", ty))
+                    .primary(span.clone(), format!("this is {}", ty))
+            }
+            TranspileError::NotCallable(span, ty) => {
+                Diagnostic::new(code, format!("Cannot call {}", ty))
+                    .primary(span.clone(), format!("this is {}", ty))
+            }
+            TranspileError::UnknownType(ident) => {
+                Diagnostic::new(code, format!("Unknown type `{}`", ident.name))
+                    .primary(ident.span.clone(), "")
+                    .note("the types are nil, bool, int, real, string, function, and error")
+            }
+            TranspileError::AnnotationMismatch(span, name, expected, found) => Diagnostic::new(
+                code,
+                format!("Expected {} to be {}, but it is {}", name, expected, found),
+            )
+            .primary(span.clone(), format!("this is {}", found)),
+            TranspileError::TestTakesParams(ident) => {
+                Diagnostic::new(code, format!("Test `{}` takes params", ident.name))
+                    .primary(ident.span.clone(), "")
+                    .note("tests are run without arguments")
+            }
+            TranspileError::ReservedExternName(ident) => {
+                Diagnostic::new(code, format!("Extern `{}` has a reserved name", ident.name))
+                    .primary(ident.span.clone(), "")
+                    .note("the runtime and the generated C already use this name")
+            }
+        }
+    }
+}
+
+impl<'a> fmt::Display for TranspileError<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.diagnostic())
+    }
+}
+
+/// Describe the chain of references from a returned value to a local one
+fn describe_reference(provenance: &Provenance) -> String {
+    let mut description = String::from("returns");
+    let mut after_def = false;
+    for step in &provenance.steps {
+        let noun = match step {
+            RefStep::Def(ident) => {
+                description.push_str(&format!(" `{}`, which", ident.name));
+                after_def = true;
+                continue;
+            }
+            RefStep::Tail => "a list whose tail",
+            RefStep::Dad => "a value whose dad",
+            RefStep::Child => "a tree whose child",
+            RefStep::Capture => "a closure that",
+            RefStep::Return => "a closure that returns",
+        };
+        if after_def {
+            description.push_str(" is");
         }
+        description.push(' ');
+        description.push_str(noun);
+        after_def = false;
+    }
+    match provenance.steps.last() {
+        None => description.push_str(" a value that references"),
+        Some(RefStep::Capture) => description.push_str(" captures"),
+        Some(RefStep::Return) => {}
+        Some(_) => description.push_str(" references"),
+    }
+    match &provenance.origin {
+        RefOrigin::Binding(ident) => format!("{} local `{}`", description, ident.name),
+        RefOrigin::Value(_) => format!("{} a local value", description),
     }
 }
 
-fn format_span(message: impl Into<String>, span: Span, f: &mut fmt::Formatter) -> fmt::Result {
-    let error = PestError::<Rule>::new_from_span(
-        ErrorVariant::CustomError {
-            message: message.into(),
+/// Describe what a syntax error expected, the way pest does
+fn syntax_error(input: &str, error: PestError<Rule>) -> TranspileError<'_> {
+    fn list(rules: &[Rule]) -> String {
+        let rules: Vec<String> = rules.iter().map(|rule| format!("{:?}", rule)).collect();
+        match rules.len() {
+            1 => rules[0].clone(),
+            2 => format!("{} or {}", rules[0], rules[1]),
+            n => format!("{}, or {}", rules[..n - 1].join(", "), rules[n - 1]),
+        }
+    }
+    let message = match error.variant {
+        ErrorVariant::ParsingError {
+            positives,
+            negatives,
+        } => match (negatives.is_empty(), positives.is_empty()) {
+            (false, false) => format!(
+                "unexpected {}; expected {}",
+                list(&negatives),
+                list(&positives)
+            ),
+            (false, true) => format!("unexpected {}", list(&negatives)),
+            (true, false) => format!("expected {}", list(&positives)),
+            (true, true) => "unknown parsing error".into(),
         },
-        span.clone(),
-    );
-    write!(f, "{}", error)
+        ErrorVariant::CustomError { message } => message,
+    };
+    let (start, end) = match error.location {
+        InputLocation::Pos(pos) => (pos, pos),
+        InputLocation::Span(span) => span,
+    };
+    TranspileError::Parse(Span::new(input, start, end).unwrap(), message)
 }
 
 fn only<R>(pair: Pair<R>) -> Pair<R>
@@ -73,13 +237,65 @@ where
     pair.into_inner().next().unwrap()
 }
 
+/// The edit distance between two strings, counting swapped adjacent characters as one edit
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut dist = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in dist.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in dist[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = (a[i - 1] != b[j - 1]) as usize;
+            dist[i][j] = (dist[i - 1][j] + 1)
+                .min(dist[i][j - 1] + 1)
+                .min(dist[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                dist[i][j] = dist[i][j].min(dist[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    dist[a.len()][b.len()]
+}
+
+/// The longest source the compiler accepts, in bytes
+///
+/// Counts that the compiler keeps as a `u32`, such as params, captures, scope depths, and
+/// bytecode lengths, are at most a small multiple of the source length, so they always fit.
+pub const MAX_SOURCE_LEN: usize = u32::MAX as usize / 16;
+
 static FORBIDDEN_REDIFINITIONS: &[&str] = &["nil", "true", "false"];
 
+/// Names that externs cannot have, as C keywords or names the generated C already uses
+static RESERVED_EXTERN_NAMES: &[&str] = &[
+    // C keywords
+    "auto", "break", "case", "char", "const", "continue", "default", "do", "double", "else", "enum",
+    "extern", "float", "for", "goto", "if", "inline", "int", "long", "register", "restrict",
+    "return", "short", "signed", "sizeof", "static", "struct", "switch", "typedef", "union",
+    "unsigned", "void", "volatile", "while",
+    // Names from the C library that the generated C uses
+    "bool", "true", "false", "NULL", "uint32_t", "int64_t", "jmp_buf", "setjmp",
+    // The entry point and the locals of generated functions
+    "main", "argc", "argv", "count", "args", "captures", "padded",
+    // Runtime names without a reserved prefix
+    "byte", "min", "bin_fn", "Nil", "Bool", "Int", "Real", "String", "Function", "Closure", "Error",
+];
+
+/// Prefixes of runtime names and of the C names of Kin defs, which externs cannot start with
+static RESERVED_EXTERN_PREFIXES: &[&str] = &["kin_", "new_", "KIN_", "Kin", "k_"];
+
 #[derive(pest_derive::Parser)]
 #[grammar = "grammar.pest"]
 struct KinParser;
 
-pub fn parse(input: &str) -> Result<Items, Vec<TranspileError>> {
+pub fn parse(input: &str) -> Result<Items<'_>, Vec<TranspileError<'_>>> {
+    if input.len() > MAX_SOURCE_LEN {
+        return Err(vec![TranspileError::SourceTooLong(input.len())]);
+    }
     match KinParser::parse(Rule::file, input) {
         Ok(mut pairs) => {
             let mut state = ParseState {
@@ -100,26 +316,61 @@ pub fn parse(input: &str) -> Result<Items, Vec<TranspileError>> {
                 Err(state.errors)
             }
         }
-        Err(e) => Err(vec![TranspileError::Parse(e)]),
+        Err(e) => Err(recover_syntax_errors(input, e)),
     }
 }
 
 #[derive(Debug, Clone)]
 enum Binding<'a> {
-    Def(Def<'a>, Lifetime),
-    Param(u8),
+    Def(Def<'a>, Lifetime, Option<Provenance<'a>>),
+    Param(Ident<'a>, u32),
     Builtin,
-    Unfinished(u8),
+    Unfinished(Ident<'a>, u32),
 }
 
 impl<'a> Binding<'a> {
     pub fn lifetime(&self) -> Lifetime {
         match self {
-            Binding::Def(_, lt) => *lt,
-            Binding::Param(depth) | Binding::Unfinished(depth) => Lifetime::new(*depth, *depth),
+            Binding::Def(_, lt, _) => *lt,
+            Binding::Param(_, depth) | Binding::Unfinished(_, depth) => {
+                Lifetime::new(*depth, *depth)
+            }
             Binding::Builtin => Lifetime::STATIC,
         }
     }
+    /// The ident that introduced this binding
+    fn ident(&self) -> Option<&Ident<'a>> {
+        match self {
+            Binding::Def(def, ..) => Some(&def.ident),
+            Binding::Param(ident, _) | Binding::Unfinished(ident, _) => Some(ident),
+            Binding::Builtin => None,
+        }
+    }
+    /// Why a reference to this binding has the refs it does
+    fn provenance(&self) -> Option<Provenance<'a>> {
+        match self {
+            Binding::Def(def, _, provenance) => provenance
+                .clone()
+                .map(|provenance| provenance.step(RefStep::Def(def.ident.clone()))),
+            Binding::Param(ident, _) | Binding::Unfinished(ident, _) => {
+                Some(Provenance::new(RefOrigin::Binding(ident.clone())))
+            }
+            Binding::Builtin => None,
+        }
+    }
+}
+
+/// Pick the provenance of the node with the most refs
+fn deepest_provenance<'a, 'b>(
+    nodes: impl IntoIterator<Item = &'b Node<'a>>,
+) -> Option<Provenance<'a>>
+where
+    'a: 'b,
+{
+    nodes
+        .into_iter()
+        .max_by_key(|node| node.lifetime.refs)
+        .and_then(|node| node.provenance.clone())
 }
 
 #[derive(Default)]
@@ -129,7 +380,8 @@ struct ParenScope<'a> {
 
 struct FunctionScope<'a> {
     scopes: Vec<ParenScope<'a>>,
-    min_refs: u8,
+    min_refs: u32,
+    min_refs_provenance: Option<Provenance<'a>>,
 }
 
 impl<'a> Default for FunctionScope<'a> {
@@ -137,6 +389,7 @@ impl<'a> Default for FunctionScope<'a> {
         FunctionScope {
             scopes: vec![ParenScope::default()],
             min_refs: 0,
+            min_refs_provenance: None,
         }
     }
 }
@@ -152,8 +405,9 @@ impl<'a> ParseState<'a> {
         self.scopes.push(FunctionScope::default());
     }
     #[must_use]
-    fn pop_function_scope(&mut self) -> u8 {
-        self.scopes.pop().unwrap().min_refs
+    fn pop_function_scope(&mut self) -> (u32, Option<Provenance<'a>>) {
+        let scope = self.scopes.pop().unwrap();
+        (scope.min_refs, scope.min_refs_provenance)
     }
     fn push_paren_scope(&mut self) {
         self.function_scope().scopes.push(ParenScope::default());
@@ -170,36 +424,78 @@ impl<'a> ParseState<'a> {
     fn span(&self, start: usize, end: usize) -> Span<'a> {
         Span::new(self.input, start, end).unwrap()
     }
-    fn depth(&self) -> u8 {
+    fn depth(&self) -> u32 {
         // self.scopes
         //     .iter()
-        //     .map(|scope| scope.scopes.len() as u8)
+        //     .map(|scope| scope.scopes.len() as u32)
         //     .sum()
-        self.scopes.len() as u8
+        u32::try_from(self.scopes.len()).expect("sources are too short to nest u32::MAX deep")
     }
-    fn bind_def(&mut self, def: Def<'a>, min_refs: u8) {
+    fn bind_def(&mut self, def: Def<'a>, (min_refs, captures): (u32, Option<Provenance<'a>>)) {
         let depth = self.depth();
-        let refs = def.items.last().unwrap().lifetime().refs.max(min_refs);
+        let last = def.items.last().unwrap();
+        let (refs, provenance) = if last.lifetime().refs > min_refs {
+            (last.lifetime().refs, last.provenance())
+        } else {
+            (min_refs, captures)
+        };
         self.scope().bindings.insert(
             def.ident.name,
-            Binding::Def(def, Lifetime::new(depth, refs)),
+            Binding::Def(def, Lifetime::new(depth, refs), provenance),
         );
     }
-    fn bind_param(&mut self, name: &'a str) {
+    fn bind_param(&mut self, ident: &Ident<'a>) {
         let depth = self.depth() - 1;
-        self.scope().bindings.insert(name, Binding::Param(depth));
+        self.scope()
+            .bindings
+            .insert(ident.name, Binding::Param(ident.clone(), depth));
     }
-    fn bind_unfinished(&mut self, name: &'a str) {
+    fn bind_unfinished(&mut self, ident: &Ident<'a>) {
         let depth = self.depth();
         self.scope()
             .bindings
-            .insert(name, Binding::Unfinished(depth));
+            .insert(ident.name, Binding::Unfinished(ident.clone(), depth));
+    }
+    /// Find the bound name closest to an unknown one, if any is close enough
+    fn closest_binding(&self, name: &str) -> Option<&'a str> {
+        let len = name.chars().count();
+        let max_distance = (len / 3).max(1);
+        self.scopes
+            .iter()
+            .flat_map(|fscope| &fscope.scopes)
+            .flat_map(|pscope| pscope.bindings.keys())
+            .filter(|bound| !bound.starts_with('_'))
+            .map(|bound| (edit_distance(name, bound), *bound))
+            // Replacing every character is not a typo
+            .filter(|(distance, _)| *distance <= max_distance && *distance < len)
+            .min()
+            .map(|(_, bound)| bound)
+    }
+    fn binding(&self, name: &str) -> Option<&Binding<'a>> {
+        self.scopes
+            .iter()
+            .rev()
+            .flat_map(|fscope| fscope.scopes.iter().rev())
+            .find_map(|pscope| pscope.bindings.get(name))
+    }
+    /// Why a node lives as deep as it does
+    ///
+    /// Idents live as deep as their bindings. Every other value is created where it is.
+    fn depth_provenance(&self, node: &Node<'a>) -> Option<Provenance<'a>> {
+        if let NodeKind::Term(Term::Ident(ident), _) = &node.kind {
+            let binding = self.binding(ident.name)?;
+            return binding
+                .ident()
+                .map(|ident| Provenance::new(RefOrigin::Binding(ident.clone())));
+        }
+        Some(Provenance::new(RefOrigin::Value(node.kind.span().clone())))
     }
     fn items(&mut self, pair: Pair<'a, Rule>, check_ref: bool) -> Items<'a> {
         let mut items = Vec::new();
         for pair in pair.into_inner() {
             match pair.as_rule() {
                 Rule::item => items.push(self.item(pair)),
+                Rule::extern_decl => items.push(self.extern_decl(pair)),
                 Rule::EOI => {}
                 rule => unreachable!("{:?}", rule),
             }
@@ -211,6 +507,7 @@ impl<'a> ParseState<'a> {
                     {
                         self.errors.push(TranspileError::ReturnReferencesLocal(
                             node.kind.span().clone(),
+                            node.provenance.clone(),
                         ))
                     }
                 }
@@ -231,6 +528,30 @@ impl<'a> ParseState<'a> {
             rule => unreachable!("{:?}", rule),
         }
     }
+    fn extern_decl(&mut self, pair: Pair<'a, Rule>) -> Item<'a> {
+        let mut pairs = pair.into_inner().skip(1);
+        let ident = self.bound_ident(pairs.next().unwrap());
+        if RESERVED_EXTERN_NAMES.contains(&ident.name)
+            || RESERVED_EXTERN_PREFIXES
+                .iter()
+                .any(|prefix| ident.name.starts_with(prefix))
+        {
+            self.errors
+                .push(TranspileError::ReservedExternName(ident.clone()));
+        }
+        let arity_pair = pairs.next().unwrap();
+        let arity = match arity_pair.as_str().parse::<u32>() {
+            Ok(arity) => arity,
+            Err(_) => {
+                self.errors
+                    .push(TranspileError::InvalidLiteral(arity_pair.as_span()));
+                0
+            }
+        };
+        // Externs live as long as the program, like builtins
+        self.scope().bindings.insert(ident.name, Binding::Builtin);
+        Item::Extern(Extern { ident, arity })
+    }
     fn ident(&mut self, pair: Pair<'a, Rule>) -> Ident<'a> {
         let name = pair.as_str();
         let span = pair.as_span();
@@ -251,17 +572,31 @@ impl<'a> ParseState<'a> {
     fn param(&mut self, pair: Pair<'a, Rule>) -> Param<'a> {
         let mut pairs = pair.into_inner();
         let ident = self.bound_ident(pairs.next().unwrap());
-        Param { ident }
+        let ty = pairs.next().and_then(|pair| self.annotation(pair));
+        Param { ident, ty }
+    }
+    fn annotation(&mut self, pair: Pair<'a, Rule>) -> Option<Type> {
+        let pair = only(pair);
+        let ident = Ident {
+            name: pair.as_str(),
+            span: pair.as_span(),
+        };
+        let ty = Type::from_name(ident.name);
+        if ty.is_none() {
+            self.errors.push(TranspileError::UnknownType(ident));
+        }
+        ty
     }
     fn def(&mut self, pair: Pair<'a, Rule>) -> Item<'a> {
         let mut pairs = pair.into_inner();
         let ident = self.bound_ident(pairs.next().unwrap());
+        let mut ty = None;
         let mut params = Vec::new();
         for pair in pairs.by_ref() {
-            if let Rule::param = pair.as_rule() {
-                params.push(self.param(pair));
-            } else {
-                break;
+            match pair.as_rule() {
+                Rule::annotation => ty = self.annotation(pair),
+                Rule::param => params.push(self.param(pair)),
+                _ => break,
             }
         }
         let is_function = !params.is_empty();
@@ -270,10 +605,10 @@ impl<'a> ParseState<'a> {
                 self.errors
                     .push(TranspileError::FunctionNamedUnderscore(ident.span.clone()));
             }
-            self.bind_unfinished(ident.name);
+            self.bind_unfinished(&ident);
             self.push_function_scope();
             for param in &params {
-                self.bind_param(param.ident.name);
+                self.bind_param(&param.ident);
             }
         }
         let pair = pairs.next().unwrap();
@@ -282,15 +617,19 @@ impl<'a> ParseState<'a> {
         let min_refs = if is_function {
             self.pop_function_scope()
         } else if ident.is_underscore() {
-            let refs = items.last().unwrap().lifetime().refs;
+            let last = items.last().unwrap();
+            let (refs, provenance) = (last.lifetime().refs, last.provenance());
             return Item::Node(
-                NodeKind::Term(Term::Expr(items), items_span).life(self.depth(), refs),
+                NodeKind::Term(Term::Expr(items), items_span)
+                    .life(self.depth(), refs)
+                    .traced(provenance),
             );
         } else {
-            0
+            (0, None)
         };
         let def = Def {
             ident,
+            ty,
             params,
             items,
         };
@@ -318,8 +657,10 @@ impl<'a> ParseState<'a> {
             span = self.span(span.start(), right.as_span().end());
             let right = self.expr_and(right);
             let refs = left.lifetime.refs.max(right.lifetime.refs);
+            let provenance = deepest_provenance([&left, &right]);
             left = NodeKind::BinExpr(BinExpr::new(left, right, op, span.clone(), op_span))
-                .life(self.depth(), refs);
+                .life(self.depth(), refs)
+                .traced(provenance);
         }
         left
     }
@@ -337,8 +678,10 @@ impl<'a> ParseState<'a> {
             span = self.span(span.start(), right.as_span().end());
             let right = self.expr_cmp(right);
             let refs = left.lifetime.refs.max(right.lifetime.refs);
+            let provenance = deepest_provenance([&left, &right]);
             left = NodeKind::BinExpr(BinExpr::new(left, right, op, span.clone(), op_span))
-                .life(self.depth(), refs);
+                .life(self.depth(), refs)
+                .traced(provenance);
         }
         left
     }
@@ -453,7 +796,10 @@ impl<'a> ParseState<'a> {
         let mut call_node = if first_call.args.is_empty() {
             *first_call.caller
         } else {
-            NodeKind::Call(first_call).life(self.depth(), refs)
+            let provenance = deepest_provenance(&first_call.args);
+            NodeKind::Call(first_call)
+                .life(self.depth(), refs)
+                .traced(provenance)
         };
         for mut chained_call in calls {
             refs = chained_call
@@ -462,8 +808,15 @@ impl<'a> ParseState<'a> {
                 .map(|node| node.lifetime.refs)
                 .max()
                 .unwrap_or(refs);
+            let provenance = if chained_call.args.is_empty() {
+                call_node.provenance.clone()
+            } else {
+                deepest_provenance(&chained_call.args)
+            };
             chained_call.args.insert(0, call_node);
-            call_node = NodeKind::Call(chained_call).life(self.depth(), refs);
+            call_node = NodeKind::Call(chained_call)
+                .life(self.depth(), refs)
+                .traced(provenance);
         }
         call_node
     }
@@ -481,8 +834,12 @@ impl<'a> ParseState<'a> {
             span = self.span(span.start(), right.as_span().end());
             let head = self.expr_mom(right);
             let refs = dad.lifetime.depth;
+            let provenance = self
+                .depth_provenance(&dad)
+                .map(|provenance| provenance.step(RefStep::Dad));
             dad = NodeKind::BinExpr(BinExpr::new(dad, head, op, span.clone(), op_span))
-                .life(self.depth(), refs);
+                .life(self.depth(), refs)
+                .traced(provenance);
         }
         dad
     }
@@ -497,11 +854,15 @@ impl<'a> ParseState<'a> {
                 ":" => BinOp::Mom,
                 rule => unreachable!("{:?}", rule),
             };
-            span = self.span(head.as_span().end(), span.start());
+            span = self.span(head.as_span().start(), span.end());
             let head = self.expr_head(head);
             let refs = mom.lifetime.depth;
+            let provenance = self
+                .depth_provenance(&mom)
+                .map(|provenance| provenance.step(RefStep::Tail));
             mom = NodeKind::BinExpr(BinExpr::new(head, mom, op, span.clone(), op_span))
-                .life(self.depth(), refs);
+                .life(self.depth(), refs)
+                .traced(provenance);
         }
         mom
     }
@@ -528,6 +889,7 @@ impl<'a> ParseState<'a> {
     fn term(&mut self, pair: Pair<'a, Rule>) -> Node<'a> {
         let span = pair.as_span();
         let pair = only(pair);
+        let mut provenance = None;
         let (term, lifetime) = match pair.as_rule() {
             Rule::int => match pair.as_str().parse::<i64>() {
                 Ok(i) => (Term::Int(i), Lifetime::new(self.depth(), 0)),
@@ -547,27 +909,28 @@ impl<'a> ParseState<'a> {
             },
             Rule::ident => {
                 let ident = self.ident(pair);
-                let lifetime = if let Some((_, binding)) = self
-                    .scopes
-                    .iter()
-                    .enumerate()
-                    .rev()
-                    .find_map(|(fi, fscope)| {
-                        fscope.scopes.iter().rev().find_map(|pscope| {
-                            pscope.bindings.get(ident.name).map(|binding| (fi, binding))
-                        })
-                    }) {
+                let lifetime = if let Some(binding) = self.binding(ident.name) {
                     let lt = binding.lifetime();
+                    provenance = binding.provenance();
                     if lt.depth > 0 && lt.depth < self.depth() {
+                        let captured = binding
+                            .ident()
+                            .map(|ident| Provenance::new(RefOrigin::Binding(ident.clone())));
                         let affected_scopes = (self.depth() - lt.depth) as usize;
                         for fscope in self.scopes.iter_mut().rev().take(affected_scopes) {
-                            let min_refs = &mut fscope.min_refs;
-                            *min_refs = (*min_refs).max(lt.depth);
+                            if lt.depth > fscope.min_refs {
+                                fscope.min_refs = lt.depth;
+                                fscope.min_refs_provenance = captured
+                                    .clone()
+                                    .map(|provenance| provenance.step(RefStep::Capture));
+                            }
                         }
                     }
                     lt
                 } else {
-                    self.errors.push(TranspileError::UnknownDef(ident.clone()));
+                    let suggestion = self.closest_binding(ident.name);
+                    self.errors
+                        .push(TranspileError::UnknownDef(ident.clone(), suggestion));
                     Lifetime::STATIC
                 };
                 (Term::Ident(ident), lifetime)
@@ -577,7 +940,9 @@ impl<'a> ParseState<'a> {
                 self.push_paren_scope();
                 let items = self.items(pair, true);
                 self.pop_paren_scope();
-                let lifetime = Lifetime::new(self.depth(), items.last().unwrap().lifetime().refs);
+                let last = items.last().unwrap();
+                let lifetime = Lifetime::new(self.depth(), last.lifetime().refs);
+                provenance = last.provenance();
                 (Term::Expr(items), lifetime)
             }
             Rule::string => {
@@ -591,15 +956,19 @@ impl<'a> ParseState<'a> {
                 let params: Vec<Param> = params_pairs.map(|pair| self.param(pair)).collect();
                 self.push_function_scope();
                 for param in &params {
-                    self.bind_param(param.ident.name);
+                    self.bind_param(&param.ident);
                 }
                 let pair = pairs.next().unwrap();
                 let body = self.function_body(pair, true);
-                let min_refs = self.pop_function_scope();
-                let lifetime = Lifetime::new(
-                    self.depth(),
-                    body.last().unwrap().lifetime().refs.max(min_refs),
-                );
+                let (min_refs, captures) = self.pop_function_scope();
+                let last = body.last().unwrap();
+                let lifetime = Lifetime::new(self.depth(), last.lifetime().refs.max(min_refs));
+                provenance = if last.lifetime().refs > min_refs {
+                    last.provenance()
+                        .map(|provenance| provenance.step(RefStep::Return))
+                } else {
+                    captures
+                };
                 (
                     Term::Closure(Closure { span, params, body }.into()),
                     lifetime,
@@ -620,6 +989,9 @@ impl<'a> ParseState<'a> {
                     let mut tail = items.next().unwrap();
                     for item in items {
                         let refs = tail.lifetime.depth;
+                        let provenance = self
+                            .depth_provenance(&tail)
+                            .map(|provenance| provenance.step(RefStep::Tail));
                         tail = NodeKind::BinExpr(BinExpr {
                             left: item.into(),
                             right: tail.into(),
@@ -627,7 +999,8 @@ impl<'a> ParseState<'a> {
                             op_span: span.clone(),
                             op: BinOp::Mom,
                         })
-                        .life(self.depth(), refs);
+                        .life(self.depth(), refs)
+                        .traced(provenance);
                     }
                     return tail;
                 }
@@ -637,11 +1010,20 @@ impl<'a> ParseState<'a> {
                 let left = self.term(pairs.next().unwrap());
                 let middle = self.term(pairs.next().unwrap());
                 let right = self.term(pairs.next().unwrap());
-                let refs = left
-                    .lifetime
-                    .depth
-                    .max(middle.lifetime.depth)
-                    .max(right.lifetime.depth);
+                let children = [&left, &middle, &right];
+                // Prefer pointing at a binding over pointing at a literal
+                let deepest = children
+                    .iter()
+                    .copied()
+                    .max_by_key(|node| {
+                        let is_ident = matches!(node.kind, NodeKind::Term(Term::Ident(_), _));
+                        (node.lifetime.depth, is_ident)
+                    })
+                    .unwrap();
+                let refs = deepest.lifetime.depth;
+                provenance = self
+                    .depth_provenance(deepest)
+                    .map(|provenance| provenance.step(RefStep::Child));
                 (
                     Term::Tree(Box::new([left, middle, right])),
                     Lifetime::new(self.depth(), refs),
@@ -649,7 +1031,9 @@ impl<'a> ParseState<'a> {
             }
             rule => unreachable!("{:?}", rule),
         };
-        NodeKind::Term(term, span).life(lifetime.depth, lifetime.refs)
+        NodeKind::Term(term, span)
+            .life(lifetime.depth, lifetime.refs)
+            .traced(provenance)
     }
     fn function_body(&mut self, pair: Pair<'a, Rule>, check_ref: bool) -> Items<'a> {
         match pair.as_rule() {
@@ -659,6 +1043,7 @@ impl<'a> ParseState<'a> {
                 if check_ref && node.lifetime.refs >= self.depth() {
                     self.errors.push(TranspileError::ReturnReferencesLocal(
                         node.kind.span().clone(),
+                        node.provenance.clone(),
                     ))
                 }
                 vec![Item::Node(node)]
@@ -707,3 +1092,217 @@ impl<'a> ParseState<'a> {
         s
     }
 }
+
+/// Collect the syntax errors of every item that fails to parse on its own
+///
+/// Pest stops at the first error, so the input is split into top-level items,
+/// and items that span multiple lines and end with `end` are split again
+/// into the items of their body. Each chunk is parsed with the rest of the input
+/// masked out so that error positions still refer to the original input.
+fn recover_syntax_errors(input: &str, error: PestError<Rule>) -> Vec<TranspileError<'_>> {
+    let lines = SourceLine::split(input);
+    let mut errors = Vec::new();
+    recover_chunks(input, &lines, &mut errors);
+    if errors.is_empty() {
+        errors.push(syntax_error(input, error));
+    }
+    errors
+}
+
+fn recover_chunks<'a>(input: &'a str, lines: &[SourceLine], errors: &mut Vec<TranspileError<'a>>) {
+    for chunk in SourceLine::chunks(lines) {
+        let start = chunk[0].start;
+        let end = chunk.last().unwrap().code_end;
+        if let Err(e) = KinParser::parse(Rule::file, &mask_outside(input, start, end)) {
+            let inner_errors = errors.len();
+            if let Some(body) = SourceLine::block_body(chunk) {
+                recover_chunks(input, body, errors);
+            }
+            if errors.len() == inner_errors {
+                errors.push(syntax_error(input, e));
+            }
+        }
+    }
+}
+
+/// Replace everything before `start` with whitespace and cut everything after `end`
+fn mask_outside(input: &str, start: usize, end: usize) -> String {
+    let mut masked: String = input[..start]
+        .chars()
+        .map(|c| if c == '\n' || c == '\r' { c } else { ' ' })
+        .collect();
+    masked.push_str(&input[start..end]);
+    masked
+}
+
+/// What the text being scanned for comments is in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scan {
+    Code,
+    String,
+    Comment,
+}
+
+impl Scan {
+    /// Scan a line, returning where its first comment starts and what the next line starts in
+    ///
+    /// Comment markers in strings are part of the string.
+    fn line(mut self, text: &str) -> (Option<usize>, Scan) {
+        let mut comment_start = None;
+        let mut i = 0;
+        while i < text.len() {
+            let rest = &text[i..];
+            let mut step = rest.chars().next().unwrap().len_utf8();
+            match self {
+                Scan::Code if rest.starts_with("--") => {
+                    comment_start.get_or_insert(i);
+                    break;
+                }
+                Scan::Code if rest.starts_with("'''") => {
+                    comment_start.get_or_insert(i);
+                    self = Scan::Comment;
+                    step = 3;
+                }
+                Scan::Code if rest.starts_with('"') => self = Scan::String,
+                Scan::String if rest.starts_with('\\') => {
+                    step += rest[1..].chars().next().map_or(0, char::len_utf8)
+                }
+                Scan::String if rest.starts_with('"') => self = Scan::Code,
+                Scan::Comment if rest.starts_with("'''") => {
+                    self = Scan::Code;
+                    step = 3;
+                }
+                _ => {}
+            }
+            i += step;
+        }
+        (comment_start, self)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SourceLine<'a> {
+    start: usize,
+    /// The end of the line's text, before the line break
+    code_end: usize,
+    indent: usize,
+    /// The line's text without indentation or comments
+    code: &'a str,
+}
+
+impl<'a> SourceLine<'a> {
+    fn split(input: &'a str) -> Vec<Self> {
+        let mut lines = Vec::new();
+        let mut start = 0;
+        let mut scan = Scan::Code;
+        for line in input.split_inclusive('\n') {
+            let end = start + line.len();
+            let text = line.trim_end_matches(&['\r', '\n'][..]);
+            let code_end = start + text.len();
+            // Lines that start in multi-line comments count as blank
+            let code = if scan == Scan::Comment {
+                scan = scan.line(text).1;
+                ""
+            } else {
+                let (comment_start, next) = scan.line(text);
+                scan = next;
+                &text[..comment_start.unwrap_or(text.len())]
+            };
+            let trimmed = code.trim_start();
+            lines.push(SourceLine {
+                start,
+                code_end,
+                indent: code.len() - trimmed.len(),
+                code: trimmed.trim_end(),
+            });
+            start = end;
+        }
+        lines
+    }
+    fn is_blank(&self) -> bool {
+        self.code.is_empty()
+    }
+    fn starts_with_word(&self, word: &str) -> bool {
+        self.code.starts_with(word)
+            && !self.code[word.len()..].starts_with(|c: char| c.is_alphanumeric() || c == '_')
+    }
+    fn ends_with_word(&self, word: &str) -> bool {
+        self.code.ends_with(word)
+            && !self.code[..self.code.len() - word.len()]
+                .ends_with(|c: char| c.is_alphanumeric() || c == '_')
+    }
+    /// Whether this line opens a block that is closed by `end`
+    fn opens_block(&self) -> bool {
+        let opens_def = self.code.ends_with('=')
+            && !["==", "!=", "<=", ">="]
+                .iter()
+                .any(|op| self.code.ends_with(op));
+        opens_def || self.code.ends_with('|')
+    }
+    fn closes_block(&self) -> bool {
+        self.starts_with_word("end")
+    }
+    /// Whether the line after this one continues the same item
+    fn continues_after(&self) -> bool {
+        self.code.ends_with(',')
+            || self.code.ends_with('(')
+            || self.ends_with_word("and")
+            || self.ends_with_word("or")
+    }
+    /// Whether this line continues the item on the line before it
+    fn continues_before(&self) -> bool {
+        self.code.starts_with(')')
+            || self.code.starts_with(',')
+            || self.starts_with_word("and")
+            || self.starts_with_word("or")
+    }
+    /// Split lines into chunks that each hold one item at the lines' base indentation
+    fn chunks(lines: &[Self]) -> Vec<&[Self]> {
+        let base = lines
+            .iter()
+            .filter(|line| !line.is_blank())
+            .map(|line| line.indent)
+            .min()
+            .unwrap_or(0);
+        let mut chunks = Vec::new();
+        let mut chunk_start = None;
+        let mut prev: Option<&Self> = None;
+        let mut open_blocks = 0usize;
+        for (i, line) in lines.iter().enumerate() {
+            if line.is_blank() {
+                continue;
+            }
+            let continues = line.indent > base
+                || open_blocks > 0
+                || line.continues_before()
+                || prev.is_some_and(|prev| prev.continues_after());
+            if chunk_start.is_none() || !continues {
+                if let Some(start) = chunk_start {
+                    chunks.push(&lines[start..i]);
+                }
+                chunk_start = Some(i);
+            }
+            if line.closes_block() {
+                open_blocks = open_blocks.saturating_sub(1);
+            }
+            if line.opens_block() {
+                open_blocks += 1;
+            }
+            prev = Some(line);
+        }
+        if let Some(start) = chunk_start {
+            chunks.push(&lines[start..]);
+        }
+        chunks
+    }
+    /// Get the body lines of a chunk that is a multi-line block ending with `end`
+    fn block_body(chunk: &[Self]) -> Option<&[Self]> {
+        let first = chunk.iter().position(|line| !line.is_blank())?;
+        let last = chunk.iter().rposition(|line| !line.is_blank())?;
+        if last > first + 1 && chunk[first].opens_block() && chunk[last].closes_block() {
+            Some(&chunk[first + 1..last])
+        } else {
+            None
+        }
+    }
+}
@@ -1,8 +1,9 @@
 use std::{
-    collections::{BTreeMap, VecDeque},
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
     fs::{self, File},
     io::{self, Write},
     iter::once,
+    path::{Path, PathBuf},
 };
 
 use itertools::*;
@@ -10,6 +11,28 @@ use rpds::{RedBlackTreeMap, Vector};
 
 use crate::ast::*;
 
+/// The Kin runtime header, bundled into the compiler
+pub const RUNTIME_HEADER: &str = include_str!("../clibs/kin.h");
+
+/// Where the generated C gets the Kin runtime header from
+#[derive(Debug, Clone)]
+pub enum Runtime {
+    /// Write the bundled header next to the generated C
+    Bundled,
+    /// Paste the bundled header into the generated C
+    Inline,
+    /// Include an external header, such as a development copy of `clibs/kin.h`
+    External(PathBuf),
+}
+
+/// What a function's annotations are checked against
+struct Signature<'a> {
+    /// How panics refer to the function
+    description: String,
+    params: Params<'a>,
+    ret: Option<Type>,
+}
+
 struct KinDef {
     is_function: bool,
     c_name: String,
@@ -49,47 +72,59 @@ pub const BUILTIN_VALUES: &[(&str, &str)] = &[
     ("false", "KIN_FALSE"),
 ];
 
-static RESERVED_NAMES: &[&str] = &[
-    // C keywords
-    "auto",
-    "break",
-    "case",
-    "char",
-    "const",
-    "continue",
-    "default",
-    "do",
-    "double",
-    "else",
-    "enum",
-    "extern",
-    "float",
-    "for",
-    "goto",
-    "if",
-    "inline ",
-    "int",
-    "long",
-    "register",
-    "restrict ",
-    "return",
-    "short",
-    "signed",
-    "sizeof",
-    "static",
-    "struct",
-    "switch",
-    "typedef",
-    "union",
-    "unsigned",
-    "void",
-    "volatile",
-    "while",
-    // Others
-    "count",
-    "argc",
-    "argv",
-];
+/// Get the C operator and the value constructor for an operation with a raw int fast path
+fn int_op(op: BinOp) -> Option<(&'static str, &'static str)> {
+    Some(match op {
+        BinOp::Add => ("+", "new_int"),
+        BinOp::Sub => ("-", "new_int"),
+        BinOp::Mul => ("*", "new_int"),
+        BinOp::Less => ("<", "new_bool"),
+        BinOp::LessOrEqual => ("<=", "new_bool"),
+        BinOp::Greater => (">", "new_bool"),
+        BinOp::GreaterOrEqual => (">=", "new_bool"),
+        _ => return None,
+    })
+}
+
+/// Get the raw C int of an expression that evaluates to an int
+fn int_value(expr: &str) -> String {
+    match expr
+        .strip_prefix("new_int(")
+        .and_then(|s| s.strip_suffix(')'))
+    {
+        Some(i) if i.parse::<i64>().is_ok() => i.into(),
+        Some(i) => format!("({})", i),
+        None => format!("{}.data.Int", operand(expr)),
+    }
+}
+
+/// Escape a Kin name so that it is a valid part of a C identifier
+///
+/// ASCII letters, digits, and underscores are kept. Anything else, such as an emoji,
+/// becomes `__u` followed by its code point in hex and an underscore. So that no two
+/// names are escaped the same way, an underscore before another underscore is escaped too.
+fn escape_c_name(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    let mut chars = name.chars().peekable();
+    while let Some(c) = chars.next() {
+        let before_underscore = chars.peek() == Some(&'_');
+        if c.is_ascii_alphanumeric() || c == '_' && !before_underscore {
+            escaped.push(c);
+        } else {
+            escaped.push_str(&format!("__u{:x}_", c as u32));
+        }
+    }
+    escaped
+}
+
+/// Parenthesize a dereference so that its members can be accessed
+fn operand(expr: &str) -> String {
+    if expr.starts_with('*') {
+        format!("({})", expr)
+    } else {
+        expr.into()
+    }
+}
 
 #[derive(Clone)]
 struct TranspileStack<'a> {
@@ -141,6 +176,36 @@ impl<'a> TranspileStack<'a> {
 pub struct Transpilation<'a> {
     functions: BTreeMap<String, CFunction<'a>>,
     function_stack: Vec<String>,
+    /// The C functions declared with `extern`, whose names are taken
+    externs: Vec<&'a str>,
+    test_harness: bool,
+    inline_ops: bool,
+    type_checks: bool,
+    /// The prefix of exported symbols, if this is a library
+    library: Option<String>,
+    /// The exported symbols and the C functions they call
+    exports: Vec<(String, String)>,
+    /// Top-level functions that cannot be exported because they capture values
+    unexported: Vec<&'a str>,
+    /// Every C name given to a Kin name or function
+    c_names: HashSet<String>,
+    /// How many times each C name has been asked for
+    c_name_counts: HashMap<String, usize>,
+}
+
+/// Options that change the generated C
+#[derive(Debug, Clone, Default)]
+pub struct TranspileOptions {
+    /// Generate a test harness that runs every top-level `test_*` def
+    pub test_harness: bool,
+    /// Call arithmetic and comparison builtins directly, without recording call sites,
+    /// and use raw C arithmetic where operands are known to be ints
+    pub inline_ops: bool,
+    /// Leave out the runtime checks of type annotations
+    pub no_type_checks: bool,
+    /// Generate a library with no `main` that exports its top-level functions, prefixing
+    /// their symbols with this name
+    pub library: Option<String>,
 }
 
 #[derive(Clone)]
@@ -150,16 +215,19 @@ struct CFunction<'a> {
     lines: Vec<CLine>,
     captures: Vec<CCapture>,
     indent: usize,
+    /// How many times each temporary name has been used
+    temps: HashMap<String, usize>,
 }
 
 impl<'a> CFunction<'a> {
-    pub fn new(kin_name: &'a str) -> CFunction {
+    pub fn new(kin_name: &'a str) -> CFunction<'a> {
         CFunction {
             kin_name,
             exprs: Default::default(),
             lines: Default::default(),
             captures: Default::default(),
             indent: 0,
+            temps: Default::default(),
         }
     }
 }
@@ -240,12 +308,70 @@ impl<'a> CFunction<'a> {
     }
 }
 
+/// Include or paste the runtime header
+fn write_runtime_include<W: Write>(mut source: W, runtime: &Runtime) -> io::Result<()> {
+    match runtime {
+        Runtime::Bundled => writeln!(source, "#include \"kin.h\""),
+        Runtime::Inline => writeln!(source, "{}", RUNTIME_HEADER),
+        Runtime::External(path) => {
+            let path = path.to_string_lossy();
+            writeln!(source, "#include \"{}\"", escape_c_string(&path))
+        }
+    }
+}
+
+/// Escape backslashes and quotes so that text can go between the quotes of a C string
+///
+/// Unlike Rust's debug formatting, other characters are kept as they are.
+fn escape_c_string(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
 pub fn transpile(items: Items) -> Transpilation {
+    transpile_with(items, TranspileOptions::default())
+}
+
+/// Transpile a test harness that runs every top-level `test_*` def
+pub fn transpile_tests(items: Items) -> Transpilation {
+    let options = TranspileOptions {
+        test_harness: true,
+        ..Default::default()
+    };
+    transpile_with(items, options)
+}
+
+pub fn transpile_with(items: Items, options: TranspileOptions) -> Transpilation {
     let mut transpilation = Transpilation::new();
+    transpilation.test_harness = options.test_harness;
+    transpilation.inline_ops = options.inline_ops;
+    transpilation.type_checks = !options.no_type_checks;
+    transpilation.library = options.library;
+    transpilation.externs = items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Extern(ext) => Some(ext.ident.name),
+            _ => None,
+        })
+        .collect();
+    // The test harness only runs tests and the defs they use
+    let items = if options.test_harness {
+        items
+            .into_iter()
+            .filter(|item| !matches!(item, Item::Node(_)))
+            .collect()
+    } else {
+        items
+    };
     transpilation.items(items, TranspileStack::new());
     transpilation
 }
 
+impl<'a> Default for Transpilation<'a> {
+    fn default() -> Self {
+        Transpilation::new()
+    }
+}
+
 impl<'a> Transpilation<'a> {
     pub fn new() -> Self {
         Transpilation {
@@ -254,29 +380,94 @@ impl<'a> Transpilation<'a> {
                 .map(|name| (name.into(), CFunction::new(name)))
                 .collect(),
             function_stack: once("main".into()).collect(),
+            externs: Vec::new(),
+            library: None,
+            exports: Vec::new(),
+            unexported: Vec::new(),
+            c_names: HashSet::new(),
+            c_name_counts: HashMap::new(),
+            test_harness: false,
+            inline_ops: false,
+            type_checks: true,
         }
     }
-    pub fn write(self) -> io::Result<()> {
-        fs::create_dir_all("build")?;
-        let mut source = File::create("build/main.c")?;
-
+    /// Write `main.c` to a directory, along with the runtime header if it is bundled
+    pub fn write(self, dir: &Path, runtime: &Runtime) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        if let Runtime::Bundled = runtime {
+            fs::write(dir.join("kin.h"), RUNTIME_HEADER)?;
+        }
+        let source = File::create(dir.join("main.c"))?;
+        self.write_to(io::BufWriter::new(source), runtime)
+    }
+    /// Top-level functions of a library that capture values, so they cannot be exported
+    pub fn unexported(&self) -> &[&'a str] {
+        &self.unexported
+    }
+    /// Write a header that declares a library's exported functions
+    pub fn write_header<W: Write>(&self, mut header: W, runtime: &Runtime) -> io::Result<()> {
+        let prefix = self.library.as_deref().unwrap_or("kin");
+        let guard = format!("{}_H", prefix.to_uppercase());
+        writeln!(
+            header,
+            "// Generated by `kin build --lib`. Functions take a count of args and a pointer to them"
+        )?;
+        writeln!(header, "#ifndef {}\n#define {}\n", guard, guard)?;
+        write_runtime_include(&mut header, runtime)?;
+        writeln!(header)?;
+        for (symbol, _) in &self.exports {
+            writeln!(
+                header,
+                "KinValue {}(uint32_t count, KinValue* args);",
+                symbol
+            )?;
+        }
+        writeln!(header, "\n#endif")?;
+        header.flush()
+    }
+    /// Get the generated C as a string
+    pub fn into_c_source(self, runtime: &Runtime) -> String {
+        let mut source = Vec::new();
+        self.write_to(&mut source, runtime).unwrap();
+        String::from_utf8(source).unwrap()
+    }
+    /// Write the generated C
+    ///
+    /// A bundled runtime is included as `kin.h`, so the caller must make sure it
+    /// is next to the generated C. Use `Runtime::Inline` for self-contained C.
+    pub fn write_to<W: Write>(&self, mut source: W, runtime: &Runtime) -> io::Result<()> {
         // Write headers
-        writeln!(source, "#include \"../clibs/kin.h\"")?;
+        write_runtime_include(&mut source, runtime)?;
         writeln!(source)?;
 
+        // Write extern declarations
+        for name in &self.externs {
+            writeln!(source, "KinValue {}(uint32_t count, KinValue* args);", name)?;
+        }
+        if !self.externs.is_empty() {
+            writeln!(source)?;
+        }
+
+        // Only a library's exports are public, so that libraries can be linked together
+        let linkage = if self.library.is_some() {
+            "static "
+        } else {
+            ""
+        };
+
         // Write function declarations
         for (name, cf) in self.functions.iter().filter(|&(name, _)| name != "main") {
             if cf.captures.is_empty() {
                 writeln!(
                     source,
-                    "KinValue {}(uint8_t count, KinValue* args);",
-                    name
+                    "{}KinValue {}(uint32_t count, KinValue* args);",
+                    linkage, name
                 )?;
             } else {
                 writeln!(
                     source,
-                    "KinValue {}(uint8_t count, KinValue* args, KinValue* captures);",
-                    name
+                    "{}KinValue {}(uint32_t count, KinValue* args, KinValue* captures);",
+                    linkage, name
                 )?;
             }
         }
@@ -285,20 +476,24 @@ impl<'a> Transpilation<'a> {
         // Write function definitions
         for (name, cf) in &self.functions {
             let main = name == "main";
+            // Libraries have no `main`, so their top-level code never runs
+            if main && self.library.is_some() {
+                continue;
+            }
             // Write signature
             if main {
                 writeln!(source, "int main(int argc, char** argv) {{")?;
             } else if cf.captures.is_empty() {
                 writeln!(
                     source,
-                    "KinValue {}(uint8_t count, KinValue* args) {{",
-                    name
+                    "{}KinValue {}(uint32_t count, KinValue* args) {{",
+                    linkage, name
                 )?;
             } else {
                 writeln!(
                     source,
-                    "KinValue {}(uint8_t count, KinValue* args, KinValue* captures) {{",
-                    name
+                    "{}KinValue {}(uint32_t count, KinValue* args, KinValue* captures) {{",
+                    linkage, name
                 )?;
             }
             // Write lines
@@ -322,38 +517,69 @@ impl<'a> Transpilation<'a> {
                 if let Some(expr) = cf.clone().pop_expr() {
                     writeln!(source, "    {};", expr)?;
                 }
-                writeln!(source, "    return 0;")?;
+                if self.test_harness {
+                    writeln!(source, "    return kin_test_report();")?;
+                } else {
+                    writeln!(source, "    return 0;")?;
+                }
             }
             // Close function
             writeln!(source, "}}\n")?;
         }
 
-        Ok(())
+        // Write exports
+        for (symbol, c_name) in &self.exports {
+            writeln!(
+                source,
+                "KinValue {}(uint32_t count, KinValue* args) {{\n    return {}(count, args);\n}}\n",
+                symbol, c_name
+            )?;
+        }
+
+        source.flush()
     }
-    fn c_name_exists(&self, c_name: &str, function: bool) -> bool {
-        RESERVED_NAMES.contains(&c_name)
-            || function && self.functions.keys().any(|name| name == c_name)
-            || !function
-                && self
-                    .functions
-                    .values()
-                    .flat_map(|cf| &cf.lines)
-                    .filter_map(|cf| cf.var_name.as_ref())
-                    .any(|var_name| var_name == c_name)
+    /// Get a unique C name for a Kin name bound in the current function
+    ///
+    /// The name is the path of the C function it is bound in followed by the escaped Kin
+    /// name, such as `k_outer_inner` for `inner` in `outer`. Only redefinitions in the
+    /// same function need a numbered suffix. The `k_` prefix keeps user names apart from
+    /// the runtime, C keywords, and the C library.
+    fn c_name_for(&mut self, kin_name: &str) -> String {
+        let current = self.function_stack.last().unwrap();
+        let path = if current == "main" { "k" } else { current };
+        let c_name = format!("{}_{}", path, escape_c_name(kin_name));
+        self.unique_c_name(c_name)
     }
-    fn c_name_for(&self, kin_name: &str, function: bool) -> String {
-        let mut c_name = kin_name.to_owned();
-        if c_name.starts_with("kin") || c_name.starts_with("Kin") {
-            c_name = "_".to_owned() + &c_name;
+    /// Number a name if it is taken, then take it
+    fn unique_c_name(&mut self, c_name: String) -> String {
+        loop {
+            let count = self.c_name_counts.entry(c_name.clone()).or_insert(0);
+            *count += 1;
+            let candidate = match *count {
+                1 => c_name.clone(),
+                n => format!("{}_{}", c_name, n),
+            };
+            if self.c_names.insert(candidate.clone()) {
+                return candidate;
+            }
         }
-        let mut i = 1;
-        while self.c_name_exists(&c_name, function) {
-            i += 1;
-            c_name = format!("{}_{}", kin_name, i);
+    }
+    /// Get a name for a temporary in the current function
+    ///
+    /// Temporaries never start with `k_`, so they cannot collide with Kin names.
+    fn temp_name(&mut self, name: &str) -> String {
+        let count = self.c_function().temps.entry(name.into()).or_insert(0);
+        *count += 1;
+        match *count {
+            1 => name.into(),
+            n => format!("{}_{}", name, n),
         }
-        c_name
     }
     fn start_c_function(&mut self, c_name: String, kin_name: &'a str) {
+        // Reserve the names of the function's closure and captures
+        for suffix in ["closure", "captures"] {
+            self.c_names.insert(format!("{}_{}", c_name, suffix));
+        }
         self.functions
             .insert(c_name.clone(), CFunction::new(kin_name));
         self.function_stack.push(c_name);
@@ -394,7 +620,7 @@ impl<'a> Transpilation<'a> {
         let item_count = items.len();
         for (i, item) in items.into_iter().enumerate() {
             stack = self.item(item, stack);
-            if i < item_count - 1 {
+            if i + 1 < item_count {
                 let cf = self.c_function();
                 if let Some(expr) = cf.pop_expr() {
                     cf.push_line(expr);
@@ -406,6 +632,7 @@ impl<'a> Transpilation<'a> {
     fn item(&mut self, item: Item<'a>, stack: TranspileStack<'a>) -> TranspileStack<'a> {
         match item {
             Item::Def(def) => self.def(def, stack),
+            Item::Extern(ext) => self.extern_def(ext, stack),
             Item::Node(node) => {
                 self.node(node, stack.clone());
                 stack
@@ -413,8 +640,88 @@ impl<'a> Transpilation<'a> {
         }
     }
 
+    /// Wrap an extern C function so that it is always passed `arity` args
+    fn extern_def(&mut self, ext: Extern<'a>, stack: TranspileStack<'a>) -> TranspileStack<'a> {
+        let name = ext.ident.name;
+        let c_name = self.c_name_for(name);
+        self.start_c_function(c_name.clone(), name);
+        let cf = self.c_function();
+        if ext.arity == 0 {
+            cf.push_line(format!("return {}(0, NULL)", name));
+        } else {
+            cf.push_line(format!("KinValue padded[{}]", ext.arity));
+            cf.push_line(format!(
+                "for (uint32_t i = 0; i < {}; i++) padded[i] = i < count ? args[i] : KIN_NIL",
+                ext.arity
+            ));
+            cf.push_line(format!("return {}({}, padded)", name, ext.arity));
+        }
+        self.function_stack.pop();
+        stack.with_kin_def(
+            name,
+            KinDef {
+                c_name,
+                is_function: true,
+            },
+        )
+    }
+
     fn def(&mut self, def: Def<'a>, stack: TranspileStack<'a>) -> TranspileStack<'a> {
-        let c_name = self.c_name_for(&def.ident.name, def.is_function());
+        let top_level = self.function_stack.len() == 1;
+        if self.test_harness && top_level && def.is_test() {
+            self.test_def(def, stack)
+        } else if self.library.is_some() && top_level {
+            self.export_def(def, stack)
+        } else {
+            self.plain_def(def, stack)
+        }
+    }
+    /// Transpile a test, which is a def that the harness evaluates while catching panics
+    ///
+    /// Inference rejects tests with params, so every test is a value.
+    fn test_def(&mut self, def: Def<'a>, stack: TranspileStack<'a>) -> TranspileStack<'a> {
+        let c_name = self.c_name_for(def.ident.name);
+        let cf = self.c_function();
+        // Declare the value outside the block so that later items can use it
+        cf.push_line("KIN_NIL").name(c_name.clone());
+        cf.push_line(format!(
+            "kin_test_start(\"{}\")",
+            escape_c_string(def.ident.name)
+        ));
+        cf.push_line("if (!setjmp(kin_test_jmp)) {").no_semicolon();
+        cf.indent();
+        let stack = self.value_def(def, c_name, true, stack);
+        let cf = self.c_function();
+        cf.push_line("kin_test_pass()");
+        cf.deindent();
+        cf.push_line("}").no_semicolon();
+        stack
+    }
+    /// Transpile a top-level def of a library and export it if it is a function
+    ///
+    /// Only the last def of a name is exported. The library has no `main` to keep captured
+    /// values alive, so only functions that capture nothing can be exported.
+    fn export_def(&mut self, def: Def<'a>, stack: TranspileStack<'a>) -> TranspileStack<'a> {
+        let name = def.ident.name;
+        let is_function = def.is_function();
+        let prefix = self.library.as_ref().unwrap();
+        let symbol = format!("{}_{}", prefix, escape_c_name(name));
+        self.exports.retain(|(export, _)| *export != symbol);
+        self.unexported.retain(|unexported| *unexported != name);
+        let stack = self.plain_def(def, stack);
+        if !is_function {
+            return stack;
+        }
+        let c_name = stack.kin_scopes.last().unwrap()[name].c_name.clone();
+        if self.functions[&c_name].captures.is_empty() {
+            self.exports.push((symbol, c_name));
+        } else {
+            self.unexported.push(name);
+        }
+        stack
+    }
+    fn plain_def(&mut self, def: Def<'a>, stack: TranspileStack<'a>) -> TranspileStack<'a> {
+        let c_name = self.c_name_for(def.ident.name);
         if def.is_function() {
             // Function
             let stack = stack.with_kin_def(
@@ -424,24 +731,46 @@ impl<'a> Transpilation<'a> {
                     is_function: true,
                 },
             );
-            self.function(c_name, def.ident.name, def.params, def.items, stack.clone());
+            let signature = Signature {
+                description: format!("`{}`", def.ident.name),
+                params: def.params,
+                ret: def.ty,
+            };
+            self.function(c_name, def.ident.name, signature, def.items, stack.clone());
             stack
         } else {
-            // Value
-            self.items(def.items, stack.clone());
-            let cf = self.c_function();
-            let line = cf.pop_expr();
-            if let Some(line) = line {
-                cf.push_line(line).name(c_name.clone());
+            self.value_def(def, c_name, false, stack)
+        }
+    }
+    /// Transpile a def that is not a function, assigning it to a variable that is
+    /// declared here unless it already has been
+    fn value_def(
+        &mut self,
+        def: Def<'a>,
+        c_name: String,
+        declared: bool,
+        stack: TranspileStack<'a>,
+    ) -> TranspileStack<'a> {
+        self.items(def.items, stack.clone());
+        let cf = self.c_function();
+        let line = cf.pop_expr();
+        if let Some(line) = line {
+            let line = cf.push_line(line).name(c_name.clone());
+            if declared {
+                line.no_type();
+            }
+            if let Some(ty) = def.ty {
+                let name = format!("`{}`", def.ident.name);
+                self.check_type(&c_name, ty, &name);
             }
-            stack.with_kin_def(
-                def.ident.name,
-                KinDef {
-                    c_name,
-                    is_function: false,
-                },
-            )
         }
+        stack.with_kin_def(
+            def.ident.name,
+            KinDef {
+                c_name,
+                is_function: false,
+            },
+        )
     }
     fn node(&mut self, node: Node<'a>, stack: TranspileStack<'a>) {
         match node.kind {
@@ -452,18 +781,25 @@ impl<'a> Transpilation<'a> {
         }
     }
     fn bin_expr(&mut self, expr: BinExpr<'a>, stack: TranspileStack<'a>) {
+        let types = (expr.left.ty, expr.right.ty);
         self.node(*expr.left, stack.clone());
         let left = self.pop_expr();
         let (f, can_fail) = match expr.op {
             BinOp::Or | BinOp::And => {
                 let or = expr.op == BinOp::Or;
-                let temp_name = self.c_name_for("temp", false);
+                let temp_name = self.temp_name("temp");
+                // Bools need no conversion to be tested
+                let condition = if self.inline_ops && types.0 == Type::BOOL {
+                    format!("{}.data.Bool", temp_name)
+                } else {
+                    format!("kin_is_true({})", temp_name)
+                };
                 let cf = self.c_function();
                 cf.push_line(left).name(&temp_name);
                 cf.push_line(format!(
-                    "if ({}kin_is_true({})) {{",
+                    "if ({}{}) {{",
                     if or { "!" } else { "" },
-                    temp_name
+                    condition
                 ))
                 .no_semicolon();
                 cf.indent();
@@ -480,7 +816,7 @@ impl<'a> Transpilation<'a> {
                 let mom = expr.op == BinOp::Mom;
                 self.node(*expr.right, stack);
                 let right = self.pop_expr();
-                let head_name = self.c_name_for("head", false);
+                let head_name = self.temp_name("head");
                 let cf = self.c_function();
                 cf.push_line(if mom { left.clone() } else { right.clone() })
                     .name(&head_name);
@@ -506,21 +842,90 @@ impl<'a> Transpilation<'a> {
         };
         self.node(*expr.right, stack);
         let right = self.pop_expr();
-        if can_fail {
+        // Operators that can fail record where they were called, so panics can report it
+        let call_site = if can_fail {
             let function_name = &self.curr_c_function().kin_name;
             let (line, col) = expr.op_span.split().0.line_col();
-            let call_line = format!(
-                "kin_call_bin_op({}, {}, {}, \"{} {}:{}\")",
-                f, left, right, function_name, line, col
-            );
-            self.push_expr(call_line)
+            Some(format!("{} {}:{}", function_name, line, col))
+        } else {
+            None
+        };
+        let call = |left: &str, right: &str| match &call_site {
+            Some(call_site) => format!(
+                "kin_call_bin_op({}, {}, {}, \"{}\")",
+                f,
+                left,
+                right,
+                escape_c_string(call_site)
+            ),
+            None => format!("{}({}, {})", f, left, right),
+        };
+        if self.inline_ops {
+            let ints = (types.0 == Type::INT, types.1 == Type::INT);
+            // A type check is only worth it if the other operand may be an int
+            let may_be_int = types.0.contains(Type::INT) && types.1.contains(Type::INT);
+            let fast_path = int_op(expr.op).and_then(|(op, wrap)| match ints {
+                (true, true) => Some(format!(
+                    "{}({} {} {})",
+                    wrap,
+                    int_value(&left),
+                    op,
+                    int_value(&right)
+                )),
+                // Check the type of the operand that is not known to be an int
+                (true, false) | (false, true) if may_be_int => {
+                    let (left, right) = (
+                        self.simple_expr(left.clone()),
+                        self.simple_expr(right.clone()),
+                    );
+                    let unknown = if ints.0 { &right } else { &left };
+                    Some(format!(
+                        "({}.type == Int ? {}({} {} {}) : {})",
+                        operand(unknown),
+                        wrap,
+                        int_value(&left),
+                        op,
+                        int_value(&right),
+                        call(&left, &right)
+                    ))
+                }
+                _ => None,
+            });
+            let expr = fast_path.unwrap_or_else(|| call(&left, &right));
+            self.push_expr(expr)
+        } else {
+            self.push_expr(call(&left, &right))
+        }
+    }
+    /// Bind an expression to a temporary unless it is already a name or a literal,
+    /// so that it can be used more than once
+    fn simple_expr(&mut self, expr: String) -> String {
+        let is_name = expr
+            .trim_start_matches('*')
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if is_name || expr.starts_with("new_int(") {
+            expr
         } else {
-            self.push_expr(format!("{}({}, {})", f, left, right))
+            let temp_name = self.temp_name("operand");
+            self.c_function().push_line(expr).name(&temp_name);
+            temp_name
         }
     }
     fn un_expr(&mut self, expr: UnExpr<'a>, stack: TranspileStack<'a>) {
+        let is_int = expr.inner.ty == Type::INT;
         self.node(*expr.inner, stack);
         let inner = self.pop_expr();
+        if self.inline_ops && is_int && matches!(expr.op, UnOp::Neg) {
+            let value = int_value(&inner);
+            // Avoid writing a decrement
+            let value = if value.starts_with('-') {
+                format!("({})", value)
+            } else {
+                value
+            };
+            return self.push_expr(format!("new_int(-{})", value));
+        }
         let f = match expr.op {
             UnOp::Neg => "kin_neg",
             UnOp::Head => "kin_head",
@@ -557,7 +962,7 @@ impl<'a> Transpilation<'a> {
         } else {
             self.node(node, stack.clone());
             let left = self.pop_expr();
-            let name = self.c_name_for(name, false);
+            let name = self.temp_name(name);
             self.c_function().push_line(left).name(&name);
             name
         }
@@ -569,14 +974,13 @@ impl<'a> Transpilation<'a> {
             Term::String(s) => self.push_expr(format!("new_string({:?}, {})", s, s.len())),
             Term::Expr(items) => self.items(items, stack),
             Term::Closure(closure) => {
-                let c_name = self.c_name_for("anon", true);
-                self.function(
-                    c_name.clone(),
-                    "closure",
-                    closure.params,
-                    closure.body,
-                    stack,
-                );
+                let c_name = self.c_name_for("anon");
+                let signature = Signature {
+                    description: "a closure".into(),
+                    params: closure.params,
+                    ret: None,
+                };
+                self.function(c_name.clone(), "closure", signature, closure.body, stack);
                 if self.functions.get(&c_name).unwrap().captures.is_empty() {
                     self.push_expr(format!("new_function(&{})", c_name))
                 } else {
@@ -650,7 +1054,7 @@ impl<'a> Transpilation<'a> {
                             let is_closure = self
                                 .functions
                                 .get(&def.c_name)
-                                .map_or(false, |cf| !cf.captures.is_empty());
+                                .is_some_and(|cf| !cf.captures.is_empty());
                             if is_closure {
                                 format!("{}_closure", def.c_name)
                             } else {
@@ -671,20 +1075,42 @@ impl<'a> Transpilation<'a> {
             }
         }
     }
+    /// Panic at runtime if a value does not have an annotated type
+    fn check_type(&mut self, value: &str, ty: Type, name: &str) {
+        if self.type_checks {
+            self.c_function().push_line(format!(
+                "kin_check_type({}, {}, \"{}\", \"{}\")",
+                value,
+                ty.bits(),
+                ty,
+                escape_c_string(name)
+            ));
+        }
+    }
     fn function(
         &mut self,
         c_name: String,
         kin_name: &'a str,
-        params: Params<'a>,
+        signature: Signature<'a>,
         items: Items<'a>,
         stack: TranspileStack<'a>,
     ) {
+        let Signature {
+            description,
+            params,
+            ret,
+        } = signature;
         self.start_c_function(c_name.clone(), kin_name);
-        let cf = self.c_function();
-        for i in 0..params.len() {
-            cf.push_line(format!("{i} < count ? &args[{i}] : &KIN_NIL", i = i))
-                .name(format!("{}_arg{}", c_name, i))
+        for (i, param) in params.iter().enumerate() {
+            let arg_name = format!("{}_arg{}", c_name, i);
+            self.c_names.insert(arg_name.clone());
+            self.c_function()
+                .push_line(format!("{i} < count ? &args[{i}] : &KIN_NIL", i = i))
+                .name(&arg_name)
                 .ty("KinValue*");
+            if let Some(ty) = param.ty {
+                self.check_type(&format!("*{}", arg_name), ty, &param.describe(&description));
+            }
         }
         let stack = params
             .into_iter()
@@ -700,6 +1126,14 @@ impl<'a> Transpilation<'a> {
             });
         // Transpile body items and finish function
         self.items(items, stack);
+        if let (Some(ty), true) = (ret, self.type_checks) {
+            let ret_expr = self.pop_expr();
+            let ret_name = self.temp_name("ret");
+            self.c_function().push_line(ret_expr).name(&ret_name);
+            let name = format!("the return value of {}", description);
+            self.check_type(&ret_name, ty, &name);
+            self.push_expr(ret_name);
+        }
         let captures = self.curr_c_function().captures.clone();
         self.finish_c_function();
         // Set captures in parent scope
@@ -0,0 +1,499 @@
+use std::{cmp::Ordering, fmt, io, rc::Rc};
+
+use crate::{
+    ast::BinOp,
+    bytecode::{CaptureFrom, Constant, Op, Program},
+    transpile::BUILTIN_FUNCTIONS,
+};
+
+/// Why a program stopped early
+#[derive(Debug)]
+pub enum VmError {
+    /// The program panicked. The message has already been printed.
+    Panic,
+    Io(io::Error),
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VmError::Panic => write!(f, "Kin panicked"),
+            VmError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for VmError {}
+
+impl From<io::Error> for VmError {
+    fn from(e: io::Error) -> Self {
+        VmError::Io(e)
+    }
+}
+
+type VmResult<T = ()> = Result<T, VmError>;
+
+/// A Kin value, laid out like `KinValue` in kin.h
+#[derive(Debug, Clone)]
+pub struct Value {
+    pub data: Data,
+    pub mom: Option<Rc<Value>>,
+    pub dad: Option<Rc<Value>>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Data {
+    Nil,
+    Bool(bool),
+    Int(i64),
+    Real(f64),
+    String(Rc<str>),
+    /// A builtin function by its index in `BUILTIN_FUNCTIONS`
+    Builtin(u32),
+    Closure(Rc<Closure>),
+    Error(Rc<Value>),
+}
+
+#[derive(Debug)]
+pub struct Closure {
+    pub function: u32,
+    pub captures: Vec<Value>,
+}
+
+const NIL: Value = Value::new(Data::Nil);
+
+/// How many values the stack may hold before the program panics
+const MAX_STACK: usize = 1 << 22;
+
+impl Value {
+    const fn new(data: Data) -> Self {
+        Value {
+            data,
+            mom: None,
+            dad: None,
+        }
+    }
+    /// The tag of the value's type in kin.h
+    fn type_tag(&self) -> u8 {
+        match self.data {
+            Data::Nil => 0,
+            Data::Bool(_) => 1,
+            Data::Int(_) => 2,
+            Data::Real(_) => 3,
+            Data::String(_) => 4,
+            Data::Builtin(_) => 5,
+            Data::Closure(_) => 6,
+            Data::Error(_) => 7,
+        }
+    }
+    fn type_name(&self) -> &'static str {
+        [
+            "nil", "bool", "int", "real", "string", "function", "function", "error",
+        ][self.type_tag() as usize]
+    }
+    fn is_true(&self) -> bool {
+        match self.data {
+            Data::Bool(b) => b,
+            Data::Nil | Data::Error(_) => false,
+            _ => true,
+        }
+    }
+    fn as_real(&self) -> Option<f64> {
+        match self.data {
+            Data::Int(i) => Some(i as f64),
+            Data::Real(r) => Some(r),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.data {
+            Data::Nil => write!(f, "nil"),
+            Data::Bool(b) => write!(f, "{}", b),
+            Data::Int(i) => write!(f, "{}", i),
+            Data::Real(r) if r.is_nan() => write!(f, "nan"),
+            // Like the C runtime, print six decimal places without trailing zeros or point
+            Data::Real(r) => {
+                let s = format!("{:.6}", r);
+                write!(f, "{}", s.trim_end_matches(['0', '.']))
+            }
+            Data::String(s) => write!(f, "{}", s),
+            Data::Builtin(_) | Data::Closure(_) => write!(f, "function"),
+            Data::Error(inner) => write!(f, "Error: {}", inner),
+        }
+    }
+}
+
+struct Frame {
+    function: u32,
+    ip: usize,
+    /// Where the frame's locals start on the stack. The callee is just below.
+    base: usize,
+    callee: Value,
+}
+
+/// Run a program, printing to `out`
+pub fn run<W: io::Write>(program: &Program, out: W) -> VmResult {
+    let mut vm = Vm {
+        program,
+        out,
+        stack: Vec::new(),
+        frames: Vec::new(),
+    };
+    let main = Value::new(Data::Closure(Rc::new(Closure {
+        function: program.main,
+        captures: Vec::new(),
+    })));
+    vm.stack.push(main);
+    vm.call(0)?;
+    vm.execute()?;
+    vm.out.flush()?;
+    Ok(())
+}
+
+struct Vm<'p, W> {
+    program: &'p Program,
+    out: W,
+    stack: Vec<Value>,
+    frames: Vec<Frame>,
+}
+
+impl<'p, W: io::Write> Vm<'p, W> {
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("stack underflow")
+    }
+    /// Print a message and stop the program
+    fn panic<T>(&mut self, message: impl fmt::Display) -> VmResult<T> {
+        writeln!(self.out, "{}", message)?;
+        self.out.flush()?;
+        Err(VmError::Panic)
+    }
+    fn execute(&mut self) -> VmResult {
+        while let Some(frame) = self.frames.last_mut() {
+            let function = &self.program.functions[frame.function as usize];
+            let op = function.code[frame.ip];
+            frame.ip += 1;
+            let base = frame.base;
+            match op {
+                Op::Const(i) => {
+                    let data = match &self.program.constants[i as usize] {
+                        Constant::Int(i) => Data::Int(*i),
+                        Constant::Real(r) => Data::Real(*r),
+                        Constant::String(s) => Data::String(s.as_str().into()),
+                    };
+                    self.stack.push(Value::new(data));
+                }
+                Op::Nil => self.stack.push(NIL),
+                Op::True => self.stack.push(Value::new(Data::Bool(true))),
+                Op::False => self.stack.push(Value::new(Data::Bool(false))),
+                Op::Builtin(i) => self.stack.push(Value::new(Data::Builtin(i))),
+                Op::Local(i) => {
+                    let value = self.stack[base + i as usize].clone();
+                    self.stack.push(value);
+                }
+                Op::SetLocal(i) => {
+                    let value = self.pop();
+                    self.stack[base + i as usize] = value;
+                }
+                Op::Capture(i) => {
+                    let frame = self.frames.last().unwrap();
+                    let value = match &frame.callee.data {
+                        Data::Closure(closure) => closure.captures[i as usize].clone(),
+                        _ => unreachable!("only closures run in frames"),
+                    };
+                    self.stack.push(value);
+                }
+                Op::Current => {
+                    let callee = self.frames.last().unwrap().callee.clone();
+                    self.stack.push(callee);
+                }
+                Op::Closure(i) => {
+                    let frame = self.frames.last().unwrap();
+                    let captures = self.program.functions[i as usize]
+                        .captures
+                        .iter()
+                        .map(|from| match *from {
+                            CaptureFrom::Local(slot) => self.stack[base + slot as usize].clone(),
+                            CaptureFrom::Current => frame.callee.clone(),
+                            CaptureFrom::Capture(i) => match &frame.callee.data {
+                                Data::Closure(closure) => closure.captures[i as usize].clone(),
+                                _ => unreachable!("only closures run in frames"),
+                            },
+                        })
+                        .collect();
+                    let closure = Closure {
+                        function: i,
+                        captures,
+                    };
+                    self.stack.push(Value::new(Data::Closure(Rc::new(closure))));
+                }
+                Op::Call(count) => self.call(count)?,
+                Op::Pop => {
+                    self.pop();
+                }
+                Op::JumpIfFalsy(target) | Op::JumpIfTruthy(target) => {
+                    let jump_if = matches!(op, Op::JumpIfTruthy(_));
+                    if self.stack.last().unwrap().is_true() == jump_if {
+                        self.frames.last_mut().unwrap().ip = target as usize;
+                    } else {
+                        self.pop();
+                    }
+                }
+                Op::Binary(op) => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    let value = self.binary(op, left, right)?;
+                    self.stack.push(value);
+                }
+                Op::Neg => {
+                    let value = self.pop();
+                    let data = match value.data {
+                        Data::Int(i) => Data::Int(i.wrapping_neg()),
+                        Data::Real(r) => Data::Real(-r),
+                        _ => {
+                            return self.panic(format!("Attempted to negate {}", value.type_name()))
+                        }
+                    };
+                    self.stack.push(Value::new(data));
+                }
+                Op::Head => {
+                    let value = self.pop();
+                    self.stack.push(Value::new(value.data));
+                }
+                Op::Tree => return self.panic("Trees are not supported by the VM"),
+                Op::CheckType(types, expected, name) => {
+                    let value = self.stack.last().unwrap();
+                    if types & (1 << value.type_tag()) == 0 {
+                        let constant = |i: u32| match &self.program.constants[i as usize] {
+                            Constant::String(s) => s.as_str(),
+                            _ => "",
+                        };
+                        let message = format!(
+                            "Expected {} to be {}, but it is {}",
+                            constant(name),
+                            constant(expected),
+                            value.type_name()
+                        );
+                        return self.panic(message);
+                    }
+                }
+                Op::Return => {
+                    let value = self.pop();
+                    self.stack.truncate(base - 1);
+                    self.stack.push(value);
+                    self.frames.pop();
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Call the callee below the top `count` values of the stack
+    ///
+    /// Builtins finish right away. Closures push a frame that `execute` runs.
+    fn call(&mut self, count: u32) -> VmResult {
+        let base = self.stack.len() - count as usize;
+        let callee = self.stack[base - 1].clone();
+        match &callee.data {
+            Data::Builtin(i) => {
+                let args: Vec<Value> = self.stack.drain(base..).collect();
+                self.stack.pop();
+                let value = self.builtin(*i, args)?;
+                self.stack.push(value);
+            }
+            Data::Closure(closure) => {
+                // Missing arguments are nil and extra arguments are dropped
+                let function = &self.program.functions[closure.function as usize];
+                if base + function.locals as usize > MAX_STACK {
+                    return self.panic("Stack overflow");
+                }
+                self.stack
+                    .truncate(base + (count.min(function.params) as usize));
+                self.stack.resize(base + function.locals as usize, NIL);
+                self.frames.push(Frame {
+                    function: closure.function,
+                    ip: 0,
+                    base,
+                    callee,
+                });
+            }
+            _ => {
+                let message = format!("Attempted to call {} value", callee.type_name());
+                return self.panic(message);
+            }
+        }
+        Ok(())
+    }
+    fn builtin(&mut self, i: u32, args: Vec<Value>) -> VmResult<Value> {
+        let mut args = args.into_iter();
+        let mut arg = || args.next().unwrap_or(NIL);
+        let name = BUILTIN_FUNCTIONS[i as usize].0;
+        let op = match name {
+            "mom" | "dad" => {
+                let value = arg();
+                let parent = if name == "mom" { value.mom } else { value.dad };
+                return Ok(parent.map_or(NIL, |parent| (*parent).clone()));
+            }
+            "print" | "println" => {
+                let value = arg();
+                write!(self.out, "{}", value)?;
+                if name == "println" {
+                    writeln!(self.out)?;
+                }
+                return Ok(value);
+            }
+            "error" => return Ok(Value::new(Data::Error(Rc::new(arg())))),
+            "panic" => {
+                let value = arg();
+                return self.panic(format!("\nKin panicked:\n{}\n", value));
+            }
+            "not" => {
+                let value = arg();
+                let not = match value.data {
+                    Data::Bool(b) => !b,
+                    Data::Nil => true,
+                    _ => false,
+                };
+                return Ok(Value::new(Data::Bool(not)));
+            }
+            "assert" => {
+                let value = arg();
+                if !value.is_true() {
+                    let message = match args.next() {
+                        Some(message) => message,
+                        None => value,
+                    };
+                    return self.panic(format!("\nKin panicked:\n{}\n", message));
+                }
+                return Ok(value);
+            }
+            "add" => BinOp::Add,
+            "sub" => BinOp::Sub,
+            "mul" => BinOp::Mul,
+            "div" => BinOp::Div,
+            "rem" => BinOp::Rem,
+            "eq" => BinOp::Equals,
+            "ne" => BinOp::NotEquals,
+            "lt" => BinOp::Less,
+            "le" => BinOp::LessOrEqual,
+            "gt" => BinOp::Greater,
+            "ge" => BinOp::GreaterOrEqual,
+            _ => unreachable!("unknown builtin {}", name),
+        };
+        let left = arg();
+        let right = arg();
+        self.binary(op, left, right)
+    }
+    fn binary(&mut self, op: BinOp, left: Value, right: Value) -> VmResult<Value> {
+        let data = match op {
+            BinOp::Or | BinOp::And => unreachable!("`and` and `or` are compiled to jumps"),
+            BinOp::Mom => {
+                return Ok(Value {
+                    mom: Some(Rc::new(right)),
+                    ..left
+                })
+            }
+            BinOp::Dad => {
+                return Ok(Value {
+                    dad: Some(Rc::new(left)),
+                    ..right
+                })
+            }
+            BinOp::Equals => Data::Bool(equal(&left, &right)),
+            BinOp::NotEquals => Data::Bool(!equal(&left, &right)),
+            BinOp::Less | BinOp::LessOrEqual | BinOp::Greater | BinOp::GreaterOrEqual => {
+                let ordering = match compare(&left, &right) {
+                    Some(ordering) => ordering,
+                    None => return self.binary_panic("compare", &left, &right),
+                };
+                Data::Bool(ordering.is_some_and(|ordering| match op {
+                    BinOp::Less => ordering == Ordering::Less,
+                    BinOp::LessOrEqual => ordering != Ordering::Greater,
+                    BinOp::Greater => ordering == Ordering::Greater,
+                    _ => ordering != Ordering::Less,
+                }))
+            }
+            BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Rem => {
+                match (&left.data, &right.data) {
+                    (Data::Int(a), Data::Int(b)) => {
+                        let (a, b) = (*a, *b);
+                        if b == 0 && matches!(op, BinOp::Div | BinOp::Rem) {
+                            return self.panic("Attempted to divide by zero");
+                        }
+                        Data::Int(match op {
+                            BinOp::Add => a.wrapping_add(b),
+                            BinOp::Sub => a.wrapping_sub(b),
+                            BinOp::Mul => a.wrapping_mul(b),
+                            BinOp::Div => a.wrapping_div(b),
+                            _ => a.wrapping_rem(b),
+                        })
+                    }
+                    _ => match (left.as_real(), right.as_real()) {
+                        (Some(a), Some(b)) => Data::Real(match op {
+                            BinOp::Add => a + b,
+                            BinOp::Sub => a - b,
+                            BinOp::Mul => a * b,
+                            BinOp::Div => a / b,
+                            _ => a % b,
+                        }),
+                        _ => {
+                            let verb = match op {
+                                BinOp::Add => "add",
+                                BinOp::Sub => "subtract",
+                                BinOp::Mul => "multiply",
+                                _ => "divide",
+                            };
+                            return self.binary_panic(verb, &left, &right);
+                        }
+                    },
+                }
+            }
+        };
+        Ok(Value::new(data))
+    }
+    fn binary_panic<T>(&mut self, verb: &str, left: &Value, right: &Value) -> VmResult<T> {
+        let message = format!(
+            "Attempted to {} incompatible types {} and {}",
+            verb,
+            left.type_name(),
+            right.type_name()
+        );
+        self.panic(message)
+    }
+}
+
+fn equal(a: &Value, b: &Value) -> bool {
+    match (&a.data, &b.data) {
+        (Data::Nil, Data::Nil) => true,
+        (Data::Bool(a), Data::Bool(b)) => a == b,
+        (Data::Int(a), Data::Int(b)) => a == b,
+        (Data::String(a), Data::String(b)) => a == b,
+        (Data::Builtin(a), Data::Builtin(b)) => a == b,
+        // Like the C runtime, closures are equal if they run the same code
+        (Data::Closure(a), Data::Closure(b)) => a.function == b.function,
+        (Data::Error(a), Data::Error(b)) => equal(a, b),
+        _ => match (a.as_real(), b.as_real()) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        },
+    }
+}
+
+/// Order two values, if they can be compared
+///
+/// Reals may be unordered, in which case every comparison is false, like in C.
+fn compare(a: &Value, b: &Value) -> Option<Option<Ordering>> {
+    match (&a.data, &b.data) {
+        (Data::Bool(a), Data::Bool(b)) => Some(Some(a.cmp(b))),
+        (Data::Int(a), Data::Int(b)) => Some(Some(a.cmp(b))),
+        (Data::String(a), Data::String(b)) => Some(Some(a.as_bytes().cmp(b.as_bytes()))),
+        (Data::Builtin(a), Data::Builtin(b)) => Some(Some(a.cmp(b))),
+        (Data::Closure(a), Data::Closure(b)) => Some(Some(a.function.cmp(&b.function))),
+        (Data::Error(a), Data::Error(b)) => compare(a, b),
+        _ => match (a.as_real(), b.as_real()) {
+            (Some(a), Some(b)) => Some(a.partial_cmp(&b)),
+            _ => None,
+        },
+    }
+}
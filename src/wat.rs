@@ -0,0 +1,577 @@
+use std::{collections::HashMap, fmt::Write};
+
+use crate::{
+    ast::*,
+    transpile::{TranspileOptions, BUILTIN_FUNCTIONS},
+};
+
+/// The runtime pasted into every WebAssembly module
+pub const WAT_RUNTIME: &str = include_str!("../clibs/kin.wat");
+
+/// Where the program's data starts. The runtime's data is below this.
+const DATA_START: usize = 2048;
+
+/// Transpile checked items to a WebAssembly text module
+///
+/// The module imports `write` and `write_real` from `kin` to print, and exports `main`
+/// and its `memory`. Panics print their message and trap.
+pub fn transpile_wat(items: Items, options: TranspileOptions) -> String {
+    let mut wat = Wat {
+        frames: vec![Frame::new("$main".into())],
+        functions: Vec::new(),
+        table: BUILTIN_FUNCTIONS
+            .iter()
+            .map(|(name, _)| format!("$kin_builtin_{}", name))
+            .collect(),
+        data: Vec::new(),
+        names: HashMap::new(),
+        type_checks: !options.no_type_checks,
+    };
+    let body = wat.items(items);
+    let main = wat.frames.pop().unwrap();
+    let mut module = String::from("(module\n");
+    module.push_str(WAT_RUNTIME);
+    let heap = (DATA_START + wat.data.len()).next_multiple_of(8);
+    writeln!(
+        module,
+        "\n  (global $kin_heap (mut i32) (i32.const {}))",
+        heap
+    )
+    .unwrap();
+    writeln!(module, "  (table {} funcref)", wat.table.len()).unwrap();
+    writeln!(
+        module,
+        "  (elem (i32.const 0) func {})",
+        wat.table.join(" ")
+    )
+    .unwrap();
+    if !wat.data.is_empty() {
+        writeln!(
+            module,
+            "  (data (i32.const {}) \"{}\")",
+            DATA_START,
+            escape(&wat.data)
+        )
+        .unwrap();
+    }
+    for function in &wat.functions {
+        module.push('\n');
+        module.push_str(function);
+    }
+    module.push_str("\n  (func $main (export \"main\")\n");
+    main.write_locals(&mut module);
+    main.write_lines(&mut module, &body.lines);
+    writeln!(module, "    (drop {}))\n)", body.value).unwrap();
+    module
+}
+
+/// Escape bytes for a WebAssembly string
+fn escape(bytes: &[u8]) -> String {
+    let mut escaped = String::new();
+    for &b in bytes {
+        if b.is_ascii_graphic() && b != b'"' && b != b'\\' || b == b' ' {
+            escaped.push(b as char);
+        } else {
+            write!(escaped, "\\{:02x}", b).unwrap();
+        }
+    }
+    escaped
+}
+
+/// How a name is bound
+#[derive(Debug, Clone)]
+enum Binding {
+    /// A value in a local
+    Local(String),
+    /// A function that captures nothing
+    Function { func: String, index: usize },
+    /// The function of the frame the name is bound in
+    This { func: String, index: usize },
+}
+
+/// A WebAssembly function being built
+struct Frame<'a> {
+    func: String,
+    locals: Vec<String>,
+    scopes: Vec<HashMap<&'a str, Binding>>,
+    /// The names captured from enclosing frames, with the locals they are loaded into
+    captures: Vec<(&'a str, String)>,
+}
+
+impl<'a> Frame<'a> {
+    fn new(func: String) -> Self {
+        Frame {
+            func,
+            locals: Vec::new(),
+            scopes: vec![HashMap::new()],
+            captures: Vec::new(),
+        }
+    }
+    fn write_locals(&self, module: &mut String) {
+        for local in &self.locals {
+            writeln!(module, "    (local {} i32)", local).unwrap();
+        }
+    }
+    fn write_lines(&self, module: &mut String, lines: &[String]) {
+        for line in lines {
+            writeln!(module, "    {}", line).unwrap();
+        }
+    }
+}
+
+/// Instructions that run before an expression that gives a value
+#[derive(Default)]
+struct Block {
+    lines: Vec<String>,
+    value: String,
+}
+
+impl Block {
+    /// Make the block a single expression
+    fn into_expr(self) -> String {
+        if self.lines.is_empty() {
+            self.value
+        } else {
+            format!(
+                "(block (result i32) {} {})",
+                self.lines.join(" "),
+                self.value
+            )
+        }
+    }
+}
+
+struct Wat<'a> {
+    frames: Vec<Frame<'a>>,
+    /// Finished functions
+    functions: Vec<String>,
+    /// The functions in the table, in order
+    table: Vec<String>,
+    data: Vec<u8>,
+    /// How many times each name has been used for a function or local
+    names: HashMap<String, usize>,
+    type_checks: bool,
+}
+
+impl<'a> Wat<'a> {
+    /// Get a unique WebAssembly identifier for a name
+    fn unique(&mut self, prefix: &str, name: &str) -> String {
+        let name: String = name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let count = self.names.entry(name.clone()).or_insert(0);
+        *count += 1;
+        format!("${}_{}_{}", prefix, name, count)
+    }
+    fn frame(&mut self) -> &mut Frame<'a> {
+        self.frames.last_mut().unwrap()
+    }
+    /// Declare a local in the current frame
+    fn local(&mut self, name: &str) -> String {
+        let local = self.unique("v", name);
+        self.frame().locals.push(local.clone());
+        local
+    }
+    fn bind(&mut self, name: &'a str, binding: Binding) {
+        self.frame()
+            .scopes
+            .last_mut()
+            .unwrap()
+            .insert(name, binding);
+    }
+    /// Put bytes in the data segment, returning their address
+    fn string(&mut self, bytes: &[u8]) -> (usize, usize) {
+        let ptr = DATA_START + self.data.len();
+        self.data.extend_from_slice(bytes);
+        (ptr, bytes.len())
+    }
+    /// Find a name from a frame, capturing it from enclosing frames if needed
+    fn resolve(&mut self, frame: usize, name: &'a str) -> Option<Binding> {
+        let found = self.frames[frame]
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).cloned());
+        if found.is_some() || frame == 0 {
+            return found;
+        }
+        match self.resolve(frame - 1, name)? {
+            // Functions that capture nothing can be used from anywhere
+            binding @ Binding::Function { .. } => Some(binding),
+            _ => {
+                let local = self.unique("v", name);
+                let frame = &mut self.frames[frame];
+                frame.locals.push(local.clone());
+                frame.captures.push((name, local.clone()));
+                let binding = Binding::Local(local);
+                frame.scopes[0].insert(name, binding.clone());
+                Some(binding)
+            }
+        }
+    }
+    /// The value of a binding in the frame it was resolved in
+    fn binding_value(binding: &Binding) -> String {
+        match binding {
+            Binding::Local(local) => format!("(local.get {})", local),
+            Binding::Function { index, .. } => {
+                format!("(call $kin_new_function (i32.const {}))", index)
+            }
+            Binding::This { index, .. } => format!(
+                "(call $kin_new_closure (i32.const {}) (local.get $captures))",
+                index
+            ),
+        }
+    }
+    fn check_type(&mut self, lines: &mut Vec<String>, local: &str, ty: Type, name: &str) {
+        if self.type_checks {
+            let (expected, expected_len) = self.string(ty.to_string().as_bytes());
+            let (name, name_len) = self.string(name.as_bytes());
+            lines.push(format!(
+                "(call $kin_check_type (local.get {}) (i32.const {}) (i32.const {}) (i32.const {}) (i32.const {}) (i32.const {}))",
+                local,
+                ty.bits(),
+                expected,
+                expected_len,
+                name,
+                name_len
+            ));
+        }
+    }
+    fn items(&mut self, items: Items<'a>) -> Block {
+        let mut block = Block::default();
+        let mut value = None;
+        for item in items {
+            if let Some(value) = value.take() {
+                block.lines.push(format!("(drop {})", value));
+            }
+            match item {
+                Item::Def(def) => self.def(def, &mut block.lines),
+                Item::Extern(ext) => self.def(ext.stub("WebAssembly"), &mut block.lines),
+                Item::Node(node) => value = Some(self.node(node)),
+            }
+        }
+        block.value = value.unwrap_or_else(|| "(global.get $kin_nil)".into());
+        block
+    }
+    fn def(&mut self, def: Def<'a>, lines: &mut Vec<String>) {
+        let name = def.ident.name;
+        if def.is_function() {
+            let description = format!("`{}`", name);
+            let (func, index, captures) = self.function(
+                name,
+                Some(name),
+                def.params,
+                def.ty,
+                def.items,
+                &description,
+            );
+            if captures.is_empty() {
+                self.bind(name, Binding::Function { func, index });
+            } else {
+                let closure = self.closure(index, captures);
+                let local = self.local(name);
+                lines.push(format!("(local.set {} {})", local, closure));
+                self.bind(name, Binding::Local(local));
+            }
+        } else {
+            // Defs in the body are local to it
+            self.frame().scopes.push(HashMap::new());
+            let value = self.items(def.items).into_expr();
+            self.frame().scopes.pop();
+            let local = self.local(name);
+            lines.push(format!("(local.set {} {})", local, value));
+            if let Some(ty) = def.ty {
+                self.check_type(lines, &local, ty, &format!("`{}`", name));
+            }
+            self.bind(name, Binding::Local(local));
+        }
+    }
+    /// Build a function in its own frame, returning its name, its table index, and the
+    /// names it captures from the current frame
+    fn function(
+        &mut self,
+        name: &str,
+        this: Option<&'a str>,
+        params: Params<'a>,
+        ret: Option<Type>,
+        items: Items<'a>,
+        description: &str,
+    ) -> (String, usize, Vec<&'a str>) {
+        let func = self.unique("k", name);
+        let index = self.table.len();
+        self.table.push(func.clone());
+        self.frames.push(Frame::new(func.clone()));
+        if let Some(this) = this {
+            let binding = Binding::This {
+                func: func.clone(),
+                index,
+            };
+            self.bind(this, binding);
+        }
+        let mut lines = Vec::new();
+        for (i, param) in params.iter().enumerate() {
+            let local = self.local(param.ident.name);
+            lines.push(format!(
+                "(local.set {} (call $kin_arg (local.get $args) (local.get $count) (i32.const {})))",
+                local, i
+            ));
+            if let Some(ty) = param.ty {
+                self.check_type(&mut lines, &local, ty, &param.describe(description));
+            }
+            self.bind(param.ident.name, Binding::Local(local));
+        }
+        let body = self.items(items);
+        lines.extend(body.lines);
+        let mut value = body.value;
+        if let Some(ty) = ret {
+            let local = self.local("ret");
+            lines.push(format!("(local.set {} {})", local, value));
+            let name = format!("the return value of {}", description);
+            self.check_type(&mut lines, &local, ty, &name);
+            value = format!("(local.get {})", local);
+        }
+        let frame = self.frames.pop().unwrap();
+        let mut text = format!(
+            "  (func {} (type $kin_fn) (param $args i32) (param $count i32) (param $captures i32) (result i32)\n",
+            frame.func
+        );
+        frame.write_locals(&mut text);
+        let loads: Vec<String> = frame
+            .captures
+            .iter()
+            .enumerate()
+            .map(|(i, (_, local))| {
+                format!(
+                    "(local.set {} (i32.load offset={} (local.get $captures)))",
+                    local,
+                    i * 4
+                )
+            })
+            .collect();
+        frame.write_lines(&mut text, &loads);
+        frame.write_lines(&mut text, &lines);
+        writeln!(text, "    {})", value).unwrap();
+        self.functions.push(text);
+        let captures = frame.captures.into_iter().map(|(name, _)| name).collect();
+        (func, index, captures)
+    }
+    /// Build a closure value from the current frame
+    fn closure(&mut self, index: usize, captures: Vec<&'a str>) -> String {
+        if captures.is_empty() {
+            return format!("(call $kin_new_function (i32.const {}))", index);
+        }
+        let local = self.local("captures");
+        let mut expr = format!(
+            "(block (result i32) (local.set {} (call $kin_alloc (i32.const {})))",
+            local,
+            captures.len() * 4
+        );
+        let frame = self.frames.len() - 1;
+        for (i, name) in captures.into_iter().enumerate() {
+            let binding = self.resolve(frame, name).expect("captured name is bound");
+            write!(
+                expr,
+                " (i32.store offset={} (local.get {}) {})",
+                i * 4,
+                local,
+                Self::binding_value(&binding)
+            )
+            .unwrap();
+        }
+        write!(
+            expr,
+            " (call $kin_new_closure (i32.const {}) (local.get {})))",
+            index, local
+        )
+        .unwrap();
+        expr
+    }
+    fn node(&mut self, node: Node<'a>) -> String {
+        match node.kind {
+            NodeKind::Term(term, _) => self.term(term),
+            NodeKind::BinExpr(expr) => self.bin_expr(expr),
+            NodeKind::UnExpr(expr) => {
+                let inner = self.node(*expr.inner);
+                match expr.op {
+                    UnOp::Neg => format!("(call $kin_neg {})", inner),
+                    UnOp::Head => format!("(call $kin_head {})", inner),
+                }
+            }
+            NodeKind::Call(expr) => self.call_expr(expr),
+        }
+    }
+    fn term(&mut self, term: Term<'a>) -> String {
+        match term {
+            Term::Int(i) => format!("(call $kin_new_int (i64.const {}))", i),
+            Term::Real(r) => format!("(call $kin_new_real (f64.const {:?}))", r),
+            Term::String(s) => {
+                let (ptr, len) = self.string(s.as_bytes());
+                format!(
+                    "(call $kin_new_string (i32.const {}) (i32.const {}))",
+                    ptr, len
+                )
+            }
+            Term::Ident(ident) => {
+                let frame = self.frames.len() - 1;
+                if let Some(binding) = self.resolve(frame, ident.name) {
+                    return Self::binding_value(&binding);
+                }
+                match ident.name {
+                    "_" | "nil" => "(global.get $kin_nil)".into(),
+                    "true" => "(global.get $kin_true)".into(),
+                    "false" => "(global.get $kin_false)".into(),
+                    name => {
+                        let index = BUILTIN_FUNCTIONS
+                            .iter()
+                            .position(|(n, _)| *n == name)
+                            .expect("unknown names are rejected when parsing");
+                        format!("(call $kin_new_function (i32.const {}))", index)
+                    }
+                }
+            }
+            Term::Expr(items) => {
+                self.frame().scopes.push(HashMap::new());
+                let block = self.items(items);
+                self.frame().scopes.pop();
+                block.into_expr()
+            }
+            Term::Tree(nodes) => {
+                let [left, middle, right] = *nodes;
+                format!(
+                    "(call $kin_tree {} {} {})",
+                    self.node(left),
+                    self.node(middle),
+                    self.node(right)
+                )
+            }
+            Term::Closure(closure) => {
+                let (_, index, captures) = self.function(
+                    "closure",
+                    None,
+                    closure.params,
+                    None,
+                    closure.body,
+                    "a closure",
+                );
+                self.closure(index, captures)
+            }
+        }
+    }
+    fn bin_expr(&mut self, expr: BinExpr<'a>) -> String {
+        let ints = expr.left.ty == Type::INT && expr.right.ty == Type::INT;
+        let left = self.node(*expr.left);
+        if let BinOp::Or | BinOp::And = expr.op {
+            let temp = self.local("temp");
+            let right = self.node(*expr.right);
+            let (then, otherwise) = if expr.op == BinOp::Or {
+                (format!("(local.get {})", temp), right)
+            } else {
+                (right, format!("(local.get {})", temp))
+            };
+            return format!(
+                "(if (result i32) (call $kin_is_true (local.tee {} {})) (then {}) (else {}))",
+                temp, left, then, otherwise
+            );
+        }
+        let right = self.node(*expr.right);
+        // Ints are stored at the same offset in every cell
+        if ints {
+            if let Some((op, constructor)) = int_op(expr.op) {
+                return format!(
+                    "(call {} (i64.{} (i64.load offset=8 {}) (i64.load offset=8 {})))",
+                    constructor, op, left, right
+                );
+            }
+        }
+        let f = match expr.op {
+            BinOp::Or | BinOp::And => unreachable!(),
+            BinOp::Equals => "$kin_eq",
+            BinOp::NotEquals => "$kin_neq",
+            BinOp::Less => "$kin_lt",
+            BinOp::LessOrEqual => "$kin_le",
+            BinOp::Greater => "$kin_gt",
+            BinOp::GreaterOrEqual => "$kin_ge",
+            BinOp::Add => "$kin_add",
+            BinOp::Sub => "$kin_sub",
+            BinOp::Mul => "$kin_mul",
+            BinOp::Div => "$kin_div",
+            BinOp::Rem => "$kin_rem",
+            BinOp::Mom => "$kin_with_mom",
+            BinOp::Dad => "$kin_with_dad",
+        };
+        format!("(call {} {} {})", f, left, right)
+    }
+    fn call_expr(&mut self, expr: CallExpr<'a>) -> String {
+        let frame = self.frames.len() - 1;
+        // Functions known by name are called directly
+        let direct = match &expr.caller.kind {
+            NodeKind::Term(Term::Ident(ident), _) => match self.resolve(frame, ident.name) {
+                Some(Binding::Function { func, .. }) => Some((func, "(i32.const 0)")),
+                Some(Binding::This { func, .. }) => Some((func, "(local.get $captures)")),
+                Some(Binding::Local(_)) => None,
+                None => BUILTIN_FUNCTIONS
+                    .iter()
+                    .find(|(name, _)| *name == ident.name)
+                    .map(|(name, _)| (format!("$kin_builtin_{}", name), "(i32.const 0)")),
+            },
+            _ => None,
+        };
+        let mut lines = Vec::new();
+        let callee = if direct.is_none() {
+            let callee = self.local("callee");
+            let caller = self.node(*expr.caller);
+            lines.push(format!("(local.set {} {})", callee, caller));
+            Some(callee)
+        } else {
+            None
+        };
+        let (args, count) = if expr.args.is_empty() {
+            ("(i32.const 0)".to_string(), 0)
+        } else {
+            let count = expr.args.len();
+            let args = self.local("args");
+            lines.push(format!(
+                "(local.set {} (call $kin_alloc (i32.const {})))",
+                args,
+                count * 4
+            ));
+            for (i, arg) in expr.args.into_iter().enumerate() {
+                let arg = self.node(arg);
+                lines.push(format!(
+                    "(i32.store offset={} (local.get {}) {})",
+                    i * 4,
+                    args,
+                    arg
+                ));
+            }
+            (format!("(local.get {})", args), count)
+        };
+        let value = match (direct, callee) {
+            (Some((func, captures)), _) => format!(
+                "(call {} {} (i32.const {}) {})",
+                func, args, count, captures
+            ),
+            (None, Some(callee)) => format!(
+                "(call $kin_call (local.get {}) {} (i32.const {}))",
+                callee, args, count
+            ),
+            (None, None) => unreachable!(),
+        };
+        Block { lines, value }.into_expr()
+    }
+}
+
+/// Get the WebAssembly operator and the value constructor for an operation on two ints
+fn int_op(op: BinOp) -> Option<(&'static str, &'static str)> {
+    Some(match op {
+        BinOp::Add => ("add", "$kin_new_int"),
+        BinOp::Sub => ("sub", "$kin_new_int"),
+        BinOp::Mul => ("mul", "$kin_new_int"),
+        BinOp::Less => ("lt_s", "$kin_new_bool"),
+        BinOp::LessOrEqual => ("le_s", "$kin_new_bool"),
+        BinOp::Greater => ("gt_s", "$kin_new_bool"),
+        BinOp::GreaterOrEqual => ("ge_s", "$kin_new_bool"),
+        _ => return None,
+    })
+}
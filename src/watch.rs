@@ -0,0 +1,72 @@
+use std::{
+    collections::HashMap,
+    env,
+    ffi::OsString,
+    fs, io,
+    io::Write,
+    path::PathBuf,
+    process::{Child, Command},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use crate::manifest::Project;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(300);
+
+/// Rerun `kin run` with the given arguments each time a source file changes
+///
+/// Each run is a separate `kin` process, so errors and panics only end that run.
+/// A run that is still going when a file changes is stopped.
+pub fn watch(project: &Project, run_args: Vec<OsString>) -> ! {
+    let exe = env::current_exe().unwrap_or_else(|_| "kin".into());
+    let mut stamps = HashMap::new();
+    let mut child: Option<Child> = None;
+    loop {
+        let new_stamps = modified_times(project);
+        if new_stamps != stamps {
+            stamps = new_stamps;
+            if let Some(mut child) = child.take() {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+            // Clear the screen and move the cursor to the top left
+            print!("\x1b[2J\x1b[H");
+            let _ = io::stdout().flush();
+            match Command::new(&exe).args(&run_args).spawn() {
+                Ok(spawned) => child = Some(spawned),
+                Err(e) => println!("Unable to start {}: {}", exe.display(), e),
+            }
+        }
+        if let Some(Ok(Some(_))) = child.as_mut().map(Child::try_wait) {
+            child = None;
+            println!("\nWatching for changes...");
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// The modification times of the project's source files
+fn modified_times(project: &Project) -> HashMap<PathBuf, Option<SystemTime>> {
+    project
+        .source_files()
+        .into_iter()
+        .map(|path| {
+            let modified = fs::metadata(&path).and_then(|meta| meta.modified()).ok();
+            (path, modified)
+        })
+        .collect()
+}
+
+/// The arguments after the subcommand that `kin` was run with
+pub fn subcommand_args() -> Vec<OsString> {
+    let mut args = env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--runtime" {
+            args.next();
+        } else if !arg.to_string_lossy().starts_with('-') {
+            break;
+        }
+    }
+    args.collect()
+}
@@ -0,0 +1,70 @@
+//! The harness shared by the backend tests
+//!
+//! Each valid fixture in `tests/fixtures` is run with a backend, and its output must match
+//! the output the C backend recorded in `tests/snapshots/<name>.out`. Programs that panic
+//! leave out the call stack, so their output need only be a prefix of the recorded output.
+//! Fixtures without recorded output are skipped.
+
+use std::{fs, path::PathBuf};
+
+/// What a backend printed when it ran a fixture
+pub struct Run {
+    pub output: String,
+    /// Whether the program finished without panicking
+    pub finished: bool,
+    /// Anything else worth showing if the output does not match, such as stderr
+    pub details: String,
+}
+
+fn manifest_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+}
+
+/// Run each fixture with output with a backend, given its name and source
+pub fn check_fixtures(mut run: impl FnMut(&str, &str) -> Run) {
+    let fixtures_dir = manifest_dir().join("tests/fixtures");
+    let snapshots = manifest_dir().join("tests/snapshots");
+    let mut fixtures: Vec<PathBuf> = fs::read_dir(&fixtures_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "kin"))
+        .collect();
+    fixtures.sort();
+
+    let mut mismatches = Vec::new();
+    let mut run_count = 0;
+    for fixture in &fixtures {
+        let name = fixture.file_stem().unwrap().to_str().unwrap();
+        let expected = match fs::read_to_string(snapshots.join(format!("{}.out", name))) {
+            Ok(expected) => expected.replace("\r\n", "\n"),
+            Err(_) => continue,
+        };
+        let source = fs::read_to_string(fixture).unwrap();
+        let Run {
+            output,
+            finished,
+            details,
+        } = run(name, &source);
+        run_count += 1;
+        let output = output.replace("\r\n", "\n");
+        let matches = if expected.contains("\nexit status: ") {
+            !finished && expected.starts_with(&output)
+        } else {
+            finished && output == expected
+        };
+        if !matches {
+            mismatches.push(format!(
+                "{} does not match\n--- expected ---\n{}\n--- actual ---\n{}\n{}",
+                name, expected, output, details
+            ));
+        }
+    }
+    assert!(run_count > 0, "No fixtures with output found");
+    if !mismatches.is_empty() {
+        panic!(
+            "{} fixture(s) failed:\n\n{}",
+            mismatches.len(),
+            mismatches.join("\n\n")
+        );
+    }
+}
@@ -0,0 +1,436 @@
+//! Golden-file tests for the transpiler and its diagnostics
+//!
+//! Each `.kin` file in `tests/fixtures` is checked and transpiled with the `kin` library.
+//! Valid programs snapshot their generated C in `tests/snapshots/<name>.c` and,
+//! when a C compiler is available, their output in `tests/snapshots/<name>.out`.
+//! Invalid programs snapshot their rendered errors in `tests/snapshots/<name>.errors`.
+//!
+//! Set `KIN_UPDATE_SNAPSHOTS=1` to write the current results as the new snapshots.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{self, Command},
+};
+
+use kin::{CompileError, Compiler};
+
+fn manifest_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+}
+
+fn updating() -> bool {
+    env::var("KIN_UPDATE_SNAPSHOTS").is_ok_and(|var| !var.is_empty() && var != "0")
+}
+
+fn compiler_available(cc: &str) -> bool {
+    Command::new(cc)
+        .arg("-v")
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+fn c_compiler_available() -> bool {
+    ["gcc", "clang", "tcc"]
+        .iter()
+        .any(|cc| compiler_available(cc))
+}
+
+fn normalize(output: &[u8]) -> String {
+    String::from_utf8_lossy(output).replace("\r\n", "\n")
+}
+
+/// Compare a result to its snapshot, or update the snapshot
+///
+/// Returns a description of the mismatch if there is one
+fn check_snapshot(path: &Path, actual: &str) -> Option<String> {
+    if updating() {
+        fs::write(path, actual).unwrap();
+        return None;
+    }
+    match fs::read_to_string(path) {
+        Ok(expected) if expected.replace("\r\n", "\n") == actual => None,
+        Ok(expected) => Some(format!(
+            "{} does not match\n--- expected ---\n{}\n--- actual ---\n{}",
+            path.display(),
+            expected,
+            actual
+        )),
+        Err(_) => Some(format!(
+            "{} is missing. Run with KIN_UPDATE_SNAPSHOTS=1 to create it",
+            path.display()
+        )),
+    }
+}
+
+/// Run a single fixture in its own directory, returning any snapshot mismatches
+fn run_fixture(fixture: &Path, snapshots: &Path, run_programs: bool) -> Vec<String> {
+    let name = fixture.file_stem().unwrap().to_str().unwrap();
+    let dir = env::temp_dir().join(format!("kin-golden-{}-{}", process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    let compiler = Compiler::new().out_dir(&dir).output(dir.join(name));
+    let source = fs::read_to_string(fixture).unwrap();
+
+    let mut mismatches = Vec::new();
+    match compiler.c_source(&source) {
+        // Invalid programs snapshot their errors
+        Err(e @ CompileError::Check(_)) => {
+            mismatches.extend(check_snapshot(
+                &snapshots.join(format!("{}.errors", name)),
+                &format!("{}\n", e),
+            ));
+        }
+        Err(e) => mismatches.push(format!("{} failed to transpile: {}", name, e)),
+        // Valid programs snapshot their C and their output
+        Ok(c_source) => {
+            mismatches.extend(check_snapshot(
+                &snapshots.join(format!("{}.c", name)),
+                &c_source,
+            ));
+            if run_programs {
+                let built = compiler
+                    .check(&source)
+                    .and_then(|items| compiler.write_c(items))
+                    .and_then(|_| compiler.compile_c());
+                match built {
+                    Ok(executable) => {
+                        let run = Command::new(executable).current_dir(&dir).output().unwrap();
+                        let mut output = normalize(&run.stdout);
+                        if !run.status.success() {
+                            output.push_str(&format!("\nexit status: {:?}\n", run.status.code()));
+                        }
+                        mismatches.extend(check_snapshot(
+                            &snapshots.join(format!("{}.out", name)),
+                            &output,
+                        ));
+                    }
+                    Err(e) => mismatches.push(format!("{} failed to build: {}", name, e)),
+                }
+            }
+        }
+    }
+    let _ = fs::remove_dir_all(&dir);
+    mismatches
+}
+
+#[test]
+fn golden() {
+    let fixtures_dir = manifest_dir().join("tests/fixtures");
+    let snapshots = manifest_dir().join("tests/snapshots");
+    fs::create_dir_all(&snapshots).unwrap();
+    let run_programs = c_compiler_available();
+    if !run_programs {
+        println!("No C compiler found. Program output will not be checked.");
+    }
+
+    let mut fixtures: Vec<PathBuf> = fs::read_dir(&fixtures_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "kin"))
+        .collect();
+    fixtures.sort();
+    assert!(!fixtures.is_empty(), "No fixtures found");
+
+    let mismatches: Vec<String> = fixtures
+        .iter()
+        .flat_map(|fixture| run_fixture(fixture, &snapshots, run_programs))
+        .collect();
+    if !mismatches.is_empty() {
+        panic!(
+            "{} snapshot(s) failed:\n\n{}",
+            mismatches.len(),
+            mismatches.join("\n\n")
+        );
+    }
+}
+
+#[test]
+fn unchanged_builds_are_skipped() {
+    if !c_compiler_available() {
+        println!("No C compiler found. Build caching will not be checked.");
+        return;
+    }
+    let dir = env::temp_dir().join(format!("kin-incremental-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let compiler = Compiler::new().out_dir(&dir).output(dir.join("main"));
+    let build = |source: &str| {
+        let items = compiler.check(source).unwrap();
+        compiler.write_c(items).unwrap();
+        compiler.compile_c_if_changed().unwrap().1
+    };
+    assert!(build("println 1"), "the first build should compile");
+    assert!(!build("println 1"), "an unchanged build should be skipped");
+    assert!(build("println 2"), "a changed source should compile");
+    let debug = Compiler::new()
+        .out_dir(&dir)
+        .output(dir.join("main"))
+        .build_args(kin::compile::BuildArgs {
+            debug: true,
+            ..Default::default()
+        });
+    assert!(!debug.is_compiled(), "changed flags should compile");
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn unoptimized_builds_link() {
+    if !c_compiler_available() {
+        println!("No C compiler found. Unoptimized builds will not be checked.");
+        return;
+    }
+    let dir = env::temp_dir().join(format!("kin-unoptimized-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let compiler = Compiler::new()
+        .out_dir(&dir)
+        .output(dir.join("main"))
+        .build_args(kin::compile::BuildArgs {
+            opt_level: Some(kin::compile::OptLevel::O0),
+            ..Default::default()
+        });
+    let executable = compiler.executable("println (7 % 2)").unwrap();
+    let run = Command::new(executable).output().unwrap();
+    assert_eq!(normalize(&run.stdout), "1\n");
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn externs_call_c_sources() {
+    if !c_compiler_available() {
+        println!("No C compiler found. Externs will not be checked.");
+        return;
+    }
+    let dir = env::temp_dir().join(format!("kin-extern-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let native = dir.join("native.c");
+    fs::write(
+        &native,
+        "#include \"kin.h\"\n\
+         \n\
+         KinValue twice(uint32_t count, KinValue* args) {\n\
+         \x20   return args[1].type == Nil ? new_int(args[0].data.Int * 2) : KIN_NIL;\n\
+         }\n",
+    )
+    .unwrap();
+    let compiler = Compiler::new()
+        .out_dir(&dir)
+        .output(dir.join("main"))
+        .build_args(kin::compile::BuildArgs {
+            c_sources: vec![native],
+            ..Default::default()
+        });
+    let source = "extern twice 2\nprintln (twice 21)\nprintln (twice 1 2 3)\n";
+    let executable = compiler.executable(source).unwrap();
+    let run = Command::new(executable).output().unwrap();
+    assert!(run.status.success());
+    assert_eq!(normalize(&run.stdout), "42\nnil\n");
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn libraries_export_functions() {
+    if !compiler_available("gcc") {
+        println!("gcc not found. Libraries will not be checked.");
+        return;
+    }
+    let dir = env::temp_dir().join(format!("kin-lib-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let lib = |output: &str| {
+        Compiler::new()
+            .out_dir(&dir)
+            .output(dir.join(output))
+            .build_args(kin::compile::BuildArgs {
+                lib: true,
+                ..Default::default()
+            })
+    };
+
+    // Functions that capture top-level values cannot be exported
+    let captures = lib("captures");
+    match captures.executable("x = 1 : 2\nf a = x\n") {
+        Err(CompileError::Unexportable(names)) => assert_eq!(names, ["f"]),
+        result => panic!("expected an unexportable error, got {:?}", result),
+    }
+
+    // Exports would clash with the runtime
+    match lib("kin").executable("f a = a\n") {
+        Err(CompileError::InvalidLibraryName(name)) => assert_eq!(name, "kin"),
+        result => panic!("expected an invalid library name error, got {:?}", result),
+    }
+
+    // Only the last def of a name is exported, and hosts may pass any type
+    let compiler = lib("square");
+    let object = compiler
+        .executable("square x = x\nsquare x = x * x\nprintln (square 3)\n")
+        .unwrap();
+    // Internal functions are static, so libraries with the same defs link together
+    let other = lib("cube")
+        .executable("square x = x * x\ncube x = x * square x\n")
+        .unwrap();
+    let host = dir.join("host.c");
+    fs::write(
+        &host,
+        "#include \"square.h\"\n\
+         #include \"cube.h\"\n\
+         \n\
+         int main() {\n\
+         \x20   KinValue arg = kin_int(7);\n\
+         \x20   printf(\"%ld\\n\", kin_as_int(square_square(1, &arg)));\n\
+         \x20   arg = kin_real(1.5);\n\
+         \x20   printf(\"%g\\n\", kin_as_real(square_square(1, &arg)));\n\
+         \x20   arg = kin_int(2);\n\
+         \x20   printf(\"%ld\\n\", kin_as_int(cube_cube(1, &arg)));\n\
+         }\n",
+    )
+    .unwrap();
+    let executable = dir.join("host");
+    let host_build = Command::new("gcc")
+        .arg(&host)
+        .arg(&object)
+        .arg(&other)
+        .arg("-lm")
+        .arg("-o")
+        .arg(&executable)
+        .output()
+        .unwrap();
+    assert!(
+        host_build.status.success(),
+        "the host failed to compile:\n{}",
+        normalize(&host_build.stderr)
+    );
+    let run = Command::new(&executable).output().unwrap();
+    assert!(run.status.success());
+    assert_eq!(normalize(&run.stdout), "49\n2.25\n8\n");
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn tests_report_passes_and_failures() {
+    if !c_compiler_available() {
+        println!("No C compiler found. The test harness will not be checked.");
+        return;
+    }
+    let dir = env::temp_dir().join(format!("kin-harness-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let compiler = Compiler::new()
+        .out_dir(&dir)
+        .output(dir.join("tests"))
+        .tests(true);
+    let source = "\
+println \"not a test\"
+test_passes = assert (1 + 1 == 2)
+test_fails = assert (1 == 2) \"one is not two\"
+test_panics =
+    x = 1
+    panic \"oh no\"
+end
+test_uses_value = assert test_passes
+test_ｶﾞ = assert true
+id v = v
+test_adds = id \"a\" + id 1
+";
+    let executable = compiler.executable(source).unwrap();
+    let run = Command::new(executable).output().unwrap();
+    assert!(!run.status.success(), "failing tests should fail the run");
+    assert_eq!(
+        normalize(&run.stdout),
+        "\
+test test_passes ... ok
+test test_fails ... FAILED
+    one is not two
+    failed at main 3:14
+test test_panics ... FAILED
+    oh no
+    failed at main 6:5
+test test_uses_value ... ok
+test test_ｶﾞ ... ok
+test test_adds ... FAILED
+    Attempted to add incompatible types string and int
+    failed at main 11:20
+
+test result: FAILED. 3 passed; 3 failed
+"
+    );
+
+    // Tests are run without arguments
+    match compiler.check("test_takes_params x = x\n") {
+        Err(CompileError::Check(errors)) => assert_eq!(errors[0].code(), "K0015"),
+        result => panic!("expected a check error, got {:?}", result),
+    }
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn overlong_sources_are_rejected() {
+    let source = " ".repeat(kin::parse::MAX_SOURCE_LEN + 1);
+    match Compiler::new().check(&source) {
+        Err(CompileError::Check(errors)) => {
+            assert_eq!(errors[0].code(), "K0009");
+            assert!(errors[0]
+                .to_string()
+                .starts_with("error[K0009]: Source is 268435456 bytes long"));
+        }
+        result => panic!("expected a check error, got {:?}", result.map(|_| ())),
+    }
+}
+
+#[test]
+fn external_runtime_paths_are_c_strings() {
+    let runtime = kin::transpile::Runtime::External(PathBuf::from("dïr\\\"kin\".h"));
+    let c_source = Compiler::new()
+        .runtime(runtime)
+        .c_source("println 1")
+        .unwrap();
+    assert!(
+        c_source.starts_with("#include \"dïr\\\\\\\"kin\\\".h\"\n"),
+        "{}",
+        c_source
+    );
+}
+
+#[test]
+fn custom_compilers_may_have_args() {
+    if !compiler_available("gcc") || !compiler_available("env") {
+        println!("gcc or env not found. Custom compilers will not be checked.");
+        return;
+    }
+    let dir = env::temp_dir().join(format!("kin-custom-cc-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let compiler = Compiler::new()
+        .out_dir(&dir)
+        .output(dir.join("main"))
+        .build_args(kin::compile::BuildArgs {
+            compiler: Some(kin::compile::CCompiler::Custom("env gcc".into())),
+            ..Default::default()
+        });
+    let executable = compiler.executable("println 1").unwrap();
+    let run = Command::new(executable).output().unwrap();
+    assert_eq!(normalize(&run.stdout), "1\n");
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn new_projects_read_back() {
+    let dir = env::temp_dir().join(format!("kin-new-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let kin = env!("CARGO_BIN_EXE_kin");
+    let created = Command::new(kin)
+        .arg("new")
+        .arg("cafe\u{301}")
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    assert!(created.status.success());
+    let checked = Command::new(kin)
+        .arg("check")
+        .current_dir(dir.join("cafe\u{301}"))
+        .output()
+        .unwrap();
+    assert!(
+        checked.status.success(),
+        "the new project failed to check:\n{}",
+        normalize(&checked.stderr)
+    );
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,39 @@
+//! Tests for the JavaScript backend
+//!
+//! When Node is available, each valid fixture in `tests/fixtures` is transpiled to
+//! JavaScript and run, then compared with the output of the C backend.
+
+mod common;
+
+use std::{env, fs, process, process::Command};
+
+use common::Run;
+use kin::Compiler;
+
+fn node_available() -> bool {
+    Command::new("node")
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+#[test]
+fn fixtures_match_c_output() {
+    if !node_available() {
+        println!("Node not found. JavaScript output will not be checked.");
+        return;
+    }
+    let dir = env::temp_dir().join(format!("kin-js-{}", process::id()));
+    common::check_fixtures(|name, source| {
+        let compiler = Compiler::new().output(dir.join(name));
+        let items = compiler.check(source).unwrap();
+        let script = compiler.write_js(items).unwrap();
+        let run = Command::new("node").arg(&script).output().unwrap();
+        Run {
+            output: String::from_utf8_lossy(&run.stdout).into_owned(),
+            finished: run.status.success(),
+            details: String::from_utf8_lossy(&run.stderr).into_owned(),
+        }
+    });
+    let _ = fs::remove_dir_all(&dir);
+}
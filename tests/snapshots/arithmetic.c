@@ -0,0 +1,18 @@
+#include "kin.h"
+
+
+int main(int argc, char** argv) {
+    kin_call(new_function(&kin_println), 1, &new_int(3), "main 1:1");
+    kin_call(new_function(&kin_println), 1, &new_int(1), "main 2:1");
+    kin_call(new_function(&kin_println), 1, &new_int(6), "main 3:1");
+    kin_call(new_function(&kin_println), 1, &new_int(2), "main 4:1");
+    kin_call(new_function(&kin_println), 1, &new_real(2.4), "main 5:1");
+    kin_call(new_function(&kin_println), 1, &new_int(1), "main 6:1");
+    kin_call(new_function(&kin_println), 1, &new_int(1), "main 7:1");
+    kin_call(new_function(&kin_println), 1, &KIN_TRUE, "main 8:1");
+    kin_call(new_function(&kin_println), 1, &KIN_TRUE, "main 9:1");
+    KinValue arg = kin_call(new_function(&kin_not), 1, &KIN_TRUE, "main 10:10");
+    kin_call(new_function(&kin_println), 1, &arg, "main 10:1");
+    return 0;
+}
+
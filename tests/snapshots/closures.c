@@ -0,0 +1,50 @@
+#include "kin.h"
+
+KinValue k_anon(uint32_t count, KinValue* args);
+KinValue k_anon_2(uint32_t count, KinValue* args);
+KinValue k_apply(uint32_t count, KinValue* args);
+KinValue k_make_adder(uint32_t count, KinValue* args);
+KinValue k_make_adder_adder(uint32_t count, KinValue* args, KinValue* captures);
+
+KinValue k_anon(uint32_t count, KinValue* args) {
+    KinValue* k_anon_arg0 = 0 < count ? &args[0] : &KIN_NIL;
+    KinValue* k_anon_arg1 = 1 < count ? &args[1] : &KIN_NIL;
+    return kin_call_bin_op(kin_add, *k_anon_arg0, *k_anon_arg1, "closure 1:15");
+}
+
+KinValue k_anon_2(uint32_t count, KinValue* args) {
+    KinValue* k_anon_2_arg0 = 0 < count ? &args[0] : &KIN_NIL;
+    return ((*k_anon_2_arg0).type == Int ? new_int((*k_anon_2_arg0).data.Int * 2) : kin_call_bin_op(kin_mul, *k_anon_2_arg0, new_int(2), "closure 5:22"));
+}
+
+KinValue k_apply(uint32_t count, KinValue* args) {
+    KinValue* k_apply_arg0 = 0 < count ? &args[0] : &KIN_NIL;
+    KinValue* k_apply_arg1 = 1 < count ? &args[1] : &KIN_NIL;
+    return kin_call(*k_apply_arg0, 1, &*k_apply_arg1, "apply 4:13");
+}
+
+KinValue k_make_adder(uint32_t count, KinValue* args) {
+    KinValue* k_make_adder_arg0 = 0 < count ? &args[0] : &KIN_NIL;
+    KinValue k_make_adder_adder_captures[1];
+    k_make_adder_adder_captures[0] = *k_make_adder_arg0;
+    KinValue k_make_adder_adder_closure = new_closure(&k_make_adder_adder, k_make_adder_adder_captures);
+    KinValue arg = kin_call(k_make_adder_adder_closure, 1, &new_int(1), "make_adder 9:14");
+    return kin_call(new_function(&kin_println), 1, &arg, "make_adder 9:5");
+}
+
+KinValue k_make_adder_adder(uint32_t count, KinValue* args, KinValue* captures) {
+    KinValue* k_make_adder_adder_arg0 = 0 < count ? &args[0] : &KIN_NIL;
+    return new_int((*k_make_adder_adder_arg0).data.Int + captures[0].data.Int);
+}
+
+int main(int argc, char** argv) {
+    KinValue k_add = new_function(&k_anon);
+    KinValue arg = kin_call(k_add, 2, (KinValue[]) { new_int(1), new_int(2) }, "main 2:10");
+    kin_call(new_function(&kin_println), 1, &arg, "main 2:1");
+    KinValue arg_2 = new_function(&k_anon_2);
+    KinValue arg_3 = kin_call(new_function(&k_apply), 2, (KinValue[]) { arg_2, new_int(21) }, "main 5:10");
+    kin_call(new_function(&kin_println), 1, &arg_3, "main 5:1");
+    kin_call(new_function(&k_make_adder), 1, &new_int(5), "main 11:1");
+    return 0;
+}
+
@@ -0,0 +1,24 @@
+#include "kin.h"
+
+KinValue k_f(uint32_t count, KinValue* args);
+
+KinValue k_f(uint32_t count, KinValue* args) {
+    KinValue* k_f_arg0 = 0 < count ? &args[0] : &KIN_NIL;
+    return new_int((*k_f_arg0).data.Int + 5);
+}
+
+int main(int argc, char** argv) {
+    KinValue k_verbose = kin_call(new_function(&kin_not), 1, &KIN_FALSE, "main 2:11");
+    kin_call(new_function(&kin_println), 1, &new_int(11), "main 5:1");
+    kin_call(new_function(&kin_println), 1, &new_int(3), "main 6:1");
+    kin_call(new_function(&kin_println), 1, &new_real(3.5), "main 7:1");
+    kin_call(new_function(&kin_println), 1, &KIN_TRUE, "main 8:1");
+    kin_call(new_function(&kin_println), 1, &KIN_FALSE, "main 9:1");
+    kin_call(new_function(&kin_println), 1, &new_int(3), "main 10:1");
+    kin_call(new_function(&kin_println), 1, &new_real(2), "main 11:1");
+    KinValue arg = kin_call(new_function(&k_f), 1, &new_int(1), "main 14:10");
+    kin_call(new_function(&kin_println), 1, &arg, "main 14:1");
+    kin_call(new_function(&kin_println), 1, &new_string("hi", 2), "main 15:1");
+    return 0;
+}
+
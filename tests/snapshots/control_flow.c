@@ -0,0 +1,27 @@
+#include "kin.h"
+
+KinValue k_check(uint32_t count, KinValue* args);
+
+KinValue k_check(uint32_t count, KinValue* args) {
+    KinValue* k_check_arg0 = 0 < count ? &args[0] : &KIN_NIL;
+    KinValue temp = new_bool((*k_check_arg0).data.Int < 10);
+    if (temp.data.Bool) {
+        temp = kin_call(new_function(&kin_println), 1, &new_string("small", 5), "check 6:9");
+    }
+    KinValue temp_2 = temp;
+    if (!kin_is_true(temp_2)) {
+        temp_2 = kin_call(new_function(&kin_println), 1, &new_string("big", 3), "check 7:8");
+    }
+    return temp_2;
+}
+
+int main(int argc, char** argv) {
+    kin_call(new_function(&kin_println), 1, &new_int(3), "main 1:1");
+    kin_call(new_function(&kin_println), 1, &KIN_NIL, "main 2:1");
+    kin_call(new_function(&kin_println), 1, &new_int(2), "main 3:1");
+    kin_call(new_function(&kin_println), 1, &new_string("too big", 7), "main 4:1");
+    kin_call(new_function(&k_check), 1, &new_int(5), "main 8:1");
+    kin_call(new_function(&k_check), 1, &new_int(50), "main 9:1");
+    return 0;
+}
+
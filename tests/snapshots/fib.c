@@ -0,0 +1,25 @@
+#include "kin.h"
+
+KinValue k_fib(uint32_t count, KinValue* args);
+
+KinValue k_fib(uint32_t count, KinValue* args) {
+    KinValue* k_fib_arg0 = 0 < count ? &args[0] : &KIN_NIL;
+    KinValue temp = new_bool((*k_fib_arg0).data.Int < 2);
+    if (temp.data.Bool) {
+        temp = *k_fib_arg0;
+    }
+    KinValue temp_2 = temp;
+    if (!kin_is_true(temp_2)) {
+        KinValue arg = new_int((*k_fib_arg0).data.Int - 1);
+        KinValue arg_2 = new_int((*k_fib_arg0).data.Int - 2);
+        temp_2 = kin_call_bin_op(kin_add, kin_call(new_function(&k_fib), 1, &arg, "fib 1:24"), kin_call(new_function(&k_fib), 1, &arg_2, "fib 1:38"), "fib 1:36");
+    }
+    return temp_2;
+}
+
+int main(int argc, char** argv) {
+    KinValue arg = kin_call(new_function(&k_fib), 1, &new_int(20), "main 2:10");
+    kin_call(new_function(&kin_println), 1, &arg, "main 2:1");
+    return 0;
+}
+
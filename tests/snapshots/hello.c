@@ -0,0 +1,8 @@
+#include "kin.h"
+
+
+int main(int argc, char** argv) {
+    kin_call(new_function(&kin_println), 1, &new_string("Hello World!", 12), "main 1:1");
+    return 0;
+}
+
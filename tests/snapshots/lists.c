@@ -0,0 +1,23 @@
+#include "kin.h"
+
+
+int main(int argc, char** argv) {
+    KinValue head = new_int(2);
+    head.mom = &new_int(3);
+    KinValue head_2 = new_int(1);
+    head_2.mom = &head;
+    KinValue k_xs = head_2;
+    kin_call(new_function(&kin_println), 1, &k_xs, "main 2:1");
+    KinValue arg = kin_call(new_function(&kin_mom), 1, &k_xs, "main 3:10");
+    kin_call(new_function(&kin_println), 1, &arg, "main 3:1");
+    KinValue arg_2 = kin_call(new_function(&kin_mom), 1, &k_xs, "main 4:15");
+    KinValue arg_3 = kin_call(new_function(&kin_mom), 1, &arg_2, "main 4:10");
+    kin_call(new_function(&kin_println), 1, &arg_3, "main 4:1");
+    KinValue head_3 = new_int(0);
+    head_3.mom = &k_xs;
+    KinValue k_ys = head_3;
+    KinValue arg_4 = kin_call(new_function(&kin_mom), 1, &k_ys, "main 6:10");
+    kin_call(new_function(&kin_println), 1, &arg_4, "main 6:1");
+    return 0;
+}
+
@@ -0,0 +1,314 @@
+#include "kin.h"
+
+KinValue k_last(uint32_t count, KinValue* args);
+
+KinValue k_last(uint32_t count, KinValue* args) {
+    KinValue* k_last_arg0 = 0 < count ? &args[0] : &KIN_NIL;
+    KinValue* k_last_arg1 = 1 < count ? &args[1] : &KIN_NIL;
+    KinValue* k_last_arg2 = 2 < count ? &args[2] : &KIN_NIL;
+    KinValue* k_last_arg3 = 3 < count ? &args[3] : &KIN_NIL;
+    KinValue* k_last_arg4 = 4 < count ? &args[4] : &KIN_NIL;
+    KinValue* k_last_arg5 = 5 < count ? &args[5] : &KIN_NIL;
+    KinValue* k_last_arg6 = 6 < count ? &args[6] : &KIN_NIL;
+    KinValue* k_last_arg7 = 7 < count ? &args[7] : &KIN_NIL;
+    KinValue* k_last_arg8 = 8 < count ? &args[8] : &KIN_NIL;
+    KinValue* k_last_arg9 = 9 < count ? &args[9] : &KIN_NIL;
+    KinValue* k_last_arg10 = 10 < count ? &args[10] : &KIN_NIL;
+    KinValue* k_last_arg11 = 11 < count ? &args[11] : &KIN_NIL;
+    KinValue* k_last_arg12 = 12 < count ? &args[12] : &KIN_NIL;
+    KinValue* k_last_arg13 = 13 < count ? &args[13] : &KIN_NIL;
+    KinValue* k_last_arg14 = 14 < count ? &args[14] : &KIN_NIL;
+    KinValue* k_last_arg15 = 15 < count ? &args[15] : &KIN_NIL;
+    KinValue* k_last_arg16 = 16 < count ? &args[16] : &KIN_NIL;
+    KinValue* k_last_arg17 = 17 < count ? &args[17] : &KIN_NIL;
+    KinValue* k_last_arg18 = 18 < count ? &args[18] : &KIN_NIL;
+    KinValue* k_last_arg19 = 19 < count ? &args[19] : &KIN_NIL;
+    KinValue* k_last_arg20 = 20 < count ? &args[20] : &KIN_NIL;
+    KinValue* k_last_arg21 = 21 < count ? &args[21] : &KIN_NIL;
+    KinValue* k_last_arg22 = 22 < count ? &args[22] : &KIN_NIL;
+    KinValue* k_last_arg23 = 23 < count ? &args[23] : &KIN_NIL;
+    KinValue* k_last_arg24 = 24 < count ? &args[24] : &KIN_NIL;
+    KinValue* k_last_arg25 = 25 < count ? &args[25] : &KIN_NIL;
+    KinValue* k_last_arg26 = 26 < count ? &args[26] : &KIN_NIL;
+    KinValue* k_last_arg27 = 27 < count ? &args[27] : &KIN_NIL;
+    KinValue* k_last_arg28 = 28 < count ? &args[28] : &KIN_NIL;
+    KinValue* k_last_arg29 = 29 < count ? &args[29] : &KIN_NIL;
+    KinValue* k_last_arg30 = 30 < count ? &args[30] : &KIN_NIL;
+    KinValue* k_last_arg31 = 31 < count ? &args[31] : &KIN_NIL;
+    KinValue* k_last_arg32 = 32 < count ? &args[32] : &KIN_NIL;
+    KinValue* k_last_arg33 = 33 < count ? &args[33] : &KIN_NIL;
+    KinValue* k_last_arg34 = 34 < count ? &args[34] : &KIN_NIL;
+    KinValue* k_last_arg35 = 35 < count ? &args[35] : &KIN_NIL;
+    KinValue* k_last_arg36 = 36 < count ? &args[36] : &KIN_NIL;
+    KinValue* k_last_arg37 = 37 < count ? &args[37] : &KIN_NIL;
+    KinValue* k_last_arg38 = 38 < count ? &args[38] : &KIN_NIL;
+    KinValue* k_last_arg39 = 39 < count ? &args[39] : &KIN_NIL;
+    KinValue* k_last_arg40 = 40 < count ? &args[40] : &KIN_NIL;
+    KinValue* k_last_arg41 = 41 < count ? &args[41] : &KIN_NIL;
+    KinValue* k_last_arg42 = 42 < count ? &args[42] : &KIN_NIL;
+    KinValue* k_last_arg43 = 43 < count ? &args[43] : &KIN_NIL;
+    KinValue* k_last_arg44 = 44 < count ? &args[44] : &KIN_NIL;
+    KinValue* k_last_arg45 = 45 < count ? &args[45] : &KIN_NIL;
+    KinValue* k_last_arg46 = 46 < count ? &args[46] : &KIN_NIL;
+    KinValue* k_last_arg47 = 47 < count ? &args[47] : &KIN_NIL;
+    KinValue* k_last_arg48 = 48 < count ? &args[48] : &KIN_NIL;
+    KinValue* k_last_arg49 = 49 < count ? &args[49] : &KIN_NIL;
+    KinValue* k_last_arg50 = 50 < count ? &args[50] : &KIN_NIL;
+    KinValue* k_last_arg51 = 51 < count ? &args[51] : &KIN_NIL;
+    KinValue* k_last_arg52 = 52 < count ? &args[52] : &KIN_NIL;
+    KinValue* k_last_arg53 = 53 < count ? &args[53] : &KIN_NIL;
+    KinValue* k_last_arg54 = 54 < count ? &args[54] : &KIN_NIL;
+    KinValue* k_last_arg55 = 55 < count ? &args[55] : &KIN_NIL;
+    KinValue* k_last_arg56 = 56 < count ? &args[56] : &KIN_NIL;
+    KinValue* k_last_arg57 = 57 < count ? &args[57] : &KIN_NIL;
+    KinValue* k_last_arg58 = 58 < count ? &args[58] : &KIN_NIL;
+    KinValue* k_last_arg59 = 59 < count ? &args[59] : &KIN_NIL;
+    KinValue* k_last_arg60 = 60 < count ? &args[60] : &KIN_NIL;
+    KinValue* k_last_arg61 = 61 < count ? &args[61] : &KIN_NIL;
+    KinValue* k_last_arg62 = 62 < count ? &args[62] : &KIN_NIL;
+    KinValue* k_last_arg63 = 63 < count ? &args[63] : &KIN_NIL;
+    KinValue* k_last_arg64 = 64 < count ? &args[64] : &KIN_NIL;
+    KinValue* k_last_arg65 = 65 < count ? &args[65] : &KIN_NIL;
+    KinValue* k_last_arg66 = 66 < count ? &args[66] : &KIN_NIL;
+    KinValue* k_last_arg67 = 67 < count ? &args[67] : &KIN_NIL;
+    KinValue* k_last_arg68 = 68 < count ? &args[68] : &KIN_NIL;
+    KinValue* k_last_arg69 = 69 < count ? &args[69] : &KIN_NIL;
+    KinValue* k_last_arg70 = 70 < count ? &args[70] : &KIN_NIL;
+    KinValue* k_last_arg71 = 71 < count ? &args[71] : &KIN_NIL;
+    KinValue* k_last_arg72 = 72 < count ? &args[72] : &KIN_NIL;
+    KinValue* k_last_arg73 = 73 < count ? &args[73] : &KIN_NIL;
+    KinValue* k_last_arg74 = 74 < count ? &args[74] : &KIN_NIL;
+    KinValue* k_last_arg75 = 75 < count ? &args[75] : &KIN_NIL;
+    KinValue* k_last_arg76 = 76 < count ? &args[76] : &KIN_NIL;
+    KinValue* k_last_arg77 = 77 < count ? &args[77] : &KIN_NIL;
+    KinValue* k_last_arg78 = 78 < count ? &args[78] : &KIN_NIL;
+    KinValue* k_last_arg79 = 79 < count ? &args[79] : &KIN_NIL;
+    KinValue* k_last_arg80 = 80 < count ? &args[80] : &KIN_NIL;
+    KinValue* k_last_arg81 = 81 < count ? &args[81] : &KIN_NIL;
+    KinValue* k_last_arg82 = 82 < count ? &args[82] : &KIN_NIL;
+    KinValue* k_last_arg83 = 83 < count ? &args[83] : &KIN_NIL;
+    KinValue* k_last_arg84 = 84 < count ? &args[84] : &KIN_NIL;
+    KinValue* k_last_arg85 = 85 < count ? &args[85] : &KIN_NIL;
+    KinValue* k_last_arg86 = 86 < count ? &args[86] : &KIN_NIL;
+    KinValue* k_last_arg87 = 87 < count ? &args[87] : &KIN_NIL;
+    KinValue* k_last_arg88 = 88 < count ? &args[88] : &KIN_NIL;
+    KinValue* k_last_arg89 = 89 < count ? &args[89] : &KIN_NIL;
+    KinValue* k_last_arg90 = 90 < count ? &args[90] : &KIN_NIL;
+    KinValue* k_last_arg91 = 91 < count ? &args[91] : &KIN_NIL;
+    KinValue* k_last_arg92 = 92 < count ? &args[92] : &KIN_NIL;
+    KinValue* k_last_arg93 = 93 < count ? &args[93] : &KIN_NIL;
+    KinValue* k_last_arg94 = 94 < count ? &args[94] : &KIN_NIL;
+    KinValue* k_last_arg95 = 95 < count ? &args[95] : &KIN_NIL;
+    KinValue* k_last_arg96 = 96 < count ? &args[96] : &KIN_NIL;
+    KinValue* k_last_arg97 = 97 < count ? &args[97] : &KIN_NIL;
+    KinValue* k_last_arg98 = 98 < count ? &args[98] : &KIN_NIL;
+    KinValue* k_last_arg99 = 99 < count ? &args[99] : &KIN_NIL;
+    KinValue* k_last_arg100 = 100 < count ? &args[100] : &KIN_NIL;
+    KinValue* k_last_arg101 = 101 < count ? &args[101] : &KIN_NIL;
+    KinValue* k_last_arg102 = 102 < count ? &args[102] : &KIN_NIL;
+    KinValue* k_last_arg103 = 103 < count ? &args[103] : &KIN_NIL;
+    KinValue* k_last_arg104 = 104 < count ? &args[104] : &KIN_NIL;
+    KinValue* k_last_arg105 = 105 < count ? &args[105] : &KIN_NIL;
+    KinValue* k_last_arg106 = 106 < count ? &args[106] : &KIN_NIL;
+    KinValue* k_last_arg107 = 107 < count ? &args[107] : &KIN_NIL;
+    KinValue* k_last_arg108 = 108 < count ? &args[108] : &KIN_NIL;
+    KinValue* k_last_arg109 = 109 < count ? &args[109] : &KIN_NIL;
+    KinValue* k_last_arg110 = 110 < count ? &args[110] : &KIN_NIL;
+    KinValue* k_last_arg111 = 111 < count ? &args[111] : &KIN_NIL;
+    KinValue* k_last_arg112 = 112 < count ? &args[112] : &KIN_NIL;
+    KinValue* k_last_arg113 = 113 < count ? &args[113] : &KIN_NIL;
+    KinValue* k_last_arg114 = 114 < count ? &args[114] : &KIN_NIL;
+    KinValue* k_last_arg115 = 115 < count ? &args[115] : &KIN_NIL;
+    KinValue* k_last_arg116 = 116 < count ? &args[116] : &KIN_NIL;
+    KinValue* k_last_arg117 = 117 < count ? &args[117] : &KIN_NIL;
+    KinValue* k_last_arg118 = 118 < count ? &args[118] : &KIN_NIL;
+    KinValue* k_last_arg119 = 119 < count ? &args[119] : &KIN_NIL;
+    KinValue* k_last_arg120 = 120 < count ? &args[120] : &KIN_NIL;
+    KinValue* k_last_arg121 = 121 < count ? &args[121] : &KIN_NIL;
+    KinValue* k_last_arg122 = 122 < count ? &args[122] : &KIN_NIL;
+    KinValue* k_last_arg123 = 123 < count ? &args[123] : &KIN_NIL;
+    KinValue* k_last_arg124 = 124 < count ? &args[124] : &KIN_NIL;
+    KinValue* k_last_arg125 = 125 < count ? &args[125] : &KIN_NIL;
+    KinValue* k_last_arg126 = 126 < count ? &args[126] : &KIN_NIL;
+    KinValue* k_last_arg127 = 127 < count ? &args[127] : &KIN_NIL;
+    KinValue* k_last_arg128 = 128 < count ? &args[128] : &KIN_NIL;
+    KinValue* k_last_arg129 = 129 < count ? &args[129] : &KIN_NIL;
+    KinValue* k_last_arg130 = 130 < count ? &args[130] : &KIN_NIL;
+    KinValue* k_last_arg131 = 131 < count ? &args[131] : &KIN_NIL;
+    KinValue* k_last_arg132 = 132 < count ? &args[132] : &KIN_NIL;
+    KinValue* k_last_arg133 = 133 < count ? &args[133] : &KIN_NIL;
+    KinValue* k_last_arg134 = 134 < count ? &args[134] : &KIN_NIL;
+    KinValue* k_last_arg135 = 135 < count ? &args[135] : &KIN_NIL;
+    KinValue* k_last_arg136 = 136 < count ? &args[136] : &KIN_NIL;
+    KinValue* k_last_arg137 = 137 < count ? &args[137] : &KIN_NIL;
+    KinValue* k_last_arg138 = 138 < count ? &args[138] : &KIN_NIL;
+    KinValue* k_last_arg139 = 139 < count ? &args[139] : &KIN_NIL;
+    KinValue* k_last_arg140 = 140 < count ? &args[140] : &KIN_NIL;
+    KinValue* k_last_arg141 = 141 < count ? &args[141] : &KIN_NIL;
+    KinValue* k_last_arg142 = 142 < count ? &args[142] : &KIN_NIL;
+    KinValue* k_last_arg143 = 143 < count ? &args[143] : &KIN_NIL;
+    KinValue* k_last_arg144 = 144 < count ? &args[144] : &KIN_NIL;
+    KinValue* k_last_arg145 = 145 < count ? &args[145] : &KIN_NIL;
+    KinValue* k_last_arg146 = 146 < count ? &args[146] : &KIN_NIL;
+    KinValue* k_last_arg147 = 147 < count ? &args[147] : &KIN_NIL;
+    KinValue* k_last_arg148 = 148 < count ? &args[148] : &KIN_NIL;
+    KinValue* k_last_arg149 = 149 < count ? &args[149] : &KIN_NIL;
+    KinValue* k_last_arg150 = 150 < count ? &args[150] : &KIN_NIL;
+    KinValue* k_last_arg151 = 151 < count ? &args[151] : &KIN_NIL;
+    KinValue* k_last_arg152 = 152 < count ? &args[152] : &KIN_NIL;
+    KinValue* k_last_arg153 = 153 < count ? &args[153] : &KIN_NIL;
+    KinValue* k_last_arg154 = 154 < count ? &args[154] : &KIN_NIL;
+    KinValue* k_last_arg155 = 155 < count ? &args[155] : &KIN_NIL;
+    KinValue* k_last_arg156 = 156 < count ? &args[156] : &KIN_NIL;
+    KinValue* k_last_arg157 = 157 < count ? &args[157] : &KIN_NIL;
+    KinValue* k_last_arg158 = 158 < count ? &args[158] : &KIN_NIL;
+    KinValue* k_last_arg159 = 159 < count ? &args[159] : &KIN_NIL;
+    KinValue* k_last_arg160 = 160 < count ? &args[160] : &KIN_NIL;
+    KinValue* k_last_arg161 = 161 < count ? &args[161] : &KIN_NIL;
+    KinValue* k_last_arg162 = 162 < count ? &args[162] : &KIN_NIL;
+    KinValue* k_last_arg163 = 163 < count ? &args[163] : &KIN_NIL;
+    KinValue* k_last_arg164 = 164 < count ? &args[164] : &KIN_NIL;
+    KinValue* k_last_arg165 = 165 < count ? &args[165] : &KIN_NIL;
+    KinValue* k_last_arg166 = 166 < count ? &args[166] : &KIN_NIL;
+    KinValue* k_last_arg167 = 167 < count ? &args[167] : &KIN_NIL;
+    KinValue* k_last_arg168 = 168 < count ? &args[168] : &KIN_NIL;
+    KinValue* k_last_arg169 = 169 < count ? &args[169] : &KIN_NIL;
+    KinValue* k_last_arg170 = 170 < count ? &args[170] : &KIN_NIL;
+    KinValue* k_last_arg171 = 171 < count ? &args[171] : &KIN_NIL;
+    KinValue* k_last_arg172 = 172 < count ? &args[172] : &KIN_NIL;
+    KinValue* k_last_arg173 = 173 < count ? &args[173] : &KIN_NIL;
+    KinValue* k_last_arg174 = 174 < count ? &args[174] : &KIN_NIL;
+    KinValue* k_last_arg175 = 175 < count ? &args[175] : &KIN_NIL;
+    KinValue* k_last_arg176 = 176 < count ? &args[176] : &KIN_NIL;
+    KinValue* k_last_arg177 = 177 < count ? &args[177] : &KIN_NIL;
+    KinValue* k_last_arg178 = 178 < count ? &args[178] : &KIN_NIL;
+    KinValue* k_last_arg179 = 179 < count ? &args[179] : &KIN_NIL;
+    KinValue* k_last_arg180 = 180 < count ? &args[180] : &KIN_NIL;
+    KinValue* k_last_arg181 = 181 < count ? &args[181] : &KIN_NIL;
+    KinValue* k_last_arg182 = 182 < count ? &args[182] : &KIN_NIL;
+    KinValue* k_last_arg183 = 183 < count ? &args[183] : &KIN_NIL;
+    KinValue* k_last_arg184 = 184 < count ? &args[184] : &KIN_NIL;
+    KinValue* k_last_arg185 = 185 < count ? &args[185] : &KIN_NIL;
+    KinValue* k_last_arg186 = 186 < count ? &args[186] : &KIN_NIL;
+    KinValue* k_last_arg187 = 187 < count ? &args[187] : &KIN_NIL;
+    KinValue* k_last_arg188 = 188 < count ? &args[188] : &KIN_NIL;
+    KinValue* k_last_arg189 = 189 < count ? &args[189] : &KIN_NIL;
+    KinValue* k_last_arg190 = 190 < count ? &args[190] : &KIN_NIL;
+    KinValue* k_last_arg191 = 191 < count ? &args[191] : &KIN_NIL;
+    KinValue* k_last_arg192 = 192 < count ? &args[192] : &KIN_NIL;
+    KinValue* k_last_arg193 = 193 < count ? &args[193] : &KIN_NIL;
+    KinValue* k_last_arg194 = 194 < count ? &args[194] : &KIN_NIL;
+    KinValue* k_last_arg195 = 195 < count ? &args[195] : &KIN_NIL;
+    KinValue* k_last_arg196 = 196 < count ? &args[196] : &KIN_NIL;
+    KinValue* k_last_arg197 = 197 < count ? &args[197] : &KIN_NIL;
+    KinValue* k_last_arg198 = 198 < count ? &args[198] : &KIN_NIL;
+    KinValue* k_last_arg199 = 199 < count ? &args[199] : &KIN_NIL;
+    KinValue* k_last_arg200 = 200 < count ? &args[200] : &KIN_NIL;
+    KinValue* k_last_arg201 = 201 < count ? &args[201] : &KIN_NIL;
+    KinValue* k_last_arg202 = 202 < count ? &args[202] : &KIN_NIL;
+    KinValue* k_last_arg203 = 203 < count ? &args[203] : &KIN_NIL;
+    KinValue* k_last_arg204 = 204 < count ? &args[204] : &KIN_NIL;
+    KinValue* k_last_arg205 = 205 < count ? &args[205] : &KIN_NIL;
+    KinValue* k_last_arg206 = 206 < count ? &args[206] : &KIN_NIL;
+    KinValue* k_last_arg207 = 207 < count ? &args[207] : &KIN_NIL;
+    KinValue* k_last_arg208 = 208 < count ? &args[208] : &KIN_NIL;
+    KinValue* k_last_arg209 = 209 < count ? &args[209] : &KIN_NIL;
+    KinValue* k_last_arg210 = 210 < count ? &args[210] : &KIN_NIL;
+    KinValue* k_last_arg211 = 211 < count ? &args[211] : &KIN_NIL;
+    KinValue* k_last_arg212 = 212 < count ? &args[212] : &KIN_NIL;
+    KinValue* k_last_arg213 = 213 < count ? &args[213] : &KIN_NIL;
+    KinValue* k_last_arg214 = 214 < count ? &args[214] : &KIN_NIL;
+    KinValue* k_last_arg215 = 215 < count ? &args[215] : &KIN_NIL;
+    KinValue* k_last_arg216 = 216 < count ? &args[216] : &KIN_NIL;
+    KinValue* k_last_arg217 = 217 < count ? &args[217] : &KIN_NIL;
+    KinValue* k_last_arg218 = 218 < count ? &args[218] : &KIN_NIL;
+    KinValue* k_last_arg219 = 219 < count ? &args[219] : &KIN_NIL;
+    KinValue* k_last_arg220 = 220 < count ? &args[220] : &KIN_NIL;
+    KinValue* k_last_arg221 = 221 < count ? &args[221] : &KIN_NIL;
+    KinValue* k_last_arg222 = 222 < count ? &args[222] : &KIN_NIL;
+    KinValue* k_last_arg223 = 223 < count ? &args[223] : &KIN_NIL;
+    KinValue* k_last_arg224 = 224 < count ? &args[224] : &KIN_NIL;
+    KinValue* k_last_arg225 = 225 < count ? &args[225] : &KIN_NIL;
+    KinValue* k_last_arg226 = 226 < count ? &args[226] : &KIN_NIL;
+    KinValue* k_last_arg227 = 227 < count ? &args[227] : &KIN_NIL;
+    KinValue* k_last_arg228 = 228 < count ? &args[228] : &KIN_NIL;
+    KinValue* k_last_arg229 = 229 < count ? &args[229] : &KIN_NIL;
+    KinValue* k_last_arg230 = 230 < count ? &args[230] : &KIN_NIL;
+    KinValue* k_last_arg231 = 231 < count ? &args[231] : &KIN_NIL;
+    KinValue* k_last_arg232 = 232 < count ? &args[232] : &KIN_NIL;
+    KinValue* k_last_arg233 = 233 < count ? &args[233] : &KIN_NIL;
+    KinValue* k_last_arg234 = 234 < count ? &args[234] : &KIN_NIL;
+    KinValue* k_last_arg235 = 235 < count ? &args[235] : &KIN_NIL;
+    KinValue* k_last_arg236 = 236 < count ? &args[236] : &KIN_NIL;
+    KinValue* k_last_arg237 = 237 < count ? &args[237] : &KIN_NIL;
+    KinValue* k_last_arg238 = 238 < count ? &args[238] : &KIN_NIL;
+    KinValue* k_last_arg239 = 239 < count ? &args[239] : &KIN_NIL;
+    KinValue* k_last_arg240 = 240 < count ? &args[240] : &KIN_NIL;
+    KinValue* k_last_arg241 = 241 < count ? &args[241] : &KIN_NIL;
+    KinValue* k_last_arg242 = 242 < count ? &args[242] : &KIN_NIL;
+    KinValue* k_last_arg243 = 243 < count ? &args[243] : &KIN_NIL;
+    KinValue* k_last_arg244 = 244 < count ? &args[244] : &KIN_NIL;
+    KinValue* k_last_arg245 = 245 < count ? &args[245] : &KIN_NIL;
+    KinValue* k_last_arg246 = 246 < count ? &args[246] : &KIN_NIL;
+    KinValue* k_last_arg247 = 247 < count ? &args[247] : &KIN_NIL;
+    KinValue* k_last_arg248 = 248 < count ? &args[248] : &KIN_NIL;
+    KinValue* k_last_arg249 = 249 < count ? &args[249] : &KIN_NIL;
+    KinValue* k_last_arg250 = 250 < count ? &args[250] : &KIN_NIL;
+    KinValue* k_last_arg251 = 251 < count ? &args[251] : &KIN_NIL;
+    KinValue* k_last_arg252 = 252 < count ? &args[252] : &KIN_NIL;
+    KinValue* k_last_arg253 = 253 < count ? &args[253] : &KIN_NIL;
+    KinValue* k_last_arg254 = 254 < count ? &args[254] : &KIN_NIL;
+    KinValue* k_last_arg255 = 255 < count ? &args[255] : &KIN_NIL;
+    KinValue* k_last_arg256 = 256 < count ? &args[256] : &KIN_NIL;
+    KinValue* k_last_arg257 = 257 < count ? &args[257] : &KIN_NIL;
+    KinValue* k_last_arg258 = 258 < count ? &args[258] : &KIN_NIL;
+    KinValue* k_last_arg259 = 259 < count ? &args[259] : &KIN_NIL;
+    KinValue* k_last_arg260 = 260 < count ? &args[260] : &KIN_NIL;
+    KinValue* k_last_arg261 = 261 < count ? &args[261] : &KIN_NIL;
+    KinValue* k_last_arg262 = 262 < count ? &args[262] : &KIN_NIL;
+    KinValue* k_last_arg263 = 263 < count ? &args[263] : &KIN_NIL;
+    KinValue* k_last_arg264 = 264 < count ? &args[264] : &KIN_NIL;
+    KinValue* k_last_arg265 = 265 < count ? &args[265] : &KIN_NIL;
+    KinValue* k_last_arg266 = 266 < count ? &args[266] : &KIN_NIL;
+    KinValue* k_last_arg267 = 267 < count ? &args[267] : &KIN_NIL;
+    KinValue* k_last_arg268 = 268 < count ? &args[268] : &KIN_NIL;
+    KinValue* k_last_arg269 = 269 < count ? &args[269] : &KIN_NIL;
+    KinValue* k_last_arg270 = 270 < count ? &args[270] : &KIN_NIL;
+    KinValue* k_last_arg271 = 271 < count ? &args[271] : &KIN_NIL;
+    KinValue* k_last_arg272 = 272 < count ? &args[272] : &KIN_NIL;
+    KinValue* k_last_arg273 = 273 < count ? &args[273] : &KIN_NIL;
+    KinValue* k_last_arg274 = 274 < count ? &args[274] : &KIN_NIL;
+    KinValue* k_last_arg275 = 275 < count ? &args[275] : &KIN_NIL;
+    KinValue* k_last_arg276 = 276 < count ? &args[276] : &KIN_NIL;
+    KinValue* k_last_arg277 = 277 < count ? &args[277] : &KIN_NIL;
+    KinValue* k_last_arg278 = 278 < count ? &args[278] : &KIN_NIL;
+    KinValue* k_last_arg279 = 279 < count ? &args[279] : &KIN_NIL;
+    KinValue* k_last_arg280 = 280 < count ? &args[280] : &KIN_NIL;
+    KinValue* k_last_arg281 = 281 < count ? &args[281] : &KIN_NIL;
+    KinValue* k_last_arg282 = 282 < count ? &args[282] : &KIN_NIL;
+    KinValue* k_last_arg283 = 283 < count ? &args[283] : &KIN_NIL;
+    KinValue* k_last_arg284 = 284 < count ? &args[284] : &KIN_NIL;
+    KinValue* k_last_arg285 = 285 < count ? &args[285] : &KIN_NIL;
+    KinValue* k_last_arg286 = 286 < count ? &args[286] : &KIN_NIL;
+    KinValue* k_last_arg287 = 287 < count ? &args[287] : &KIN_NIL;
+    KinValue* k_last_arg288 = 288 < count ? &args[288] : &KIN_NIL;
+    KinValue* k_last_arg289 = 289 < count ? &args[289] : &KIN_NIL;
+    KinValue* k_last_arg290 = 290 < count ? &args[290] : &KIN_NIL;
+    KinValue* k_last_arg291 = 291 < count ? &args[291] : &KIN_NIL;
+    KinValue* k_last_arg292 = 292 < count ? &args[292] : &KIN_NIL;
+    KinValue* k_last_arg293 = 293 < count ? &args[293] : &KIN_NIL;
+    KinValue* k_last_arg294 = 294 < count ? &args[294] : &KIN_NIL;
+    KinValue* k_last_arg295 = 295 < count ? &args[295] : &KIN_NIL;
+    KinValue* k_last_arg296 = 296 < count ? &args[296] : &KIN_NIL;
+    KinValue* k_last_arg297 = 297 < count ? &args[297] : &KIN_NIL;
+    KinValue* k_last_arg298 = 298 < count ? &args[298] : &KIN_NIL;
+    KinValue* k_last_arg299 = 299 < count ? &args[299] : &KIN_NIL;
+    return *k_last_arg299;
+}
+
+int main(int argc, char** argv) {
+    KinValue arg = kin_call(new_function(&k_last), 300, (KinValue[]) { new_int(1), new_int(2), new_int(3), new_int(4), new_int(5), new_int(6), new_int(7), new_int(8), new_int(9), new_int(10), new_int(11), new_int(12), new_int(13), new_int(14), new_int(15), new_int(16), new_int(17), new_int(18), new_int(19), new_int(20), new_int(21), new_int(22), new_int(23), new_int(24), new_int(25), new_int(26), new_int(27), new_int(28), new_int(29), new_int(30), new_int(31), new_int(32), new_int(33), new_int(34), new_int(35), new_int(36), new_int(37), new_int(38), new_int(39), new_int(40), new_int(41), new_int(42), new_int(43), new_int(44), new_int(45), new_int(46), new_int(47), new_int(48), new_int(49), new_int(50), new_int(51), new_int(52), new_int(53), new_int(54), new_int(55), new_int(56), new_int(57), new_int(58), new_int(59), new_int(60), new_int(61), new_int(62), new_int(63), new_int(64), new_int(65), new_int(66), new_int(67), new_int(68), new_int(69), new_int(70), new_int(71), new_int(72), new_int(73), new_int(74), new_int(75), new_int(76), new_int(77), new_int(78), new_int(79), new_int(80), new_int(81), new_int(82), new_int(83), new_int(84), new_int(85), new_int(86), new_int(87), new_int(88), new_int(89), new_int(90), new_int(91), new_int(92), new_int(93), new_int(94), new_int(95), new_int(96), new_int(97), new_int(98), new_int(99), new_int(100), new_int(101), new_int(102), new_int(103), new_int(104), new_int(105), new_int(106), new_int(107), new_int(108), new_int(109), new_int(110), new_int(111), new_int(112), new_int(113), new_int(114), new_int(115), new_int(116), new_int(117), new_int(118), new_int(119), new_int(120), new_int(121), new_int(122), new_int(123), new_int(124), new_int(125), new_int(126), new_int(127), new_int(128), new_int(129), new_int(130), new_int(131), new_int(132), new_int(133), new_int(134), new_int(135), new_int(136), new_int(137), new_int(138), new_int(139), new_int(140), new_int(141), new_int(142), new_int(143), new_int(144), new_int(145), new_int(146), new_int(147), new_int(148), new_int(149), new_int(150), new_int(151), new_int(152), new_int(153), new_int(154), new_int(155), new_int(156), new_int(157), new_int(158), new_int(159), new_int(160), new_int(161), new_int(162), new_int(163), new_int(164), new_int(165), new_int(166), new_int(167), new_int(168), new_int(169), new_int(170), new_int(171), new_int(172), new_int(173), new_int(174), new_int(175), new_int(176), new_int(177), new_int(178), new_int(179), new_int(180), new_int(181), new_int(182), new_int(183), new_int(184), new_int(185), new_int(186), new_int(187), new_int(188), new_int(189), new_int(190), new_int(191), new_int(192), new_int(193), new_int(194), new_int(195), new_int(196), new_int(197), new_int(198), new_int(199), new_int(200), new_int(201), new_int(202), new_int(203), new_int(204), new_int(205), new_int(206), new_int(207), new_int(208), new_int(209), new_int(210), new_int(211), new_int(212), new_int(213), new_int(214), new_int(215), new_int(216), new_int(217), new_int(218), new_int(219), new_int(220), new_int(221), new_int(222), new_int(223), new_int(224), new_int(225), new_int(226), new_int(227), new_int(228), new_int(229), new_int(230), new_int(231), new_int(232), new_int(233), new_int(234), new_int(235), new_int(236), new_int(237), new_int(238), new_int(239), new_int(240), new_int(241), new_int(242), new_int(243), new_int(244), new_int(245), new_int(246), new_int(247), new_int(248), new_int(249), new_int(250), new_int(251), new_int(252), new_int(253), new_int(254), new_int(255), new_int(256), new_int(257), new_int(258), new_int(259), new_int(260), new_int(261), new_int(262), new_int(263), new_int(264), new_int(265), new_int(266), new_int(267), new_int(268), new_int(269), new_int(270), new_int(271), new_int(272), new_int(273), new_int(274), new_int(275), new_int(276), new_int(277), new_int(278), new_int(279), new_int(280), new_int(281), new_int(282), new_int(283), new_int(284), new_int(285), new_int(286), new_int(287), new_int(288), new_int(289), new_int(290), new_int(291), new_int(292), new_int(293), new_int(294), new_int(295), new_int(296), new_int(297), new_int(298), new_int(299), new_int(300) }, "main 3:10");
+    kin_call(new_function(&kin_println), 1, &arg, "main 3:1");
+    return 0;
+}
+
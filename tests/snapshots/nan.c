@@ -0,0 +1,30 @@
+#include "kin.h"
+
+KinValue k_id(uint32_t count, KinValue* args);
+
+KinValue k_id(uint32_t count, KinValue* args) {
+    KinValue* k_id_arg0 = 0 < count ? &args[0] : &KIN_NIL;
+    return *k_id_arg0;
+}
+
+int main(int argc, char** argv) {
+    KinValue k_nan = kin_call_bin_op(kin_div, kin_call(new_function(&k_id), 1, &new_real(0), "main 2:7"), kin_call(new_function(&k_id), 1, &new_real(0), "main 2:16"), "main 2:14");
+    KinValue arg = kin_call_bin_op(kin_lt, k_nan, new_real(1), "main 3:14");
+    kin_call(new_function(&kin_println), 1, &arg, "main 3:1");
+    KinValue arg_2 = kin_call_bin_op(kin_le, k_nan, new_real(1), "main 4:14");
+    kin_call(new_function(&kin_println), 1, &arg_2, "main 4:1");
+    KinValue arg_3 = kin_call_bin_op(kin_gt, k_nan, new_real(1), "main 5:14");
+    kin_call(new_function(&kin_println), 1, &arg_3, "main 5:1");
+    KinValue arg_4 = kin_call_bin_op(kin_ge, k_nan, new_real(1), "main 6:14");
+    kin_call(new_function(&kin_println), 1, &arg_4, "main 6:1");
+    KinValue arg_5 = kin_call_bin_op(kin_le, new_int(1), k_nan, "main 7:12");
+    kin_call(new_function(&kin_println), 1, &arg_5, "main 7:1");
+    KinValue arg_6 = kin_call_bin_op(kin_ge, k_nan, k_nan, "main 8:14");
+    kin_call(new_function(&kin_println), 1, &arg_6, "main 8:1");
+    KinValue arg_7 = kin_eq(k_nan, k_nan);
+    kin_call(new_function(&kin_println), 1, &arg_7, "main 9:1");
+    KinValue arg_8 = kin_neq(k_nan, k_nan);
+    kin_call(new_function(&kin_println), 1, &arg_8, "main 10:1");
+    return 0;
+}
+
@@ -0,0 +1,10 @@
+#include "kin.h"
+
+
+int main(int argc, char** argv) {
+    kin_call(new_function(&kin_println), 1, &new_string("before", 6), "main 1:1");
+    kin_call(new_function(&kin_panic), 1, &new_string("oh no", 5), "main 2:1");
+    kin_call(new_function(&kin_println), 1, &new_string("after", 5), "main 3:1");
+    return 0;
+}
+
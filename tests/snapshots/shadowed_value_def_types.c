@@ -0,0 +1,19 @@
+#include "kin.h"
+
+KinValue k_id(uint32_t count, KinValue* args);
+
+KinValue k_id(uint32_t count, KinValue* args) {
+    KinValue* k_id_arg0 = 0 < count ? &args[0] : &KIN_NIL;
+    return *k_id_arg0;
+}
+
+int main(int argc, char** argv) {
+    KinValue arg = kin_call(new_function(&k_id), 1, &new_int(1), "main 2:10");
+    kin_call(new_function(&kin_println), 1, &arg, "main 2:1");
+    KinValue k_y = kin_call(new_function(&k_id), 1, &new_string("s", 1), "main 3:5");
+    kin_call(new_function(&kin_println), 1, &new_int(5), "main 8:1");
+    KinValue arg_2 = (k_y.type == Int ? new_int(k_y.data.Int + 1) : kin_call_bin_op(kin_add, k_y, new_int(1), "main 9:12"));
+    kin_call(new_function(&kin_println), 1, &arg_2, "main 9:1");
+    return 0;
+}
+
@@ -0,0 +1,22 @@
+#include "kin.h"
+
+KinValue k_id(uint32_t count, KinValue* args);
+
+KinValue k_id(uint32_t count, KinValue* args) {
+    KinValue* k_id_arg0 = 0 < count ? &args[0] : &KIN_NIL;
+    return *k_id_arg0;
+}
+
+int main(int argc, char** argv) {
+    KinValue k_y = kin_call(new_function(&k_id), 1, &new_int(1), "main 2:5");
+    KinValue k_y_2 = kin_call(new_function(&k_id), 1, &new_int(5), "main 4:9");
+    KinValue k_x = new_int(k_y_2.data.Int + 1);
+    kin_call(new_function(&kin_println), 1, &k_x, "main 7:1");
+    kin_call(new_function(&kin_println), 1, &k_y, "main 8:1");
+    KinValue k_z = kin_call(new_function(&k_id), 1, &new_int(5), "main 11:9");
+    KinValue k_w = k_z;
+    kin_call(new_function(&kin_println), 1, &k_w, "main 14:1");
+    kin_call(new_function(&kin_println), 1, &new_string("s", 1), "main 15:1");
+    return 0;
+}
+
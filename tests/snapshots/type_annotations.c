@@ -0,0 +1,47 @@
+#include "kin.h"
+
+KinValue k_anon(uint32_t count, KinValue* args);
+KinValue k_apply(uint32_t count, KinValue* args);
+KinValue k_area(uint32_t count, KinValue* args);
+KinValue k_scale(uint32_t count, KinValue* args);
+
+KinValue k_anon(uint32_t count, KinValue* args) {
+    KinValue* k_anon_arg0 = 0 < count ? &args[0] : &KIN_NIL;
+    kin_check_type(*k_anon_arg0, 16, "string", "param `word` of a closure");
+    return kin_call(new_function(&kin_println), 1, &*k_anon_arg0, "closure 6:23");
+}
+
+KinValue k_apply(uint32_t count, KinValue* args) {
+    KinValue* k_apply_arg0 = 0 < count ? &args[0] : &KIN_NIL;
+    KinValue* k_apply_arg1 = 1 < count ? &args[1] : &KIN_NIL;
+    return kin_call(*k_apply_arg0, 1, &*k_apply_arg1, "apply 5:13");
+}
+
+KinValue k_area(uint32_t count, KinValue* args) {
+    KinValue* k_area_arg0 = 0 < count ? &args[0] : &KIN_NIL;
+    kin_check_type(*k_area_arg0, 4, "int", "param `w` of `area`");
+    KinValue* k_area_arg1 = 1 < count ? &args[1] : &KIN_NIL;
+    kin_check_type(*k_area_arg1, 4, "int", "param `h` of `area`");
+    return new_int((*k_area_arg0).data.Int * (*k_area_arg1).data.Int);
+}
+
+KinValue k_scale(uint32_t count, KinValue* args) {
+    KinValue* k_scale_arg0 = 0 < count ? &args[0] : &KIN_NIL;
+    kin_check_type(*k_scale_arg0, 4, "int", "param `x` of `scale`");
+    KinValue* k_scale_arg1 = 1 < count ? &args[1] : &KIN_NIL;
+    kin_check_type(*k_scale_arg1, 8, "real", "param `factor` of `scale`");
+    return kin_call_bin_op(kin_mul, *k_scale_arg0, *k_scale_arg1, "scale 1:31");
+}
+
+int main(int argc, char** argv) {
+    KinValue arg = kin_call(new_function(&k_scale), 2, (KinValue[]) { new_int(3), new_real(1.5) }, "main 2:10");
+    kin_call(new_function(&kin_println), 1, &arg, "main 2:1");
+    KinValue k_count = kin_call(new_function(&k_scale), 2, (KinValue[]) { new_int(2), new_real(2) }, "main 3:15");
+    kin_check_type(k_count, 8, "real", "`count`");
+    kin_call(new_function(&kin_println), 1, &k_count, "main 4:1");
+    KinValue arg_2 = new_function(&k_anon);
+    kin_call(new_function(&k_apply), 2, (KinValue[]) { arg_2, new_string("typed", 5) }, "main 6:1");
+    kin_call(new_function(&k_apply), 2, (KinValue[]) { new_function(&k_area), new_string("wide", 4) }, "main 8:1");
+    return 0;
+}
+
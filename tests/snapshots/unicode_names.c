@@ -0,0 +1,85 @@
+#include "kin.h"
+
+KinValue k_double___u1f600_(uint32_t count, KinValue* args);
+KinValue k_half(uint32_t count, KinValue* args);
+KinValue k_id(uint32_t count, KinValue* args);
+KinValue k_min(uint32_t count, KinValue* args);
+KinValue k_outer(uint32_t count, KinValue* args);
+KinValue k_outer_inner(uint32_t count, KinValue* args, KinValue* captures);
+
+KinValue k_double___u1f600_(uint32_t count, KinValue* args) {
+    KinValue* k_double___u1f600__arg0 = 0 < count ? &args[0] : &KIN_NIL;
+    return new_int((*k_double___u1f600__arg0).data.Int * 2);
+}
+
+KinValue k_half(uint32_t count, KinValue* args) {
+    KinValue* k_half_arg0 = 0 < count ? &args[0] : &KIN_NIL;
+    kin_check_type(*k_half_arg0, 4, "int", "param `ﾞx` of `half`");
+    return kin_call_bin_op(kin_div, *k_half_arg0, new_int(2), "half 35:19");
+}
+
+KinValue k_id(uint32_t count, KinValue* args) {
+    KinValue* k_id_arg0 = 0 < count ? &args[0] : &KIN_NIL;
+    return *k_id_arg0;
+}
+
+KinValue k_min(uint32_t count, KinValue* args) {
+    KinValue* k_min_arg0 = 0 < count ? &args[0] : &KIN_NIL;
+    KinValue* k_min_arg1 = 1 < count ? &args[1] : &KIN_NIL;
+    KinValue temp = kin_call_bin_op(kin_lt, *k_min_arg0, *k_min_arg1, "min 12:13");
+    if (temp.data.Bool) {
+        temp = *k_min_arg0;
+    }
+    KinValue temp_2 = temp;
+    if (!kin_is_true(temp_2)) {
+        temp_2 = *k_min_arg1;
+    }
+    return temp_2;
+}
+
+KinValue k_outer(uint32_t count, KinValue* args) {
+    KinValue* k_outer_arg0 = 0 < count ? &args[0] : &KIN_NIL;
+    KinValue k_outer_temp = new_int((*k_outer_arg0).data.Int + 1);
+    KinValue k_outer_inner_captures[1];
+    k_outer_inner_captures[0] = k_outer_temp;
+    KinValue k_outer_inner_closure = new_closure(&k_outer_inner, k_outer_inner_captures);
+    KinValue arg = kin_call(k_outer_inner_closure, 1, &*k_outer_arg0, "outer 19:14");
+    return kin_call(new_function(&kin_println), 1, &arg, "outer 19:5");
+}
+
+KinValue k_outer_inner(uint32_t count, KinValue* args, KinValue* captures) {
+    KinValue* k_outer_inner_arg0 = 0 < count ? &args[0] : &KIN_NIL;
+    return new_int((*k_outer_inner_arg0).data.Int + captures[0].data.Int);
+}
+
+int main(int argc, char** argv) {
+    KinValue k___u1f600_ = kin_call(new_function(&k_id), 1, &new_int(1), "main 3:5");
+    KinValue k___u3c0_ = kin_call(new_function(&k_id), 1, &new_real(3.14), "main 4:5");
+    KinValue arg = (k___u1f600_.type == Int ? new_int(k___u1f600_.data.Int + 1) : kin_call_bin_op(kin_add, k___u1f600_, new_int(1), "main 5:12"));
+    kin_call(new_function(&kin_println), 1, &arg, "main 5:1");
+    kin_call(new_function(&kin_println), 1, &k___u3c0_, "main 6:1");
+    KinValue arg_2 = kin_call(new_function(&k_double___u1f600_), 1, &new_int(21), "main 9:10");
+    kin_call(new_function(&kin_println), 1, &arg_2, "main 9:1");
+    KinValue k_new_int = kin_call(new_function(&k_id), 1, &new_int(7), "main 11:11");
+    KinValue k_printf = kin_call(new_function(&k_id), 1, &new_int(9), "main 13:10");
+    KinValue arg_3 = kin_call(new_function(&k_min), 2, (KinValue[]) { k_new_int, k_printf }, "main 14:10");
+    kin_call(new_function(&kin_println), 1, &arg_3, "main 14:1");
+    kin_call(new_function(&k_outer), 1, &new_int(1), "main 21:1");
+    KinValue k_inner = kin_call(new_function(&k_id), 1, &new_int(100), "main 22:9");
+    kin_call(new_function(&kin_println), 1, &k_inner, "main 23:1");
+    KinValue k_x = kin_call(new_function(&k_id), 1, &new_int(1), "main 25:5");
+    KinValue k_x_2 = kin_call(new_function(&k_id), 1, &new_int(2), "main 26:7");
+    KinValue k_x_3 = kin_call(new_function(&k_id), 1, &new_int(3), "main 27:5");
+    KinValue arg_4 = kin_call_bin_op(kin_add, k_x_3, k_x_2, "main 28:12");
+    kin_call(new_function(&kin_println), 1, &arg_4, "main 28:1");
+    KinValue k_a__u3c0_b = kin_call(new_function(&k_id), 1, &new_int(4), "main 30:7");
+    KinValue k_a__u5f__u3c0_b = kin_call(new_function(&k_id), 1, &new_int(5), "main 31:13");
+    KinValue arg_5 = kin_call_bin_op(kin_add, k_a__u3c0_b, k_a__u5f__u3c0_b, "main 32:14");
+    kin_call(new_function(&kin_println), 1, &arg_5, "main 32:1");
+    KinValue k___uff76___uff9e_ = kin_call(new_function(&k_id), 1, &new_int(6), "main 34:11");
+    kin_check_type(k___uff76___uff9e_, 4, "int", "`ｶﾞ`");
+    KinValue arg_6 = kin_call(new_function(&k_half), 1, &k___uff76___uff9e_, "main 36:10");
+    kin_call(new_function(&kin_println), 1, &arg_6, "main 36:1");
+    return 0;
+}
+
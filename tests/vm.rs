@@ -0,0 +1,143 @@
+//! Tests for the bytecode VM
+//!
+//! Each valid fixture in `tests/fixtures` is compiled to bytecode, written to `.kinc` form
+//! and read back, then run and compared with the output of the C backend.
+
+mod common;
+
+use common::Run;
+use kin::{
+    bytecode::{CaptureFrom, Function, Op, Program},
+    vm, Compiler,
+};
+
+#[test]
+fn fixtures_match_c_output() {
+    let compiler = Compiler::new();
+    common::check_fixtures(|name, source| {
+        let items = compiler.check(source).unwrap();
+        let program = compiler.bytecode(items);
+
+        let mut kinc = Vec::new();
+        program.write_to(&mut kinc).unwrap();
+        let read = Program::read_from(kinc.as_slice()).unwrap();
+        assert_eq!(program, read, "{} changed when read back", name);
+
+        let mut output = Vec::new();
+        let result = vm::run(&read, &mut output);
+        Run {
+            output: String::from_utf8(output).unwrap(),
+            finished: result.is_ok(),
+            details: format!("{:?}", result),
+        }
+    });
+}
+
+#[test]
+fn rejects_bad_kinc() {
+    assert!(Program::read_from(&b"not bytecode"[..]).is_err());
+    let program = Compiler::new().bytecode(Compiler::new().check("println 1").unwrap());
+    let mut kinc = Vec::new();
+    program.write_to(&mut kinc).unwrap();
+    kinc.truncate(kinc.len() - 1);
+    assert!(Program::read_from(kinc.as_slice()).is_err());
+}
+
+#[test]
+fn rejects_programs_that_would_crash_the_vm() {
+    let function = |params, locals, captures, code| Function {
+        name: "f".into(),
+        params,
+        locals,
+        captures,
+        code,
+    };
+    let program = |functions| Program {
+        constants: Vec::new(),
+        functions,
+        main: 0,
+    };
+    let bad = [
+        // Pops from an empty stack
+        program(vec![function(
+            0,
+            0,
+            vec![],
+            vec![Op::Pop, Op::Nil, Op::Return],
+        )]),
+        // Calls with more arguments than were pushed
+        program(vec![function(
+            0,
+            0,
+            vec![],
+            vec![Op::Nil, Op::Call(3), Op::Return],
+        )]),
+        // Reaches the same op with different stack depths
+        program(vec![function(
+            0,
+            0,
+            vec![],
+            vec![Op::True, Op::JumpIfTruthy(4), Op::Nil, Op::Nil, Op::Return],
+        )]),
+        // Captures a local the making frame does not have
+        program(vec![
+            function(0, 0, vec![], vec![Op::Closure(1), Op::Return]),
+            function(0, 0, vec![CaptureFrom::Local(5)], vec![Op::Nil, Op::Return]),
+        ]),
+        // Captures from a frame with no captures
+        program(vec![
+            function(0, 0, vec![], vec![Op::Closure(1), Op::Return]),
+            function(
+                0,
+                0,
+                vec![CaptureFrom::Capture(0)],
+                vec![Op::Nil, Op::Return],
+            ),
+        ]),
+        // Asks for far more locals than it sets
+        program(vec![function(
+            0,
+            u32::MAX,
+            vec![],
+            vec![Op::Nil, Op::Return],
+        )]),
+        // Starts in a function that expects captures
+        program(vec![function(
+            0,
+            0,
+            vec![CaptureFrom::Current],
+            vec![Op::Capture(0), Op::Return],
+        )]),
+    ];
+    for program in bad {
+        let mut kinc = Vec::new();
+        program.write_to(&mut kinc).unwrap();
+        assert!(
+            Program::read_from(kinc.as_slice()).is_err(),
+            "{:?} should be rejected",
+            program
+        );
+    }
+}
+
+#[test]
+fn huge_frames_panic() {
+    let program = Program {
+        constants: Vec::new(),
+        functions: vec![Function {
+            name: "main".into(),
+            params: u32::MAX,
+            locals: u32::MAX,
+            captures: Vec::new(),
+            code: vec![Op::Nil, Op::Return],
+        }],
+        main: 0,
+    };
+    let mut kinc = Vec::new();
+    program.write_to(&mut kinc).unwrap();
+    let read = Program::read_from(kinc.as_slice()).unwrap();
+    let mut output = Vec::new();
+    let result = vm::run(&read, &mut output);
+    assert!(matches!(result, Err(vm::VmError::Panic)));
+    assert_eq!(String::from_utf8(output).unwrap(), "Stack overflow\n");
+}
@@ -0,0 +1,75 @@
+//! Tests for the WebAssembly text backend
+//!
+//! Each valid fixture in `tests/fixtures` is transpiled to WebAssembly text and run with
+//! wasmi, then compared with the output of the C backend. Programs that panic trap
+//! instead of exiting.
+
+mod common;
+
+use common::Run;
+use kin::Compiler;
+use wasmi::{core::F64, Caller, Engine, Extern, Linker, Module, Store};
+
+/// Format a real the way the C runtime does
+fn format_real(r: f64) -> String {
+    let mut s = format!("{:.6}", r);
+    while s.ends_with('0') || s.ends_with('.') {
+        s.pop();
+    }
+    s
+}
+
+/// Run a module's `main`, returning what it printed and whether it finished
+fn run(wat: &str) -> (String, bool) {
+    let wasm = wat::parse_str(wat).unwrap();
+    let engine = Engine::default();
+    let module = Module::new(&engine, &wasm[..]).unwrap();
+    let mut store = Store::new(&engine, String::new());
+    let mut linker = <Linker<String>>::new(&engine);
+    linker
+        .func_wrap(
+            "kin",
+            "write",
+            |mut caller: Caller<'_, String>, ptr: i32, len: i32| {
+                let memory = match caller.get_export("memory") {
+                    Some(Extern::Memory(memory)) => memory,
+                    _ => panic!("module does not export its memory"),
+                };
+                let mut bytes = vec![0; len as usize];
+                memory.read(&caller, ptr as usize, &mut bytes).unwrap();
+                caller.data_mut().push_str(&String::from_utf8_lossy(&bytes));
+            },
+        )
+        .unwrap();
+    linker
+        .func_wrap(
+            "kin",
+            "write_real",
+            |mut caller: Caller<'_, String>, r: F64| {
+                caller.data_mut().push_str(&format_real(r.into()));
+            },
+        )
+        .unwrap();
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .unwrap()
+        .start(&mut store)
+        .unwrap();
+    let main = instance.get_typed_func::<(), ()>(&store, "main").unwrap();
+    let finished = main.call(&mut store, ()).is_ok();
+    (store.into_data(), finished)
+}
+
+#[test]
+fn fixtures_match_c_output() {
+    let compiler = Compiler::new();
+    common::check_fixtures(|_, source| {
+        let items = compiler.check(source).unwrap();
+        let (output, finished) = run(&compiler.transpile_wat(items));
+        Run {
+            output,
+            finished,
+            details: String::new(),
+        }
+    });
+}